@@ -0,0 +1,4 @@
+-- Add presence columns to users table
+ALTER TABLE users
+ADD COLUMN IF NOT EXISTS is_online BOOLEAN NOT NULL DEFAULT false,
+ADD COLUMN IF NOT EXISTS last_seen TIMESTAMP WITH TIME ZONE;
@@ -0,0 +1,31 @@
+-- Create enum for privacy audiences
+CREATE TYPE privacy_visibility AS ENUM ('everyone', 'contacts', 'nobody');
+
+-- Create privacy settings table (missing rows mean everything is visible to everyone)
+CREATE TABLE privacy_settings (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    last_seen privacy_visibility NOT NULL DEFAULT 'everyone',
+    online privacy_visibility NOT NULL DEFAULT 'everyone',
+    avatar privacy_visibility NOT NULL DEFAULT 'everyone',
+    status privacy_visibility NOT NULL DEFAULT 'everyone',
+    read_receipts privacy_visibility NOT NULL DEFAULT 'everyone',
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- Create privacy exceptions table for users hidden from a single setting
+CREATE TABLE privacy_exceptions (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    setting VARCHAR(20) NOT NULL CHECK (setting IN ('last_seen', 'online', 'avatar', 'status', 'read_receipts')),
+    excluded_user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, setting, excluded_user_id)
+);
+
+-- Create indexes
+CREATE INDEX idx_privacy_exceptions_excluded_user_id ON privacy_exceptions(excluded_user_id);
+
+-- Create trigger for privacy settings table
+CREATE TRIGGER update_privacy_settings_updated_at
+    BEFORE UPDATE ON privacy_settings
+    FOR EACH ROW
+    EXECUTE FUNCTION update_updated_at_column();
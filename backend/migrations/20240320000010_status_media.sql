@@ -0,0 +1,6 @@
+-- Link status updates to the uploaded media they show
+ALTER TABLE status_updates
+ADD COLUMN media_id UUID REFERENCES media(id) ON DELETE SET NULL;
+
+-- Create indexes
+CREATE INDEX idx_status_updates_media_id ON status_updates(media_id);
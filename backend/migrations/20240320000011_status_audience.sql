@@ -0,0 +1,60 @@
+-- Create enum for who a status is shared with
+CREATE TYPE status_audience_mode AS ENUM ('contacts', 'except', 'only');
+
+-- Create status audience settings table (missing rows mean all contacts)
+CREATE TABLE status_audience_settings (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    mode status_audience_mode NOT NULL DEFAULT 'contacts',
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- Create status audience lists table; both lists are kept so switching modes keeps them
+CREATE TABLE status_audience_lists (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    list VARCHAR(10) NOT NULL CHECK (list IN ('except', 'only')),
+    member_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, list, member_id)
+);
+
+-- Record the audience a status was posted with
+ALTER TABLE status_updates
+ADD COLUMN audience_mode status_audience_mode NOT NULL DEFAULT 'contacts';
+
+-- Create status recipients table, resolved from the audience at post time
+CREATE TABLE status_recipients (
+    status_id UUID NOT NULL REFERENCES status_updates(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    PRIMARY KEY (status_id, user_id)
+);
+
+-- Existing statuses were shared with mutual contacts
+INSERT INTO status_recipients (status_id, user_id)
+SELECT s.id, theirs.user_id
+FROM status_updates s
+JOIN contacts mine ON mine.user_id = s.user_id
+JOIN contacts theirs ON theirs.user_id = mine.contact_id AND theirs.contact_id = mine.user_id
+WHERE s.expires_at > NOW();
+
+-- Create status replies table keeping a preview of the status a message replies to
+CREATE TABLE status_replies (
+    message_id UUID PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+    status_id UUID REFERENCES status_updates(id) ON DELETE SET NULL,
+    status_author_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    content VARCHAR(100),
+    media_url TEXT,
+    media_type VARCHAR(50),
+    background_color VARCHAR(7),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- Create indexes
+CREATE INDEX idx_status_audience_lists_member_id ON status_audience_lists(member_id);
+CREATE INDEX idx_status_recipients_user_id ON status_recipients(user_id);
+CREATE INDEX idx_status_replies_status_id ON status_replies(status_id);
+
+-- Create trigger for status audience settings table
+CREATE TRIGGER update_status_audience_settings_updated_at
+    BEFORE UPDATE ON status_audience_settings
+    FOR EACH ROW
+    EXECUTE FUNCTION update_updated_at_column();
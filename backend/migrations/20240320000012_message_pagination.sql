@@ -0,0 +1,3 @@
+-- Create index for paging through a conversation by (created_at, id)
+CREATE INDEX idx_messages_receiver_created_at_id ON messages(receiver_id, created_at DESC, id DESC);
+CREATE INDEX idx_messages_sender_created_at_id ON messages(sender_id, created_at DESC, id DESC);
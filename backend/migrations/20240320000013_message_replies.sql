@@ -0,0 +1,6 @@
+-- Re-add reply_to_id, which was lost when the messages table was recreated
+ALTER TABLE messages
+ADD COLUMN IF NOT EXISTS reply_to_id UUID REFERENCES messages(id) ON DELETE SET NULL;
+
+-- Create indexes
+CREATE INDEX IF NOT EXISTS idx_messages_reply_to_id ON messages(reply_to_id);
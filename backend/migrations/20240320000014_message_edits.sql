@@ -0,0 +1,44 @@
+-- Create message edits table keeping every replaced version of a message
+CREATE TABLE message_edits (
+    id UUID PRIMARY KEY,
+    message_id UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    content TEXT NOT NULL,
+    edited_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- Create message search table; the search vector on messages was lost when the
+-- table was recreated, and keeping it separate leaves the messages row as is
+CREATE TABLE message_search (
+    message_id UUID PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+    search_vector tsvector NOT NULL
+);
+
+INSERT INTO message_search (message_id, search_vector)
+SELECT id, to_tsvector('english', content)
+FROM messages
+WHERE NOT is_deleted;
+
+-- Create indexes
+CREATE INDEX idx_message_edits_message_id ON message_edits(message_id, edited_at);
+CREATE INDEX idx_message_search_vector ON message_search USING GIN(search_vector);
+
+-- Keep the search vector in sync with the latest version of each message
+CREATE OR REPLACE FUNCTION message_search_update()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.is_deleted THEN
+        DELETE FROM message_search WHERE message_id = NEW.id;
+    ELSE
+        INSERT INTO message_search (message_id, search_vector)
+        VALUES (NEW.id, to_tsvector('english', NEW.content))
+        ON CONFLICT (message_id) DO UPDATE SET search_vector = EXCLUDED.search_vector;
+    END IF;
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
+
+-- Create trigger for messages table
+CREATE TRIGGER update_message_search
+    AFTER INSERT OR UPDATE OF content, is_deleted ON messages
+    FOR EACH ROW
+    EXECUTE FUNCTION message_search_update();
@@ -0,0 +1,11 @@
+-- Create hidden messages table for messages a user deleted only for themselves
+CREATE TABLE hidden_messages (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    message_id UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    hidden_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, message_id)
+);
+
+-- Create indexes
+CREATE INDEX idx_hidden_messages_message_id ON hidden_messages(message_id);
+CREATE INDEX idx_media_url ON media(url);
@@ -0,0 +1,29 @@
+-- Create enum for scheduled message states; sent messages are removed
+CREATE TYPE scheduled_message_status AS ENUM ('pending', 'failed');
+
+-- Create scheduled messages table
+CREATE TABLE scheduled_messages (
+    id UUID PRIMARY KEY,  -- Becomes the id of the message once sent
+    sender_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    receiver_id UUID NOT NULL,  -- Can be either user_id or group_id
+    is_group BOOLEAN NOT NULL,
+    content TEXT NOT NULL,
+    media_url TEXT,
+    reply_to_id UUID REFERENCES messages(id) ON DELETE SET NULL,
+    send_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    status scheduled_message_status NOT NULL DEFAULT 'pending',
+    last_error TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    CONSTRAINT scheduled_messages_content_length CHECK (char_length(content) <= 4000)
+);
+
+-- Create indexes
+CREATE INDEX idx_scheduled_messages_due ON scheduled_messages(send_at) WHERE status = 'pending';
+CREATE INDEX idx_scheduled_messages_sender_id ON scheduled_messages(sender_id, send_at);
+
+-- Create trigger for scheduled messages table
+CREATE TRIGGER update_scheduled_messages_updated_at
+    BEFORE UPDATE ON scheduled_messages
+    FOR EACH ROW
+    EXECUTE FUNCTION update_updated_at_column();
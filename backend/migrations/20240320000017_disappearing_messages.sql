@@ -0,0 +1,54 @@
+-- Add disappearing message timers to groups
+ALTER TABLE groups
+ADD COLUMN disappearing_seconds INTEGER CHECK (disappearing_seconds > 0);
+
+-- Create direct chat settings table, one row per pair of users
+CREATE TABLE direct_chat_settings (
+    user_low UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    user_high UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    disappearing_seconds INTEGER CHECK (disappearing_seconds > 0),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_low, user_high),
+    CONSTRAINT direct_chat_settings_ordered CHECK (user_low < user_high)
+);
+
+-- Add system events and expiry to messages
+ALTER TABLE messages
+ADD COLUMN is_system BOOLEAN NOT NULL DEFAULT false,
+ADD COLUMN expires_at TIMESTAMP WITH TIME ZONE;
+
+-- Create indexes
+CREATE INDEX idx_messages_expires_at ON messages(expires_at) WHERE expires_at IS NOT NULL;
+
+-- Stamp new messages with the conversation's timer, whichever path inserts them
+CREATE OR REPLACE FUNCTION stamp_message_expiry()
+RETURNS TRIGGER AS $$
+DECLARE
+    timer INTEGER;
+BEGIN
+    IF NEW.expires_at IS NULL AND NOT NEW.is_system THEN
+        SELECT disappearing_seconds INTO timer FROM groups WHERE id = NEW.receiver_id;
+        IF NOT FOUND THEN
+            SELECT disappearing_seconds INTO timer FROM direct_chat_settings
+            WHERE user_low = LEAST(NEW.sender_id, NEW.receiver_id)
+                AND user_high = GREATEST(NEW.sender_id, NEW.receiver_id);
+        END IF;
+        IF timer IS NOT NULL THEN
+            NEW.expires_at := NEW.created_at + make_interval(secs => timer);
+        END IF;
+    END IF;
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
+
+-- Create trigger for messages table
+CREATE TRIGGER stamp_messages_expires_at
+    BEFORE INSERT ON messages
+    FOR EACH ROW
+    EXECUTE FUNCTION stamp_message_expiry();
+
+-- Create trigger for direct chat settings table
+CREATE TRIGGER update_direct_chat_settings_updated_at
+    BEFORE UPDATE ON direct_chat_settings
+    FOR EACH ROW
+    EXECUTE FUNCTION update_updated_at_column();
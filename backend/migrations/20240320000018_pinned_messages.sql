@@ -0,0 +1,16 @@
+-- Create group permission enum
+CREATE TYPE group_permission AS ENUM ('admins', 'members');
+
+-- Add group permissions
+ALTER TABLE groups
+ADD COLUMN pin_permission group_permission NOT NULL DEFAULT 'admins';
+
+-- Create pinned messages table
+CREATE TABLE pinned_messages (
+    message_id UUID PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+    pinned_by UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    pinned_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- Create indexes
+CREATE INDEX idx_pinned_messages_pinned_at ON pinned_messages(pinned_at DESC);
@@ -0,0 +1,11 @@
+-- Create starred messages table
+CREATE TABLE starred_messages (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    message_id UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    starred_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, message_id)
+);
+
+-- Create indexes
+CREATE INDEX idx_starred_messages_user_starred_at ON starred_messages(user_id, starred_at DESC, message_id DESC);
+CREATE INDEX idx_starred_messages_message_id ON starred_messages(message_id);
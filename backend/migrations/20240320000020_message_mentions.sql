@@ -0,0 +1,31 @@
+-- Create mention kind enum
+CREATE TYPE mention_kind AS ENUM ('user', 'all', 'admins');
+
+-- Add group permissions
+ALTER TABLE groups
+ADD COLUMN mention_all_permission group_permission NOT NULL DEFAULT 'admins';
+
+-- Create message mentions table, one row per mention entity in the content
+CREATE TABLE message_mentions (
+    id BIGSERIAL PRIMARY KEY,
+    message_id UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    kind mention_kind NOT NULL,
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE,
+    utf16_offset INTEGER NOT NULL CHECK (utf16_offset >= 0),
+    utf16_length INTEGER NOT NULL CHECK (utf16_length > 0),
+    CONSTRAINT message_mentions_user CHECK ((kind = 'user') = (user_id IS NOT NULL))
+);
+
+-- Create mention recipients table, everyone a message mentions after
+-- expanding @all and @admins
+CREATE TABLE mention_recipients (
+    message_id UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    read_at TIMESTAMP WITH TIME ZONE,
+    PRIMARY KEY (message_id, user_id)
+);
+
+-- Create indexes
+CREATE INDEX idx_message_mentions_message_id ON message_mentions(message_id);
+CREATE INDEX idx_mention_recipients_user_id ON mention_recipients(user_id);
+CREATE INDEX idx_mention_recipients_unread ON mention_recipients(user_id) WHERE read_at IS NULL;
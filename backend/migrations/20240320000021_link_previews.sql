@@ -0,0 +1,20 @@
+-- Create link previews table, a cache of fetched pages keyed by URL
+CREATE TABLE link_previews (
+    url TEXT PRIMARY KEY,
+    title TEXT,
+    description TEXT,
+    image_url TEXT,
+    site_name TEXT,
+    -- False when the page could not be fetched or had nothing to show
+    is_available BOOLEAN NOT NULL,
+    fetched_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- Create message link previews table
+CREATE TABLE message_link_previews (
+    message_id UUID PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+    url TEXT NOT NULL REFERENCES link_previews(url) ON DELETE CASCADE
+);
+
+-- Create indexes
+CREATE INDEX idx_message_link_previews_url ON message_link_previews(url);
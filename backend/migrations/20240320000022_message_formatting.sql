@@ -0,0 +1,8 @@
+-- Add formatting entities to messages, validated by the server before they
+-- are stored, e.g. [{"type": "bold", "offset": 0, "length": 5}]
+ALTER TABLE messages
+ADD COLUMN formatting JSONB NOT NULL DEFAULT '[]';
+
+-- Keep the formatting of scheduled messages until they are sent
+ALTER TABLE scheduled_messages
+ADD COLUMN formatting JSONB NOT NULL DEFAULT '[]';
@@ -0,0 +1,13 @@
+-- Create idempotency keys table, mapping the client message id of each send
+-- to the message it created so retries return the original message
+CREATE TABLE message_idempotency_keys (
+    sender_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    idempotency_key TEXT NOT NULL,
+    message_id UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (sender_id, idempotency_key)
+);
+
+-- Create indexes
+CREATE INDEX idx_message_idempotency_keys_created_at ON message_idempotency_keys(created_at);
+CREATE INDEX idx_message_idempotency_keys_message_id ON message_idempotency_keys(message_id);
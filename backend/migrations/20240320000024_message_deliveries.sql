@@ -0,0 +1,11 @@
+-- Create message deliveries table, one row per recipient once the message
+-- has reached one of their devices
+CREATE TABLE message_deliveries (
+    message_id UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    delivered_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (message_id, user_id)
+);
+
+-- Create indexes
+CREATE INDEX idx_message_deliveries_user_id ON message_deliveries(user_id);
@@ -0,0 +1,35 @@
+-- Track reading as a per-user watermark: everything up to last_read_at (the
+-- creation time of the newest message read) counts as read. Groups keep it on
+-- group_members; read_marked_at is when it last moved.
+ALTER TABLE group_members
+ADD COLUMN read_marked_at TIMESTAMP WITH TIME ZONE;
+
+-- Create direct read watermarks table, one row per user and chat partner
+CREATE TABLE direct_read_watermarks (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    peer_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    last_read_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    read_marked_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, peer_id)
+);
+
+-- Carry existing per-message reads over to watermarks
+UPDATE group_members gm
+SET last_read_at = GREATEST(gm.last_read_at, reads.last_read_at),
+    read_marked_at = reads.read_marked_at
+FROM (
+    SELECT m.receiver_id as group_id, r.user_id,
+        MAX(m.created_at) as last_read_at, MAX(r.created_at) as read_marked_at
+    FROM message_reads r
+    JOIN messages m ON m.id = r.message_id
+    JOIN groups g ON g.id = m.receiver_id
+    GROUP BY m.receiver_id, r.user_id
+) reads
+WHERE gm.group_id = reads.group_id AND gm.user_id = reads.user_id;
+
+INSERT INTO direct_read_watermarks (user_id, peer_id, last_read_at, read_marked_at)
+SELECT r.user_id, m.sender_id, MAX(m.created_at), MAX(r.created_at)
+FROM message_reads r
+JOIN messages m ON m.id = r.message_id
+WHERE m.receiver_id = r.user_id
+GROUP BY r.user_id, m.sender_id;
@@ -0,0 +1,69 @@
+-- Track when each conversation last had a message, so the conversation list
+-- can be sorted without scanning message history
+ALTER TABLE groups
+ADD COLUMN last_message_at TIMESTAMP WITH TIME ZONE;
+
+-- Create direct chats table, one row per pair of users who have messaged
+CREATE TABLE direct_chats (
+    user_low UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    user_high UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    last_message_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    PRIMARY KEY (user_low, user_high),
+    CONSTRAINT direct_chats_ordered CHECK (user_low < user_high)
+);
+
+-- Create per-user conversation state; chat_id is the group, or the other
+-- side of a direct chat
+CREATE TABLE conversation_states (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    chat_id UUID NOT NULL,
+    archived_at TIMESTAMP WITH TIME ZONE,
+    muted_at TIMESTAMP WITH TIME ZONE,
+    -- NULL with muted_at set means muted until unmuted
+    muted_until TIMESTAMP WITH TIME ZONE,
+    pinned_at TIMESTAMP WITH TIME ZONE,
+    PRIMARY KEY (user_id, chat_id)
+);
+
+-- Create indexes
+CREATE INDEX idx_direct_chats_user_high ON direct_chats(user_high);
+CREATE INDEX idx_messages_sender_receiver_created_at ON messages(sender_id, receiver_id, created_at);
+
+-- Backfill activity from existing messages
+UPDATE groups g
+SET last_message_at = latest.created_at
+FROM (
+    SELECT receiver_id, MAX(created_at) as created_at
+    FROM messages
+    GROUP BY receiver_id
+) latest
+WHERE g.id = latest.receiver_id;
+
+INSERT INTO direct_chats (user_low, user_high, last_message_at)
+SELECT LEAST(m.sender_id, m.receiver_id), GREATEST(m.sender_id, m.receiver_id), MAX(m.created_at)
+FROM messages m
+WHERE m.sender_id <> m.receiver_id
+    AND NOT EXISTS (SELECT 1 FROM groups g WHERE g.id = m.receiver_id)
+GROUP BY 1, 2;
+
+-- Bump the conversation's activity, whichever path inserts the message
+CREATE OR REPLACE FUNCTION touch_conversation_activity()
+RETURNS TRIGGER AS $$
+BEGIN
+    UPDATE groups SET last_message_at = GREATEST(last_message_at, NEW.created_at)
+    WHERE id = NEW.receiver_id;
+    IF NOT FOUND AND NEW.sender_id <> NEW.receiver_id THEN
+        INSERT INTO direct_chats (user_low, user_high, last_message_at)
+        VALUES (LEAST(NEW.sender_id, NEW.receiver_id), GREATEST(NEW.sender_id, NEW.receiver_id), NEW.created_at)
+        ON CONFLICT (user_low, user_high) DO UPDATE
+        SET last_message_at = GREATEST(direct_chats.last_message_at, EXCLUDED.last_message_at);
+    END IF;
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
+
+-- Create trigger for messages table
+CREATE TRIGGER touch_messages_conversation_activity
+    AFTER INSERT ON messages
+    FOR EACH ROW
+    EXECUTE FUNCTION touch_conversation_activity();
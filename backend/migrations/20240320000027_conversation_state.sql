@@ -0,0 +1,34 @@
+-- Let users keep a conversation archived when new messages arrive
+ALTER TABLE conversation_states
+ADD COLUMN keep_archived BOOLEAN NOT NULL DEFAULT false;
+
+-- Create indexes
+CREATE INDEX idx_conversation_states_archived ON conversation_states(chat_id) WHERE archived_at IS NOT NULL;
+
+-- Bump the conversation's activity and bring it back out of the archive,
+-- whichever path inserts the message
+CREATE OR REPLACE FUNCTION touch_conversation_activity()
+RETURNS TRIGGER AS $$
+BEGIN
+    UPDATE groups SET last_message_at = GREATEST(last_message_at, NEW.created_at)
+    WHERE id = NEW.receiver_id;
+    IF FOUND THEN
+        IF NOT NEW.is_system THEN
+            UPDATE conversation_states SET archived_at = NULL
+            WHERE chat_id = NEW.receiver_id AND archived_at IS NOT NULL AND NOT keep_archived;
+        END IF;
+    ELSIF NEW.sender_id <> NEW.receiver_id THEN
+        INSERT INTO direct_chats (user_low, user_high, last_message_at)
+        VALUES (LEAST(NEW.sender_id, NEW.receiver_id), GREATEST(NEW.sender_id, NEW.receiver_id), NEW.created_at)
+        ON CONFLICT (user_low, user_high) DO UPDATE
+        SET last_message_at = GREATEST(direct_chats.last_message_at, EXCLUDED.last_message_at);
+        IF NOT NEW.is_system THEN
+            UPDATE conversation_states SET archived_at = NULL
+            WHERE ((user_id = NEW.sender_id AND chat_id = NEW.receiver_id)
+                    OR (user_id = NEW.receiver_id AND chat_id = NEW.sender_id))
+                AND archived_at IS NOT NULL AND NOT keep_archived;
+        END IF;
+    END IF;
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
@@ -0,0 +1,28 @@
+-- Create enums for chat exports
+CREATE TYPE chat_export_format AS ENUM ('json', 'txt', 'html');
+CREATE TYPE chat_export_status AS ENUM ('pending', 'running', 'completed', 'failed');
+
+-- Create chat exports table; chat_id is the group, or the other side of a
+-- direct chat
+CREATE TABLE chat_exports (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    chat_id UUID NOT NULL,
+    format chat_export_format NOT NULL,
+    include_media BOOLEAN NOT NULL DEFAULT false,
+    status chat_export_status NOT NULL DEFAULT 'pending',
+    -- Name of the finished file within the export directory
+    file_name TEXT,
+    file_size BIGINT,
+    message_count INTEGER,
+    last_error TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    started_at TIMESTAMP WITH TIME ZONE,
+    completed_at TIMESTAMP WITH TIME ZONE,
+    expires_at TIMESTAMP WITH TIME ZONE
+);
+
+-- Create indexes
+CREATE INDEX idx_chat_exports_user_id ON chat_exports(user_id, created_at DESC);
+CREATE INDEX idx_chat_exports_pending ON chat_exports(created_at) WHERE status IN ('pending', 'running');
+CREATE INDEX idx_chat_exports_expires_at ON chat_exports(expires_at) WHERE expires_at IS NOT NULL;
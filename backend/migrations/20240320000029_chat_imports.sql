@@ -0,0 +1,29 @@
+-- Mark messages brought in from other apps, and the stand-in users who sent
+-- them when they have no account here
+ALTER TABLE messages
+ADD COLUMN is_imported BOOLEAN NOT NULL DEFAULT false;
+
+ALTER TABLE users
+ADD COLUMN is_placeholder BOOLEAN NOT NULL DEFAULT false;
+
+-- Imported history keeps its original timestamps, so the conversation's
+-- timer must not expire it on arrival
+CREATE OR REPLACE FUNCTION stamp_message_expiry()
+RETURNS TRIGGER AS $$
+DECLARE
+    timer INTEGER;
+BEGIN
+    IF NEW.expires_at IS NULL AND NOT NEW.is_system AND NOT NEW.is_imported THEN
+        SELECT disappearing_seconds INTO timer FROM groups WHERE id = NEW.receiver_id;
+        IF NOT FOUND THEN
+            SELECT disappearing_seconds INTO timer FROM direct_chat_settings
+            WHERE user_low = LEAST(NEW.sender_id, NEW.receiver_id)
+                AND user_high = GREATEST(NEW.sender_id, NEW.receiver_id);
+        END IF;
+        IF timer IS NOT NULL THEN
+            NEW.expires_at := NEW.created_at + make_interval(secs => timer);
+        END IF;
+    END IF;
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
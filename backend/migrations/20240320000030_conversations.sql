@@ -0,0 +1,203 @@
+-- Create enum for conversation kinds
+CREATE TYPE conversation_kind AS ENUM ('direct', 'group');
+
+-- Create conversations table, one row per direct chat or group. A group's
+-- conversation shares its id; a direct chat is keyed by its pair of users,
+-- who may be the same user for notes to self
+CREATE TABLE conversations (
+    id UUID PRIMARY KEY,
+    kind conversation_kind NOT NULL,
+    user_low UUID REFERENCES users(id) ON DELETE CASCADE,
+    user_high UUID REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    last_message_at TIMESTAMP WITH TIME ZONE,
+    UNIQUE (user_low, user_high),
+    CONSTRAINT conversations_direct_pair CHECK (
+        CASE kind
+            WHEN 'direct' THEN user_low IS NOT NULL AND user_high IS NOT NULL AND user_low <= user_high
+            ELSE user_low IS NULL AND user_high IS NULL
+        END
+    )
+);
+
+-- Create conversation members table, everyone who can read and write a
+-- conversation. Reading is tracked as a watermark: everything up to
+-- last_read_at (the creation time of the newest message read) counts as
+-- read, and read_marked_at is when it last moved
+CREATE TABLE conversation_members (
+    conversation_id UUID NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    joined_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    last_read_at TIMESTAMP WITH TIME ZONE,
+    read_marked_at TIMESTAMP WITH TIME ZONE,
+    PRIMARY KEY (conversation_id, user_id)
+);
+
+-- Create indexes
+CREATE INDEX idx_conversation_members_user_id ON conversation_members(user_id);
+
+-- Carry groups over, with their members and read watermarks
+INSERT INTO conversations (id, kind, created_at, last_message_at)
+SELECT id, 'group', created_at, last_message_at
+FROM groups;
+
+INSERT INTO conversation_members (conversation_id, user_id, joined_at, last_read_at, read_marked_at)
+SELECT group_id, user_id, joined_at, last_read_at, read_marked_at
+FROM group_members;
+
+-- Messages to an account or group that has since been deleted stay with their
+-- sender, in a direct chat with a placeholder standing in for whoever is gone.
+-- Like imported senders, nobody can sign in as one
+INSERT INTO users (id, email, username, password_hash, display_name, is_placeholder)
+SELECT gone.id,
+    'deleted+' || replace(gone.id::text, '-', '') || '@placeholder.invalid',
+    'deleted_' || replace(gone.id::text, '-', ''),
+    '!', 'Deleted account', true
+FROM (
+    SELECT DISTINCT m.receiver_id as id
+    FROM messages m
+    WHERE NOT EXISTS(SELECT 1 FROM users u WHERE u.id = m.receiver_id)
+        AND NOT EXISTS(SELECT 1 FROM groups g WHERE g.id = m.receiver_id)
+) gone;
+
+-- Every pair of users who have messaged gets a direct chat
+INSERT INTO conversations (id, kind, user_low, user_high, created_at, last_message_at)
+SELECT gen_random_uuid(), 'direct', pair.user_low, pair.user_high, pair.created_at, pair.last_message_at
+FROM (
+    SELECT LEAST(m.sender_id, m.receiver_id) as user_low, GREATEST(m.sender_id, m.receiver_id) as user_high,
+        MIN(m.created_at) as created_at, MAX(m.created_at) as last_message_at
+    FROM messages m
+    JOIN users u ON u.id = m.receiver_id
+    GROUP BY 1, 2
+) pair;
+
+INSERT INTO conversation_members (conversation_id, user_id, joined_at, last_read_at, read_marked_at)
+SELECT c.id, side.user_id, c.created_at, w.last_read_at, w.read_marked_at
+FROM conversations c
+CROSS JOIN LATERAL (VALUES (c.user_low, c.user_high), (c.user_high, c.user_low)) as side(user_id, peer_id)
+LEFT JOIN direct_read_watermarks w ON w.user_id = side.user_id AND w.peer_id = side.peer_id
+WHERE c.kind = 'direct'
+ON CONFLICT (conversation_id, user_id) DO NOTHING;
+
+-- File every message under its conversation
+ALTER TABLE messages
+ADD COLUMN conversation_id UUID REFERENCES conversations(id) ON DELETE CASCADE;
+
+UPDATE messages m
+SET conversation_id = g.id
+FROM groups g
+WHERE g.id = m.receiver_id;
+
+UPDATE messages m
+SET conversation_id = c.id
+FROM conversations c
+WHERE m.conversation_id IS NULL
+    AND c.user_low = LEAST(m.sender_id, m.receiver_id)
+    AND c.user_high = GREATEST(m.sender_id, m.receiver_id);
+
+-- Every message has a conversation by now; this fails rather than lose any
+ALTER TABLE messages
+ALTER COLUMN conversation_id SET NOT NULL;
+
+DROP INDEX idx_messages_sender_receiver_created_at;
+CREATE INDEX idx_messages_conversation_id_created_at ON messages(conversation_id, created_at, id);
+
+-- Groups can't exist without their conversation
+ALTER TABLE groups
+ADD CONSTRAINT groups_conversation_id_fkey FOREIGN KEY (id) REFERENCES conversations(id) ON DELETE CASCADE;
+
+-- Activity and read watermarks now live on conversations and their members
+ALTER TABLE groups
+DROP COLUMN last_message_at;
+
+ALTER TABLE group_members
+DROP COLUMN last_read_at,
+DROP COLUMN read_marked_at;
+
+DROP TABLE direct_read_watermarks;
+DROP TABLE direct_chats;
+
+-- Key per-user conversation state by conversation rather than by the group
+-- or the other side of a direct chat
+ALTER TABLE conversation_states
+ADD COLUMN conversation_id UUID REFERENCES conversations(id) ON DELETE CASCADE;
+
+UPDATE conversation_states s
+SET conversation_id = c.id
+FROM conversations c
+WHERE (c.kind = 'group' AND c.id = s.chat_id)
+    OR (c.kind = 'direct'
+        AND c.user_low = LEAST(s.user_id, s.chat_id)
+        AND c.user_high = GREATEST(s.user_id, s.chat_id));
+
+-- Settings for a chat that never had a message describe no conversation, so
+-- they are the only rows left behind
+DELETE FROM conversation_states WHERE conversation_id IS NULL;
+
+DROP INDEX idx_conversation_states_archived;
+
+ALTER TABLE conversation_states
+DROP CONSTRAINT conversation_states_pkey,
+DROP COLUMN chat_id,
+ALTER COLUMN conversation_id SET NOT NULL,
+ADD PRIMARY KEY (user_id, conversation_id);
+
+CREATE INDEX idx_conversation_states_archived ON conversation_states(conversation_id) WHERE archived_at IS NOT NULL;
+
+-- Mirror group membership into the group's conversation, whichever path
+-- adds or removes the member
+CREATE OR REPLACE FUNCTION sync_group_conversation_members()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        INSERT INTO conversation_members (conversation_id, user_id, joined_at)
+        VALUES (NEW.group_id, NEW.user_id, NEW.joined_at)
+        ON CONFLICT (conversation_id, user_id) DO NOTHING;
+        RETURN NEW;
+    END IF;
+    DELETE FROM conversation_members
+    WHERE conversation_id = OLD.group_id AND user_id = OLD.user_id;
+    RETURN OLD;
+END;
+$$ language 'plpgsql';
+
+-- Create trigger for group members table
+CREATE TRIGGER sync_group_members_conversation_members
+    AFTER INSERT OR DELETE ON group_members
+    FOR EACH ROW
+    EXECUTE FUNCTION sync_group_conversation_members();
+
+-- Bump the conversation's activity and bring it back out of the archive,
+-- whichever path inserts the message
+CREATE OR REPLACE FUNCTION touch_conversation_activity()
+RETURNS TRIGGER AS $$
+BEGIN
+    UPDATE conversations SET last_message_at = GREATEST(last_message_at, NEW.created_at)
+    WHERE id = NEW.conversation_id;
+    IF NOT NEW.is_system THEN
+        UPDATE conversation_states SET archived_at = NULL
+        WHERE conversation_id = NEW.conversation_id AND archived_at IS NOT NULL AND NOT keep_archived;
+    END IF;
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
+
+-- Stamp new messages with their conversation's timer
+CREATE OR REPLACE FUNCTION stamp_message_expiry()
+RETURNS TRIGGER AS $$
+DECLARE
+    timer INTEGER;
+BEGIN
+    IF NEW.expires_at IS NULL AND NOT NEW.is_system AND NOT NEW.is_imported THEN
+        SELECT COALESCE(g.disappearing_seconds, s.disappearing_seconds) INTO timer
+        FROM conversations c
+        LEFT JOIN groups g ON g.id = c.id
+        LEFT JOIN direct_chat_settings s ON s.user_low = c.user_low AND s.user_high = c.user_high
+        WHERE c.id = NEW.conversation_id;
+        IF timer IS NOT NULL THEN
+            NEW.expires_at := NEW.created_at + make_interval(secs => timer);
+        END IF;
+    END IF;
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
@@ -0,0 +1,13 @@
+-- Create media deletions table, storage objects whose media rows are already
+-- gone. Deletes that fail are retried after a growing delay, so one object
+-- storage keeps refusing never holds up the rest
+CREATE TABLE media_deletions (
+    public_id TEXT PRIMARY KEY,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    last_error TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- Create indexes
+CREATE INDEX idx_media_deletions_next_attempt_at ON media_deletions(next_attempt_at);
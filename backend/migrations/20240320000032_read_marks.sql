@@ -0,0 +1,22 @@
+-- Create read marks table, one row each time a member's read watermark
+-- moves. A message was read when the first mark reaching it was made, so
+-- reading on later doesn't change when earlier messages were read
+CREATE TABLE read_marks (
+    conversation_id UUID NOT NULL,
+    user_id UUID NOT NULL,
+    read_up_to TIMESTAMP WITH TIME ZONE NOT NULL,
+    marked_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (conversation_id, user_id, read_up_to),
+    FOREIGN KEY (conversation_id, user_id)
+        REFERENCES conversation_members(conversation_id, user_id) ON DELETE CASCADE
+);
+
+-- Existing watermarks become a single mark each; when they last moved is all
+-- that's known of them
+INSERT INTO read_marks (conversation_id, user_id, read_up_to, marked_at)
+SELECT conversation_id, user_id, last_read_at, COALESCE(read_marked_at, last_read_at)
+FROM conversation_members
+WHERE last_read_at IS NOT NULL;
+
+ALTER TABLE conversation_members
+DROP COLUMN read_marked_at;
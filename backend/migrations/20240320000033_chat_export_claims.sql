@@ -0,0 +1,10 @@
+-- Track who is running an export. Each claim gets its own id, and the worker
+-- holding it beats every minute; an export whose heartbeat stops is picked up
+-- again, and the old worker's results are turned away
+ALTER TABLE chat_exports
+ADD COLUMN claim_id UUID,
+ADD COLUMN heartbeat_at TIMESTAMP WITH TIME ZONE;
+
+UPDATE chat_exports
+SET heartbeat_at = started_at
+WHERE status = 'running';
@@ -0,0 +1,3 @@
+-- Reads are tracked by watermarks and read marks now; per-message reads were
+-- carried over when watermarks were introduced
+DROP TABLE message_reads;
@@ -0,0 +1,51 @@
+-- Direct chats outlive either side: deleting an account no longer takes the
+-- other person's history with it
+ALTER TABLE conversations
+DROP CONSTRAINT conversations_user_low_fkey,
+DROP CONSTRAINT conversations_user_high_fkey,
+ADD CONSTRAINT conversations_user_low_fkey FOREIGN KEY (user_low) REFERENCES users(id),
+ADD CONSTRAINT conversations_user_high_fkey FOREIGN KEY (user_high) REFERENCES users(id);
+
+-- An account that others have chatted with becomes a placeholder instead of
+-- going away, like the deleted accounts found when conversations were
+-- introduced, so its messages stay where they are. It leaves its groups and
+-- contacts, and its profile, statuses and unsent messages are cleared
+CREATE OR REPLACE FUNCTION retire_deleted_user()
+RETURNS TRIGGER AS $$
+BEGIN
+    -- Notes to self have nobody else to keep them for
+    DELETE FROM conversations
+    WHERE kind = 'direct' AND user_low = OLD.id AND user_high = OLD.id;
+
+    IF NOT EXISTS(SELECT 1 FROM conversations WHERE kind = 'direct' AND OLD.id IN (user_low, user_high))
+        AND NOT EXISTS(SELECT 1 FROM messages WHERE sender_id = OLD.id) THEN
+        RETURN OLD;
+    END IF;
+
+    UPDATE users
+    SET email = 'deleted+' || replace(OLD.id::text, '-', '') || '@placeholder.invalid',
+        username = 'deleted_' || replace(OLD.id::text, '-', ''),
+        password_hash = '!',
+        display_name = 'Deleted account',
+        bio = NULL,
+        avatar_url = NULL,
+        status = NULL,
+        is_online = false,
+        last_seen = NULL,
+        is_placeholder = true,
+        updated_at = NOW()
+    WHERE id = OLD.id;
+
+    DELETE FROM group_members WHERE user_id = OLD.id;
+    DELETE FROM contacts WHERE user_id = OLD.id OR contact_id = OLD.id;
+    DELETE FROM status_updates WHERE user_id = OLD.id;
+    DELETE FROM scheduled_messages WHERE sender_id = OLD.id;
+    RETURN NULL;
+END;
+$$ language 'plpgsql';
+
+-- Create trigger for users table
+CREATE TRIGGER retire_deleted_users
+    BEFORE DELETE ON users
+    FOR EACH ROW
+    EXECUTE FUNCTION retire_deleted_user();
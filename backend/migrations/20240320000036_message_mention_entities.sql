@@ -0,0 +1,11 @@
+-- Mentions of a message as the API returns them, in text order, for every
+-- query that loads messages to select the same shape
+CREATE OR REPLACE FUNCTION message_mention_entities(target UUID)
+RETURNS JSON AS $$
+    SELECT COALESCE(json_agg(json_build_object(
+        'kind', mm.kind, 'user_id', mm.user_id,
+        'offset', mm.utf16_offset, 'length', mm.utf16_length
+    ) ORDER BY mm.utf16_offset), '[]')
+    FROM message_mentions mm
+    WHERE mm.message_id = target;
+$$ LANGUAGE sql STABLE;
@@ -101,4 +101,54 @@ impl Config {
     pub fn is_testing(&self) -> bool {
         self.environment == Environment::Testing
     }
-}
\ No newline at end of file
+}
+
+/// Limits on what users can do with messages after sending them.
+#[derive(Debug, Clone)]
+pub struct MessagingConfig {
+    /// How long after sending a message it can still be edited
+    pub edit_window: chrono::Duration,
+    /// How long after sending a message it can still be deleted for everyone
+    pub delete_window: chrono::Duration,
+    /// How long a client message id is remembered, so retries within it
+    /// return the original message instead of sending a duplicate
+    pub idempotency_window: chrono::Duration,
+    /// Where finished chat exports are written; must be shared by every node
+    pub export_dir: std::path::PathBuf,
+    /// How long a finished chat export can be downloaded before it is deleted
+    pub export_retention: chrono::Duration,
+}
+
+impl MessagingConfig {
+    pub fn from_env() -> Self {
+        Self {
+            edit_window: chrono::Duration::seconds(
+                env::var("MESSAGE_EDIT_WINDOW_SECS")
+                    .ok()
+                    .and_then(|secs| secs.parse().ok())
+                    .unwrap_or(15 * 60),
+            ),
+            delete_window: chrono::Duration::seconds(
+                env::var("MESSAGE_DELETE_WINDOW_SECS")
+                    .ok()
+                    .and_then(|secs| secs.parse().ok())
+                    .unwrap_or(48 * 60 * 60),
+            ),
+            idempotency_window: chrono::Duration::seconds(
+                env::var("MESSAGE_IDEMPOTENCY_WINDOW_SECS")
+                    .ok()
+                    .and_then(|secs| secs.parse().ok())
+                    .unwrap_or(24 * 60 * 60),
+            ),
+            export_dir: env::var("EXPORT_DIR")
+                .map(std::path::PathBuf::from)
+                .unwrap_or_else(|_| env::temp_dir().join("chat-exports")),
+            export_retention: chrono::Duration::seconds(
+                env::var("EXPORT_RETENTION_SECS")
+                    .ok()
+                    .and_then(|secs| secs.parse().ok())
+                    .unwrap_or(24 * 60 * 60),
+            ),
+        }
+    }
+}
@@ -47,6 +47,9 @@ pub enum AppError {
 
     #[error("Broadcast error: {0}")]
     BroadcastError(#[from] broadcast::error::SendError<String>),
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
 }
 
 impl IntoResponse for AppError {
@@ -65,6 +68,7 @@ impl IntoResponse for AppError {
             AppError::UuidError(e) => (StatusCode::BAD_REQUEST, e.to_string()),
             AppError::JsonError(e) => (StatusCode::BAD_REQUEST, e.to_string()),
             AppError::BroadcastError(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+            AppError::IoError(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
         };
 
         let body = Json(json!({
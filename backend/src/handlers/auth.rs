@@ -11,6 +11,7 @@ use crate::{
     auth::{create_token, AuthUser},
     error::AppError,
     models::{CreateUserRequest, UserResponse},
+    services::cache::CacheTag,
     AppState,
 };
 
@@ -56,6 +57,8 @@ pub async fn register(
     .fetch_one(&state.pool)
     .await?;
 
+    state.cache.invalidate(&[CacheTag::UserDirectory]).await;
+
     // Generate token
     let token = create_token(user.id)?;
 
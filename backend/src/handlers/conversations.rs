@@ -0,0 +1,591 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::{
+    AppState,
+    error::AppError,
+    models::{
+        conversation::{
+            ArchiveConversationRequest, Conversation, ConversationKind, ConversationPage,
+            ConversationState, ConversationSummary, LastMessage, MuteConversationRequest,
+        },
+        message::{ChatContext, QuotedMessage},
+        pagination::{Cursor, Paginated},
+    },
+    auth::Claims,
+    websocket::{fanout, validation::WebSocketMessage},
+};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 100;
+const MAX_PINNED_CONVERSATIONS: i64 = 3;
+
+#[derive(Debug, Deserialize)]
+pub struct ConversationsQuery {
+    /// Cursor to load less recently active conversations from
+    pub before: Option<String>,
+    pub limit: Option<i64>,
+    /// List archived conversations instead of the inbox
+    #[serde(default)]
+    pub archived: bool,
+}
+
+/// Every direct chat and group I'm in, most recently active first.
+///
+/// Ordering only needs each conversation's activity time, which is kept up to
+/// date as messages are inserted; previews and unread counts are worked out
+/// for the returned page alone.
+pub async fn get_conversations(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Query(query): Query<ConversationsQuery>,
+) -> Result<Json<ConversationPage>, AppError> {
+    let before = query.before.as_deref().map(Cursor::decode).transpose()?;
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+    let rows = sqlx::query!(
+        r#"
+        WITH chats AS (
+            SELECT c.id as conversation_id, COALESCE(g.id, peer.id) as chat_id,
+                c.kind = 'group' as is_group,
+                COALESCE(g.name, peer.display_name, peer.username) as name,
+                COALESCE(g.avatar_url, peer.avatar_url) as avatar_url,
+                CASE WHEN c.kind = 'group'
+                    THEN GREATEST(c.last_message_at, cm.joined_at)
+                    ELSE c.last_message_at
+                END as activity_at,
+                CASE WHEN c.kind = 'group'
+                    THEN COALESCE(cm.last_read_at, cm.joined_at)
+                    ELSE cm.last_read_at
+                END as read_up_to
+            FROM conversation_members cm
+            JOIN conversations c ON c.id = cm.conversation_id
+            LEFT JOIN groups g ON g.id = c.id
+            LEFT JOIN users peer ON c.kind = 'direct'
+                AND peer.id = CASE WHEN c.user_low = $1 THEN c.user_high ELSE c.user_low END
+            WHERE cm.user_id = $1
+                -- Direct chats show up once the first message is sent
+                AND (c.kind = 'group' OR c.last_message_at IS NOT NULL)
+        ),
+        listed AS (
+            SELECT c.*, s.muted_until,
+                s.archived_at IS NOT NULL as is_archived,
+                s.pinned_at IS NOT NULL AND s.archived_at IS NULL as is_pinned,
+                s.muted_at IS NOT NULL AND (s.muted_until IS NULL OR s.muted_until > NOW()) as is_muted,
+                s.pinned_at
+            FROM chats c
+            LEFT JOIN conversation_states s ON s.user_id = $1 AND s.conversation_id = c.conversation_id
+            WHERE (s.archived_at IS NOT NULL) = $2
+        ),
+        page AS (
+            (
+                SELECT * FROM listed
+                WHERE is_pinned AND $3::timestamptz IS NULL
+            )
+            UNION ALL
+            (
+                SELECT * FROM listed
+                WHERE NOT is_pinned
+                    AND ($3::timestamptz IS NULL OR (activity_at, chat_id) < ($3, $4::uuid))
+                ORDER BY activity_at DESC, chat_id DESC
+                LIMIT $5
+            )
+        )
+        SELECT p.chat_id as "chat_id!", p.is_group as "is_group!", p.name as "name!", p.avatar_url,
+            p.activity_at as "activity_at!", p.muted_until,
+            p.is_pinned as "is_pinned!", p.is_archived as "is_archived!", p.is_muted as "is_muted!",
+            lm.id as "last_id?", lm.sender_id as "last_sender_id?", lm.sender_name as "last_sender_name?",
+            lm.content as "last_content?", lm.media_url as "last_media_url?",
+            lm.is_deleted as "last_is_deleted?", lm.is_system as "last_is_system?",
+            lm.created_at as "last_created_at?",
+            unread.messages as "unread_count!", unread.mentions as "unread_mention_count!"
+        FROM page p
+        LEFT JOIN LATERAL (
+            SELECT m.id, m.sender_id, COALESCE(u.display_name, u.username) as sender_name,
+                m.content, m.media_url, m.is_deleted, m.is_system, m.created_at
+            FROM messages m
+            JOIN users u ON u.id = m.sender_id
+            WHERE m.conversation_id = p.conversation_id
+                AND NOT EXISTS (SELECT 1 FROM hidden_messages h WHERE h.message_id = m.id AND h.user_id = $1)
+                AND (m.expires_at IS NULL OR m.expires_at > NOW())
+            ORDER BY m.created_at DESC, m.id DESC
+            LIMIT 1
+        ) lm ON true
+        CROSS JOIN LATERAL (
+            SELECT COUNT(*) as messages,
+                COUNT(*) FILTER (
+                    WHERE EXISTS(SELECT 1 FROM mention_recipients mr WHERE mr.message_id = m.id AND mr.user_id = $1)
+                ) as mentions
+            FROM messages m
+            WHERE m.conversation_id = p.conversation_id
+                AND m.sender_id <> $1
+                AND m.created_at > COALESCE(p.read_up_to, '-infinity')
+                AND NOT m.is_deleted
+                AND NOT m.is_system
+                AND (m.expires_at IS NULL OR m.expires_at > NOW())
+                AND NOT EXISTS (SELECT 1 FROM hidden_messages h WHERE h.message_id = m.id AND h.user_id = $1)
+        ) unread
+        ORDER BY p.is_pinned DESC, p.pinned_at DESC, p.activity_at DESC, p.chat_id DESC
+        "#,
+        claims.sub,
+        query.archived,
+        before.map(|cursor| cursor.created_at),
+        before.map(|cursor| cursor.id),
+        limit + 1
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    let unpinned = rows.iter().filter(|row| !row.is_pinned).count() as i64;
+    let has_more = unpinned > limit;
+    let pinned = rows.len() - unpinned as usize;
+
+    let conversations: Vec<ConversationSummary> = rows
+        .into_iter()
+        .take(pinned + limit as usize)
+        .map(|row| {
+            let last_message = QuotedMessage::from_columns(
+                row.last_id,
+                row.last_sender_id,
+                row.last_sender_name,
+                row.last_content,
+                row.last_media_url,
+                row.last_is_deleted,
+            )
+            .zip(row.last_created_at)
+            .map(|(message, created_at)| LastMessage {
+                message,
+                is_system: row.last_is_system.unwrap_or_default(),
+                created_at,
+            });
+
+            ConversationSummary {
+                chat: ChatContext {
+                    id: row.chat_id,
+                    name: row.name,
+                    avatar_url: row.avatar_url,
+                    is_group: row.is_group,
+                },
+                last_activity_at: row.activity_at,
+                last_message,
+                unread_count: row.unread_count,
+                unread_mention_count: row.unread_mention_count,
+                is_pinned: row.is_pinned,
+                is_archived: row.is_archived,
+                is_muted: row.is_muted,
+                muted_until: row.muted_until.filter(|_| row.is_muted),
+            }
+        })
+        .collect();
+
+    // Pinned conversations only come with the first page, so they never
+    // serve as a cursor
+    let next_cursor = conversations
+        .iter()
+        .rev()
+        .find(|conversation| !conversation.is_pinned)
+        .filter(|_| has_more)
+        .map(|conversation| conversation.cursor().encode());
+
+    Ok(Json(ConversationPage {
+        conversations,
+        next_cursor,
+        has_more,
+    }))
+}
+
+/// My settings for a conversation as stored; the flags in `ConversationState`
+/// are derived from these.
+struct StoredState {
+    archived_at: Option<DateTime<Utc>>,
+    keep_archived: bool,
+    muted_at: Option<DateTime<Utc>>,
+    muted_until: Option<DateTime<Utc>>,
+    pinned_at: Option<DateTime<Utc>>,
+}
+
+impl StoredState {
+    fn into_state(self, chat_id: Uuid) -> ConversationState {
+        let is_muted = self.muted_at.is_some()
+            && self.muted_until.is_none_or(|until| until > Utc::now());
+        ConversationState {
+            chat_id,
+            is_archived: self.archived_at.is_some(),
+            keep_archived: self.keep_archived,
+            is_muted,
+            muted_until: self.muted_until.filter(|_| is_muted),
+            is_pinned: self.pinned_at.is_some(),
+        }
+    }
+}
+
+/// Archives a conversation, taking it out of the inbox and off the pinned
+/// list. It comes back when a new message arrives unless `keep_archived` is set.
+pub async fn archive_conversation(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(chat_id): Path<Uuid>,
+    req: Option<Json<ArchiveConversationRequest>>,
+) -> Result<Json<ConversationState>, AppError> {
+    let req = req.map(|Json(req)| req).unwrap_or_default();
+    let conversation = ensure_conversation(&state, claims.sub, chat_id).await?;
+
+    let stored = sqlx::query_as!(
+        StoredState,
+        r#"
+        INSERT INTO conversation_states (user_id, conversation_id, archived_at, keep_archived)
+        VALUES ($1, $2, NOW(), $3)
+        ON CONFLICT (user_id, conversation_id) DO UPDATE
+        SET archived_at = COALESCE(conversation_states.archived_at, EXCLUDED.archived_at),
+            keep_archived = EXCLUDED.keep_archived,
+            pinned_at = NULL
+        RETURNING archived_at, keep_archived, muted_at, muted_until, pinned_at
+        "#,
+        claims.sub,
+        conversation.id,
+        req.keep_archived
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    publish_state(&state, claims.sub, stored.into_state(chat_id)).await
+}
+
+pub async fn unarchive_conversation(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(chat_id): Path<Uuid>,
+) -> Result<Json<ConversationState>, AppError> {
+    let conversation = ensure_conversation(&state, claims.sub, chat_id).await?;
+
+    let stored = sqlx::query_as!(
+        StoredState,
+        r#"
+        INSERT INTO conversation_states (user_id, conversation_id)
+        VALUES ($1, $2)
+        ON CONFLICT (user_id, conversation_id) DO UPDATE
+        SET archived_at = NULL, keep_archived = false
+        RETURNING archived_at, keep_archived, muted_at, muted_until, pinned_at
+        "#,
+        claims.sub,
+        conversation.id
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    publish_state(&state, claims.sub, stored.into_state(chat_id)).await
+}
+
+/// Mutes a conversation for 8 hours, a week or until unmuted. Muted
+/// conversations still get messages but don't notify me about mentions.
+pub async fn mute_conversation(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(chat_id): Path<Uuid>,
+    Json(req): Json<MuteConversationRequest>,
+) -> Result<Json<ConversationState>, AppError> {
+    let conversation = ensure_conversation(&state, claims.sub, chat_id).await?;
+
+    let now = Utc::now();
+    let stored = sqlx::query_as!(
+        StoredState,
+        r#"
+        INSERT INTO conversation_states (user_id, conversation_id, muted_at, muted_until)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (user_id, conversation_id) DO UPDATE
+        SET muted_at = EXCLUDED.muted_at, muted_until = EXCLUDED.muted_until
+        RETURNING archived_at, keep_archived, muted_at, muted_until, pinned_at
+        "#,
+        claims.sub,
+        conversation.id,
+        now,
+        req.duration.until(now)
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    publish_state(&state, claims.sub, stored.into_state(chat_id)).await
+}
+
+pub async fn unmute_conversation(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(chat_id): Path<Uuid>,
+) -> Result<Json<ConversationState>, AppError> {
+    let conversation = ensure_conversation(&state, claims.sub, chat_id).await?;
+
+    let stored = sqlx::query_as!(
+        StoredState,
+        r#"
+        INSERT INTO conversation_states (user_id, conversation_id)
+        VALUES ($1, $2)
+        ON CONFLICT (user_id, conversation_id) DO UPDATE
+        SET muted_at = NULL, muted_until = NULL
+        RETURNING archived_at, keep_archived, muted_at, muted_until, pinned_at
+        "#,
+        claims.sub,
+        conversation.id
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    publish_state(&state, claims.sub, stored.into_state(chat_id)).await
+}
+
+/// Pins a conversation to the top of my inbox, up to `MAX_PINNED_CONVERSATIONS`.
+pub async fn pin_conversation(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(chat_id): Path<Uuid>,
+) -> Result<Json<ConversationState>, AppError> {
+    let conversation = ensure_conversation(&state, claims.sub, chat_id).await?;
+
+    let mut tx = state.pool.begin().await?;
+
+    // Serialize my pins so the limit holds
+    sqlx::query!(
+        "SELECT pg_advisory_xact_lock(hashtextextended($1, 0))",
+        format!("conversation-pins:{}", claims.sub)
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let current = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT archived_at IS NOT NULL FROM conversation_states
+                WHERE user_id = $1 AND conversation_id = $2) as is_archived,
+            (SELECT pinned_at IS NOT NULL FROM conversation_states
+                WHERE user_id = $1 AND conversation_id = $2) as is_pinned,
+            (SELECT COUNT(*) FROM conversation_states
+                WHERE user_id = $1 AND pinned_at IS NOT NULL) as "pinned!"
+        "#,
+        claims.sub,
+        conversation.id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if current.is_archived == Some(true) {
+        return Err(AppError::BadRequest("Archived conversations cannot be pinned".into()));
+    }
+    if current.is_pinned != Some(true) && current.pinned >= MAX_PINNED_CONVERSATIONS {
+        return Err(AppError::BadRequest(format!(
+            "You cannot pin more than {} conversations",
+            MAX_PINNED_CONVERSATIONS
+        )));
+    }
+
+    let stored = sqlx::query_as!(
+        StoredState,
+        r#"
+        INSERT INTO conversation_states (user_id, conversation_id, pinned_at)
+        VALUES ($1, $2, NOW())
+        ON CONFLICT (user_id, conversation_id) DO UPDATE
+        SET pinned_at = COALESCE(conversation_states.pinned_at, EXCLUDED.pinned_at)
+        RETURNING archived_at, keep_archived, muted_at, muted_until, pinned_at
+        "#,
+        claims.sub,
+        conversation.id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    publish_state(&state, claims.sub, stored.into_state(chat_id)).await
+}
+
+pub async fn unpin_conversation(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(chat_id): Path<Uuid>,
+) -> Result<Json<ConversationState>, AppError> {
+    let conversation = ensure_conversation(&state, claims.sub, chat_id).await?;
+
+    let stored = sqlx::query_as!(
+        StoredState,
+        r#"
+        INSERT INTO conversation_states (user_id, conversation_id)
+        VALUES ($1, $2)
+        ON CONFLICT (user_id, conversation_id) DO UPDATE
+        SET pinned_at = NULL
+        RETURNING archived_at, keep_archived, muted_at, muted_until, pinned_at
+        "#,
+        claims.sub,
+        conversation.id
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    publish_state(&state, claims.sub, stored.into_state(chat_id)).await
+}
+
+/// The subset of `user_ids` who haven't muted a conversation.
+pub(crate) async fn unmuted(
+    state: &AppState,
+    user_ids: &[Uuid],
+    conversation_id: Uuid,
+) -> Result<Vec<Uuid>, AppError> {
+    if user_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let unmuted = sqlx::query_scalar!(
+        r#"
+        SELECT u.id as "id!" FROM UNNEST($1::uuid[]) as u(id)
+        WHERE NOT EXISTS (
+            SELECT 1 FROM conversation_states s
+            WHERE s.user_id = u.id
+                AND s.conversation_id = $2
+                AND s.muted_at IS NOT NULL
+                AND (s.muted_until IS NULL OR s.muted_until > NOW())
+        )
+        "#,
+        user_ids,
+        conversation_id
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(unmuted)
+}
+
+/// The conversation `chat_id` addresses for me: the group with that id, or my
+/// direct chat with that user. `None` unless I'm a member.
+pub(crate) async fn find_conversation(
+    state: &AppState,
+    user_id: Uuid,
+    chat_id: Uuid,
+) -> Result<Option<Conversation>, AppError> {
+    let conversation = sqlx::query_as!(
+        Conversation,
+        r#"
+        SELECT c.id, c.kind as "kind: ConversationKind"
+        FROM conversations c
+        JOIN conversation_members cm ON cm.conversation_id = c.id AND cm.user_id = $1
+        WHERE (c.kind = 'group' AND c.id = $2)
+            OR (c.kind = 'direct'
+                AND c.user_low = LEAST($1::uuid, $2::uuid)
+                AND c.user_high = GREATEST($1::uuid, $2::uuid))
+        "#,
+        user_id,
+        chat_id
+    )
+    .fetch_optional(&state.pool)
+    .await?;
+
+    Ok(conversation)
+}
+
+/// `chat_id` must be a group I'm in or someone I've chatted with.
+pub(crate) async fn ensure_conversation(
+    state: &AppState,
+    user_id: Uuid,
+    chat_id: Uuid,
+) -> Result<Conversation, AppError> {
+    find_conversation(state, user_id, chat_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Conversation not found".into()))
+}
+
+/// `group_id` must be a group I'm in.
+pub(crate) async fn ensure_group_member(
+    state: &AppState,
+    user_id: Uuid,
+    group_id: Uuid,
+) -> Result<Conversation, AppError> {
+    find_conversation(state, user_id, group_id)
+        .await?
+        .filter(Conversation::is_group)
+        .ok_or_else(|| AppError::Forbidden("Not a member of this group".into()))
+}
+
+/// My direct chat with `peer_id`, started if we've never talked. Anyone with
+/// an account can be messaged, including myself.
+pub(crate) async fn direct_conversation(
+    state: &AppState,
+    user_id: Uuid,
+    peer_id: Uuid,
+) -> Result<Conversation, AppError> {
+    if let Some(conversation) = find_conversation(state, user_id, peer_id).await? {
+        if !conversation.is_group() {
+            return Ok(conversation);
+        }
+    }
+
+    let peer_exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM users WHERE id = $1 AND NOT is_placeholder) as "exists!""#,
+        peer_id
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    if !peer_exists {
+        return Err(AppError::NotFound("User not found".into()));
+    }
+
+    // Both sides join as the chat starts; a concurrent start finds the same row
+    let id = sqlx::query_scalar!(
+        r#"
+        WITH started AS (
+            INSERT INTO conversations (id, kind, user_low, user_high, created_at)
+            VALUES ($3, 'direct', LEAST($1::uuid, $2::uuid), GREATEST($1::uuid, $2::uuid), NOW())
+            ON CONFLICT (user_low, user_high) DO UPDATE SET user_low = EXCLUDED.user_low
+            RETURNING id, created_at
+        ),
+        joined AS (
+            INSERT INTO conversation_members (conversation_id, user_id, joined_at)
+            SELECT started.id, u.id, started.created_at
+            FROM started, users u
+            WHERE u.id IN ($1, $2)
+            ON CONFLICT (conversation_id, user_id) DO NOTHING
+        )
+        SELECT id as "id!" FROM started
+        "#,
+        user_id,
+        peer_id,
+        Uuid::new_v4()
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    Ok(Conversation { id, kind: ConversationKind::Direct })
+}
+
+/// Everyone in a conversation: the group's members, or both sides of a
+/// direct chat. Events about its messages go to them.
+pub(crate) async fn conversation_members(state: &AppState, conversation_id: Uuid) -> Result<Vec<Uuid>, AppError> {
+    let members = sqlx::query_scalar!(
+        r#"
+        SELECT user_id FROM conversation_members
+        WHERE conversation_id = $1
+        "#,
+        conversation_id
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(members)
+}
+
+/// Keeps my other devices in sync.
+async fn publish_state(
+    state: &AppState,
+    user_id: Uuid,
+    conversation: ConversationState,
+) -> Result<Json<ConversationState>, AppError> {
+    fanout::send_to_users(
+        state,
+        &[user_id],
+        &WebSocketMessage::ConversationStateChanged(conversation.clone()),
+    )
+    .await?;
+
+    Ok(Json(conversation))
+}
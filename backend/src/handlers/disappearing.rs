@@ -0,0 +1,247 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use sqlx::{Postgres, Transaction};
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::{
+    AppState,
+    error::AppError,
+    models::message::{DisappearingTimerResponse, Message, UpdateDisappearingTimerRequest},
+    auth::Claims,
+    handlers::conversations,
+    services::cache::CacheTag,
+    websocket::{fanout, validation::WebSocketMessage},
+};
+
+/// Timers a chat can be set to: 24 hours, 7 days or 90 days
+const TIMER_OPTIONS: [i32; 3] = [86_400, 604_800, 7_776_000];
+
+pub async fn get_direct_timer(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<DisappearingTimerResponse>, AppError> {
+    let (user_low, user_high) = ordered_pair(claims.sub, user_id);
+
+    let seconds = sqlx::query_scalar!(
+        r#"
+        SELECT disappearing_seconds FROM direct_chat_settings
+        WHERE user_low = $1 AND user_high = $2
+        "#,
+        user_low,
+        user_high
+    )
+    .fetch_optional(&state.pool)
+    .await?
+    .flatten();
+
+    Ok(Json(DisappearingTimerResponse { seconds }))
+}
+
+pub async fn update_direct_timer(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(user_id): Path<Uuid>,
+    Json(req): Json<UpdateDisappearingTimerRequest>,
+) -> Result<Json<DisappearingTimerResponse>, AppError> {
+    validate_timer(req.seconds)?;
+    if user_id == claims.sub {
+        return Err(AppError::BadRequest("Cannot set a timer on a chat with yourself".into()));
+    }
+
+    let conversation = conversations::direct_conversation(&state, claims.sub, user_id).await?;
+    let (user_low, user_high) = ordered_pair(claims.sub, user_id);
+    let mut tx = state.pool.begin().await?;
+
+    let previous = sqlx::query_scalar!(
+        r#"
+        SELECT disappearing_seconds FROM direct_chat_settings
+        WHERE user_low = $1 AND user_high = $2
+        FOR UPDATE
+        "#,
+        user_low,
+        user_high
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .flatten();
+
+    if previous == req.seconds {
+        tx.commit().await?;
+        return Ok(Json(DisappearingTimerResponse { seconds: req.seconds }));
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO direct_chat_settings (user_low, user_high, disappearing_seconds)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (user_low, user_high)
+        DO UPDATE SET disappearing_seconds = EXCLUDED.disappearing_seconds
+        "#,
+        user_low,
+        user_high,
+        req.seconds
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let message = announce_timer(&mut tx, claims.sub, user_id, conversation.id, req.seconds).await?;
+    tx.commit().await?;
+
+    let members = conversations::conversation_members(&state, conversation.id).await?;
+    fanout::send_to_users(&state, &members, &WebSocketMessage::DisappearingTimerChanged {
+        receiver_id: user_id,
+        seconds: req.seconds,
+        changed_by: claims.sub,
+        message,
+    })
+    .await?;
+
+    Ok(Json(DisappearingTimerResponse { seconds: req.seconds }))
+}
+
+pub async fn get_group_timer(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(group_id): Path<Uuid>,
+) -> Result<Json<DisappearingTimerResponse>, AppError> {
+    // Verify user is a member of the group
+    let seconds = sqlx::query_scalar!(
+        r#"
+        SELECT g.disappearing_seconds
+        FROM groups g
+        JOIN group_members gm ON gm.group_id = g.id
+        WHERE g.id = $1 AND gm.user_id = $2
+        "#,
+        group_id,
+        claims.sub
+    )
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or_else(|| AppError::Forbidden("Not a member of this group".into()))?;
+
+    Ok(Json(DisappearingTimerResponse { seconds }))
+}
+
+pub async fn update_group_timer(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(group_id): Path<Uuid>,
+    Json(req): Json<UpdateDisappearingTimerRequest>,
+) -> Result<Json<DisappearingTimerResponse>, AppError> {
+    validate_timer(req.seconds)?;
+
+    // Check if user is an admin or owner
+    let is_admin = sqlx::query!(
+        r#"
+        SELECT 1 as "exists!" FROM group_members
+        WHERE group_id = $1 AND user_id = $2 AND role IN ('owner', 'admin')
+        "#,
+        group_id,
+        claims.sub
+    )
+    .fetch_optional(&state.pool)
+    .await?
+    .is_some();
+
+    if !is_admin {
+        return Err(AppError::Forbidden("Only admins can change the disappearing messages timer".into()));
+    }
+
+    let mut tx = state.pool.begin().await?;
+
+    let previous = sqlx::query_scalar!(
+        "SELECT disappearing_seconds FROM groups WHERE id = $1 FOR UPDATE",
+        group_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if previous == req.seconds {
+        tx.commit().await?;
+        return Ok(Json(DisappearingTimerResponse { seconds: req.seconds }));
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE groups
+        SET disappearing_seconds = $1, updated_at = NOW()
+        WHERE id = $2
+        "#,
+        req.seconds,
+        group_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let message = announce_timer(&mut tx, claims.sub, group_id, group_id, req.seconds).await?;
+    tx.commit().await?;
+
+    state.cache.invalidate(&[CacheTag::Group(group_id)]).await;
+
+    let members = conversations::conversation_members(&state, group_id).await?;
+
+    fanout::send_to_users(&state, &members, &WebSocketMessage::DisappearingTimerChanged {
+        receiver_id: group_id,
+        seconds: req.seconds,
+        changed_by: claims.sub,
+        message,
+    })
+    .await?;
+
+    Ok(Json(DisappearingTimerResponse { seconds: req.seconds }))
+}
+
+/// Records the change in the chat itself so everyone sees who changed it and when.
+async fn announce_timer(
+    tx: &mut Transaction<'_, Postgres>,
+    changed_by: Uuid,
+    receiver_id: Uuid,
+    conversation_id: Uuid,
+    seconds: Option<i32>,
+) -> Result<Message, AppError> {
+    let content = match seconds {
+        Some(seconds) => format!("Disappearing messages set to {}", describe_timer(seconds)),
+        None => "Disappearing messages turned off".to_string(),
+    };
+
+    let message = sqlx::query_as!(
+        Message,
+        r#"
+        INSERT INTO messages (id, sender_id, receiver_id, conversation_id, content, created_at, is_system)
+        VALUES ($1, $2, $3, $4, $5, NOW(), true)
+        RETURNING *
+        "#,
+        Uuid::new_v4(),
+        changed_by,
+        receiver_id,
+        conversation_id,
+        content
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(message)
+}
+
+fn describe_timer(seconds: i32) -> String {
+    match seconds {
+        86_400 => "24 hours".to_string(),
+        seconds => format!("{} days", seconds / 86_400),
+    }
+}
+
+fn validate_timer(seconds: Option<i32>) -> Result<(), AppError> {
+    match seconds {
+        Some(seconds) if !TIMER_OPTIONS.contains(&seconds) => Err(AppError::BadRequest(
+            "Timer must be 24 hours, 7 days or 90 days".into(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn ordered_pair(a: Uuid, b: Uuid) -> (Uuid, Uuid) {
+    if a < b { (a, b) } else { (b, a) }
+}
@@ -0,0 +1,201 @@
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
+use crate::{
+    AppState,
+    error::AppError,
+    models::export::{ChatExport, ChatExportResponse, CreateExportRequest, ExportFormat, ExportStatus},
+    auth::Claims,
+    handlers::conversations::ensure_conversation,
+    services::chat_export::{self, DOWNLOAD_LINK_TTL_SECS},
+};
+
+const MAX_ACTIVE_EXPORTS: i64 = 3;
+
+#[derive(Debug, Deserialize)]
+pub struct DownloadQuery {
+    pub token: String,
+}
+
+/// Queues an export of a conversation's history. The export runs in the
+/// background; poll `GET /exports/:id` for its download link.
+pub async fn create_export(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(chat_id): Path<Uuid>,
+    Json(req): Json<CreateExportRequest>,
+) -> Result<(StatusCode, Json<ChatExportResponse>), AppError> {
+    ensure_conversation(&state, claims.sub, chat_id).await?;
+
+    let mut tx = state.pool.begin().await?;
+
+    // Serialize my exports so the limit holds
+    sqlx::query!(
+        "SELECT pg_advisory_xact_lock(hashtextextended($1, 0))",
+        format!("exports:{}", claims.sub)
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let active = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!" FROM chat_exports
+        WHERE user_id = $1 AND status IN ('pending', 'running')
+        "#,
+        claims.sub
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if active >= MAX_ACTIVE_EXPORTS {
+        return Err(AppError::TooManyRequests(format!(
+            "You can have at most {} exports in progress",
+            MAX_ACTIVE_EXPORTS
+        )));
+    }
+
+    let export = sqlx::query_as!(
+        ChatExport,
+        r#"
+        INSERT INTO chat_exports (id, user_id, chat_id, format, include_media)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id, user_id, chat_id, format as "format: _", include_media,
+            status as "status: _", file_name, file_size, message_count, last_error,
+            created_at, started_at, completed_at, expires_at
+        "#,
+        Uuid::new_v4(),
+        claims.sub,
+        chat_id,
+        req.format as ExportFormat,
+        req.include_media
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::ACCEPTED, Json(ChatExportResponse { export, download_url: None })))
+}
+
+/// My exports, newest first.
+pub async fn get_exports(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+) -> Result<Json<Vec<ChatExportResponse>>, AppError> {
+    let exports = sqlx::query_as!(
+        ChatExport,
+        r#"
+        SELECT id, user_id, chat_id, format as "format: _", include_media,
+            status as "status: _", file_name, file_size, message_count, last_error,
+            created_at, started_at, completed_at, expires_at
+        FROM chat_exports
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        "#,
+        claims.sub
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    let exports = exports
+        .into_iter()
+        .map(|export| with_download_url(&state, export))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Json(exports))
+}
+
+pub async fn get_export(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(export_id): Path<Uuid>,
+) -> Result<Json<ChatExportResponse>, AppError> {
+    let export = sqlx::query_as!(
+        ChatExport,
+        r#"
+        SELECT id, user_id, chat_id, format as "format: _", include_media,
+            status as "status: _", file_name, file_size, message_count, last_error,
+            created_at, started_at, completed_at, expires_at
+        FROM chat_exports
+        WHERE id = $1 AND user_id = $2
+        "#,
+        export_id,
+        claims.sub
+    )
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Export not found".into()))?;
+
+    Ok(Json(with_download_url(&state, export)?))
+}
+
+/// Serves a finished export to anyone holding a valid download link, so it
+/// can be opened straight from a browser.
+pub async fn download_export(
+    State(state): State<Arc<AppState>>,
+    Path(export_id): Path<Uuid>,
+    Query(query): Query<DownloadQuery>,
+) -> Result<Response, AppError> {
+    if chat_export::verify_download_token(&state.config.jwt_secret, &query.token)? != export_id {
+        return Err(AppError::Unauthorized("Invalid or expired download link".into()));
+    }
+
+    let export = sqlx::query!(
+        r#"
+        SELECT file_name, format as "format: ExportFormat", include_media
+        FROM chat_exports
+        WHERE id = $1 AND status = 'completed' AND expires_at > NOW()
+        "#,
+        export_id
+    )
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Export not found".into()))?;
+    let file_name = export
+        .file_name
+        .ok_or_else(|| AppError::NotFound("Export not found".into()))?;
+
+    let file = tokio::fs::File::open(chat_export::export_path(&state, &file_name))
+        .await
+        .map_err(|_| AppError::NotFound("Export not found".into()))?;
+    let content_type = if export.include_media {
+        "application/zip"
+    } else {
+        export.format.content_type()
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"chat-export-{}\"", file_name),
+            ),
+        ],
+        Body::from_stream(ReaderStream::new(file)),
+    )
+        .into_response())
+}
+
+/// Attaches a fresh download link to finished exports. Links never outlive
+/// the file they point to.
+fn with_download_url(state: &AppState, export: ChatExport) -> Result<ChatExportResponse, AppError> {
+    let download_url = match (export.status, export.expires_at) {
+        (ExportStatus::Completed, Some(expires_at)) if expires_at > Utc::now() => {
+            let link_expires_at = expires_at.min(Utc::now() + Duration::seconds(DOWNLOAD_LINK_TTL_SECS));
+            let token = chat_export::download_token(&state.config.jwt_secret, export.id, link_expires_at)?;
+            Some(format!("/exports/{}/download?token={}", export.id, token))
+        }
+        _ => None,
+    };
+
+    Ok(ChatExportResponse { export, download_url })
+}
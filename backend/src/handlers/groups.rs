@@ -9,11 +9,17 @@ use crate::{
     AppState,
     error::AppError,
     models::group::{
-        Group, GroupMember, GroupResponse, GroupMemberResponse,
+        Group, GroupMember, GroupPermission, GroupResponse, GroupMemberResponse,
         CreateGroupRequest, UpdateGroupRequest,
     },
+    models::privacy::PrivacySetting,
     auth::Claims,
+    services::{
+        cache::{CacheKey, CacheTag},
+        privacy::PrivacyRules,
+    },
 };
+use std::collections::HashMap;
 use std::sync::Arc;
 
 pub async fn create_group(
@@ -26,6 +32,18 @@ pub async fn create_group(
     // Start a transaction
     let mut tx = state.pool.begin().await?;
 
+    // The group shares its id with its conversation, which members are
+    // added to as they join
+    sqlx::query!(
+        r#"
+        INSERT INTO conversations (id, kind, created_at)
+        VALUES ($1, 'group', NOW())
+        "#,
+        group_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
     // Create the group
     let group = sqlx::query_as!(
         Group,
@@ -101,6 +119,8 @@ pub async fn create_group(
         updated_at: group.updated_at,
         is_private: group.is_private,
         max_members: group.max_members,
+        pin_permission: group.pin_permission,
+        mention_all_permission: group.mention_all_permission,
         member_count,
         role: crate::models::group::GroupRole::Owner,
     }))
@@ -128,15 +148,20 @@ pub async fn get_group(
         return Err(AppError::NotFound("Group not found or access denied".into()));
     }
 
-    let group = sqlx::query_as!(
-        Group,
-        r#"
-        SELECT * FROM groups
-        WHERE id = $1
-        "#,
-        group_id
-    )
-    .fetch_one(&state.pool)
+    let group: Group = state.cache.get_or_load(CacheKey::Group(group_id), || async {
+        let group = sqlx::query_as!(
+            Group,
+            r#"
+            SELECT * FROM groups
+            WHERE id = $1
+            "#,
+            group_id
+        )
+        .fetch_one(&state.pool)
+        .await?;
+
+        Ok(group)
+    })
     .await?;
 
     let member_count = sqlx::query!(
@@ -161,6 +186,8 @@ pub async fn get_group(
         updated_at: group.updated_at,
         is_private: group.is_private,
         max_members: group.max_members,
+        pin_permission: group.pin_permission,
+        mention_all_permission: group.mention_all_permission,
         member_count,
         role: member.unwrap().role,
     }))
@@ -200,8 +227,10 @@ pub async fn update_group(
             avatar_url = COALESCE($3, avatar_url),
             is_private = COALESCE($4, is_private),
             max_members = COALESCE($5, max_members),
+            pin_permission = COALESCE($6, pin_permission),
+            mention_all_permission = COALESCE($7, mention_all_permission),
             updated_at = NOW()
-        WHERE id = $6
+        WHERE id = $8
         RETURNING *
         "#,
         req.name,
@@ -209,11 +238,15 @@ pub async fn update_group(
         req.avatar_url,
         req.is_private,
         req.max_members,
+        req.pin_permission as Option<GroupPermission>,
+        req.mention_all_permission as Option<GroupPermission>,
         group_id
     )
     .fetch_one(&state.pool)
     .await?;
 
+    state.cache.invalidate(&[CacheTag::Group(group_id)]).await;
+
     let member_count = sqlx::query!(
         r#"
         SELECT COUNT(*) as count
@@ -236,6 +269,8 @@ pub async fn update_group(
         updated_at: group.updated_at,
         is_private: group.is_private,
         max_members: group.max_members,
+        pin_permission: group.pin_permission,
+        mention_all_permission: group.mention_all_permission,
         member_count,
         role: member.unwrap().role,
     }))
@@ -263,41 +298,72 @@ pub async fn get_group_members(
         return Err(AppError::NotFound("Group not found or access denied".into()));
     }
 
-    let members = sqlx::query!(
+    // Tagged with every member so profile changes drop the cached list too.
+    // Read watermarks move too often to cache, so they're filled in below
+    let mut members: Vec<GroupMemberResponse> = state.cache.get_or_load_tagged(CacheKey::GroupMembers(group_id), || async {
+        let members = sqlx::query!(
+            r#"
+            SELECT 
+                gm.id,
+                gm.user_id,
+                u.display_name,
+                u.avatar_url,
+                gm.role,
+                gm.joined_at
+            FROM group_members gm
+            JOIN users u ON u.id = gm.user_id
+            WHERE gm.group_id = $1
+            ORDER BY 
+                CASE gm.role
+                    WHEN 'owner' THEN 1
+                    WHEN 'admin' THEN 2
+                    ELSE 3
+                END,
+                gm.joined_at
+            "#,
+            group_id
+        )
+        .fetch_all(&state.pool)
+        .await?;
+
+        let tags = members.iter().map(|m| CacheTag::User(m.user_id)).collect();
+        let members: Vec<GroupMemberResponse> = members.into_iter().map(|m| GroupMemberResponse {
+            id: m.id,
+            user_id: m.user_id,
+            display_name: m.display_name,
+            avatar_url: m.avatar_url,
+            role: m.role.into(),
+            joined_at: m.joined_at,
+            last_read_at: None,
+        }).collect();
+
+        Ok((members, tags))
+    })
+    .await?;
+
+    let watermarks: HashMap<_, _> = sqlx::query!(
         r#"
-        SELECT 
-            gm.id,
-            gm.user_id,
-            u.display_name,
-            u.avatar_url,
-            gm.role,
-            gm.joined_at,
-            gm.last_read_at
-        FROM group_members gm
-        JOIN users u ON u.id = gm.user_id
-        WHERE gm.group_id = $1
-        ORDER BY 
-            CASE gm.role
-                WHEN 'owner' THEN 1
-                WHEN 'admin' THEN 2
-                ELSE 3
-            END,
-            gm.joined_at
+        SELECT user_id, last_read_at FROM conversation_members
+        WHERE conversation_id = $1
         "#,
         group_id
     )
     .fetch_all(&state.pool)
-    .await?;
+    .await?
+    .into_iter()
+    .filter_map(|row| Some((row.user_id, row.last_read_at?)))
+    .collect();
+    // Watermarks are read receipts, so they follow the same privacy setting
+    let member_ids: Vec<Uuid> = members.iter().map(|member| member.user_id).collect();
+    let rules = PrivacyRules::load(&state.pool, &member_ids, &[claims.sub]).await?;
+    for member in &mut members {
+        member.last_read_at = watermarks
+            .get(&member.user_id)
+            .copied()
+            .filter(|_| rules.allows(member.user_id, claims.sub, PrivacySetting::ReadReceipts));
+    }
 
-    Ok(Json(members.into_iter().map(|m| GroupMemberResponse {
-        id: m.id,
-        user_id: m.user_id,
-        display_name: m.display_name,
-        avatar_url: m.avatar_url,
-        role: m.role.into(),
-        joined_at: m.joined_at,
-        last_read_at: m.last_read_at,
-    }).collect()))
+    Ok(Json(members))
 }
 
 pub async fn add_group_member(
@@ -365,6 +431,8 @@ pub async fn add_group_member(
     .fetch_one(&state.pool)
     .await?;
 
+    state.cache.invalidate(&[CacheTag::Group(group_id)]).await;
+
     // Get user info
     let user = sqlx::query!(
         r#"
@@ -384,7 +452,7 @@ pub async fn add_group_member(
         avatar_url: user.avatar_url,
         role: new_member.role,
         joined_at: new_member.joined_at,
-        last_read_at: new_member.last_read_at,
+        last_read_at: None,
     }))
 }
 
@@ -441,5 +509,19 @@ pub async fn remove_group_member(
     .execute(&state.pool)
     .await?;
 
+    // Archive, mute and pin settings don't outlive membership
+    sqlx::query!(
+        r#"
+        DELETE FROM conversation_states
+        WHERE user_id = $1 AND conversation_id = $2
+        "#,
+        user_id,
+        group_id
+    )
+    .execute(&state.pool)
+    .await?;
+
+    state.cache.invalidate(&[CacheTag::Group(group_id)]).await;
+
     Ok(StatusCode::NO_CONTENT)
 } 
\ No newline at end of file
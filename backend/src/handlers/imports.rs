@@ -0,0 +1,241 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use sqlx::{Postgres, Transaction};
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::{
+    AppState,
+    error::AppError,
+    models::{
+        conversation::Conversation,
+        import::{ImportChatRequest, ImportParticipant, ImportResult},
+    },
+    auth::Claims,
+    handlers::conversations,
+    services::whatsapp_import::{self, ParsedMessage},
+    websocket::{fanout, validation::WebSocketMessage},
+};
+
+/// Exports are large, so the route gets a higher body limit than the default.
+pub const MAX_IMPORT_BYTES: usize = 32 * 1024 * 1024;
+const INSERT_BATCH_SIZE: usize = 1000;
+
+/// Adds the history from a WhatsApp `.txt` export to a direct chat or group,
+/// before or alongside what's already there. Messages keep their original
+/// timestamps and order and are marked as imported.
+///
+/// Senders are matched to members of the chat; in groups, anyone left over
+/// gets a placeholder identity so their messages still show under their
+/// name. Importing into a group is limited to its admins. A direct chat
+/// must already exist, and the other person's lines always go to a
+/// placeholder so nobody can put words in a real account's mouth.
+pub async fn import_chat(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(chat_id): Path<Uuid>,
+    Json(req): Json<ImportChatRequest>,
+) -> Result<Json<ImportResult>, AppError> {
+    let conversation = authorize_import(&state, claims.sub, chat_id).await?;
+
+    let parsed = whatsapp_import::parse_chat(&req.text, req.date_order)?;
+    let timestamps = whatsapp_import::sequence_timestamps(&parsed, req.utc_offset_minutes)?;
+
+    let members = chat_members(&state, conversation.id).await?;
+    if let Some(user_id) = req.participants.values().find(|id| !members.iter().any(|(m, _)| m == *id)) {
+        return Err(AppError::BadRequest(format!("{} is not a member of this chat", user_id)));
+    }
+
+    let mut names: Vec<String> = Vec::new();
+    for name in parsed.iter().filter_map(|message| message.sender.as_ref()) {
+        if !names.contains(name) {
+            names.push(name.clone());
+        }
+    }
+    let mut matched = whatsapp_import::match_participants(&names, &req.participants, &members);
+
+    // In a direct chat only the importer's own lines keep a real account
+    if !conversation.is_group() {
+        let mine = whatsapp_import::importer_names(&names, &matched, claims.sub)?;
+        matched.retain(|name, _| mine.contains(name));
+        for name in mine {
+            matched.insert(name, claims.sub);
+        }
+    }
+    let unmatched: Vec<String> = names.iter().filter(|name| !matched.contains_key(*name)).cloned().collect();
+
+    let mut tx = state.pool.begin().await?;
+    let mut placeholders = Vec::new();
+
+    // Placeholders from an earlier import of the same chat are reused
+    let existing = sqlx::query!(
+        r#"
+        SELECT DISTINCT u.id, u.display_name as "display_name!"
+        FROM messages m
+        JOIN users u ON u.id = m.sender_id
+        WHERE m.conversation_id = $1 AND m.is_imported AND u.is_placeholder AND u.display_name IS NOT NULL
+        "#,
+        conversation.id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    for name in unmatched {
+        let user_id = match existing.iter().find(|row| row.display_name == name) {
+            Some(row) => row.id,
+            None => create_placeholder(&mut tx, &name).await?,
+        };
+        placeholders.push(user_id);
+        matched.insert(name, user_id);
+    }
+
+    for (batch, times) in parsed.chunks(INSERT_BATCH_SIZE).zip(timestamps.chunks(INSERT_BATCH_SIZE)) {
+        insert_batch(&mut tx, claims.sub, chat_id, &conversation, &matched, batch, times).await?;
+    }
+
+    tx.commit().await?;
+
+    let members = conversations::conversation_members(&state, conversation.id).await?;
+    fanout::send_to_users(&state, &members, &WebSocketMessage::HistoryImported {
+        receiver_id: chat_id,
+        imported_by: claims.sub,
+        message_count: parsed.len(),
+    })
+    .await?;
+
+    let participants = names
+        .into_iter()
+        .map(|name| {
+            let user_id = matched[&name];
+            ImportParticipant { name, user_id, is_placeholder: placeholders.contains(&user_id) }
+        })
+        .collect();
+
+    Ok(Json(ImportResult { message_count: parsed.len(), participants }))
+}
+
+/// Groups need an admin; direct chats need to have been started already.
+async fn authorize_import(state: &AppState, user_id: Uuid, chat_id: Uuid) -> Result<Conversation, AppError> {
+    let role = sqlx::query_scalar!(
+        r#"
+        SELECT gm.role::text as "role?"
+        FROM groups g
+        LEFT JOIN group_members gm ON gm.group_id = g.id AND gm.user_id = $2
+        WHERE g.id = $1
+        "#,
+        chat_id,
+        user_id
+    )
+    .fetch_optional(&state.pool)
+    .await?;
+
+    match role {
+        Some(Some(role)) if role == "owner" || role == "admin" => {
+            conversations::ensure_group_member(state, user_id, chat_id).await
+        }
+        Some(Some(_)) => Err(AppError::Forbidden("Only admins can import history into a group".into())),
+        Some(None) => Err(AppError::NotFound("Conversation not found".into())),
+        None => conversations::find_conversation(state, user_id, chat_id)
+            .await?
+            .filter(|conversation| !conversation.is_group())
+            .ok_or_else(|| AppError::NotFound("Conversation not found".into())),
+    }
+}
+
+/// Everyone who can appear as a sender, with the names they might go by.
+async fn chat_members(state: &AppState, conversation_id: Uuid) -> Result<Vec<(Uuid, Vec<String>)>, AppError> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT u.id, u.username, u.display_name
+        FROM conversation_members cm
+        JOIN users u ON u.id = cm.user_id
+        WHERE cm.conversation_id = $1
+        "#,
+        conversation_id
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.id, row.display_name.into_iter().chain([row.username]).collect()))
+        .collect())
+}
+
+async fn create_placeholder(tx: &mut Transaction<'_, Postgres>, name: &str) -> Result<Uuid, AppError> {
+    let id = Uuid::new_v4();
+    let display_name: String = name.chars().take(255).collect();
+
+    // The password hash matches no password, so nobody can sign in as one
+    sqlx::query!(
+        r#"
+        INSERT INTO users (id, email, username, password_hash, display_name, is_placeholder)
+        VALUES ($1, $2, $3, '!', $4, true)
+        "#,
+        id,
+        format!("imported+{}@placeholder.invalid", id.simple()),
+        format!("imported_{}", id.simple()),
+        display_name
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(id)
+}
+
+async fn insert_batch(
+    tx: &mut Transaction<'_, Postgres>,
+    importer_id: Uuid,
+    chat_id: Uuid,
+    conversation: &Conversation,
+    senders: &HashMap<String, Uuid>,
+    messages: &[ParsedMessage],
+    timestamps: &[DateTime<Utc>],
+) -> Result<(), AppError> {
+    let mut ids = Vec::with_capacity(messages.len());
+    let mut sender_ids = Vec::with_capacity(messages.len());
+    let mut receiver_ids = Vec::with_capacity(messages.len());
+    let mut contents = Vec::with_capacity(messages.len());
+    let mut is_system = Vec::with_capacity(messages.len());
+    let mut is_edited = Vec::with_capacity(messages.len());
+    let mut is_deleted = Vec::with_capacity(messages.len());
+
+    for message in messages {
+        // System lines are attributed to whoever imported them
+        let sender_id = message.sender.as_ref().map_or(importer_id, |name| senders[name]);
+        let receiver_id = if conversation.is_group() || sender_id == importer_id { chat_id } else { importer_id };
+
+        ids.push(Uuid::new_v4());
+        sender_ids.push(sender_id);
+        receiver_ids.push(receiver_id);
+        contents.push(message.content.clone());
+        is_system.push(message.sender.is_none());
+        is_edited.push(message.is_edited);
+        is_deleted.push(message.is_deleted);
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO messages (id, sender_id, receiver_id, conversation_id, content, created_at, is_system, is_edited, is_deleted, is_imported)
+        SELECT id, sender_id, receiver_id, $9, content, created_at, is_system, is_edited, is_deleted, true
+        FROM UNNEST($1::uuid[], $2::uuid[], $3::uuid[], $4::text[], $5::timestamptz[], $6::bool[], $7::bool[], $8::bool[])
+            AS t(id, sender_id, receiver_id, content, created_at, is_system, is_edited, is_deleted)
+        "#,
+        &ids,
+        &sender_ids,
+        &receiver_ids,
+        &contents,
+        timestamps,
+        &is_system,
+        &is_edited,
+        &is_deleted,
+        conversation.id
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
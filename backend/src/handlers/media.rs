@@ -5,7 +5,6 @@ use axum::{
     Json,
 };
 use axum_extra::extract::multipart::Multipart;
-use sqlx::PgPool;
 use uuid::Uuid;
 use crate::{
     AppState,
@@ -15,9 +14,6 @@ use crate::{
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use cloudinary::api::upload::Upload;
-use cloudinary::api::delete::Delete;
-use cloudinary::Cloudinary;
 use tracing::{error, info};
 
 const MAX_FILE_SIZE: usize = 10 * 1024 * 1024; // 10MB
@@ -26,16 +22,10 @@ const ALLOWED_VIDEO_TYPES: [&str; 2] = ["video/mp4", "video/webm"];
 const ALLOWED_AUDIO_TYPES: [&str; 2] = ["audio/mpeg", "audio/wav"];
 
 pub async fn upload_media(
-    State(pool): State<PgPool>,
+    State(state): State<Arc<AppState>>,
     claims: Claims,
     mut multipart: Multipart,
 ) -> Result<Json<MediaUploadResponse>, AppError> {
-    let cloudinary = Cloudinary::new(
-        std::env::var("CLOUDINARY_CLOUD_NAME").unwrap_or_default(),
-        std::env::var("CLOUDINARY_API_KEY").unwrap_or_default(),
-        std::env::var("CLOUDINARY_API_SECRET").unwrap_or_default(),
-    );
-
     let mut file = None;
     let mut media_type = None;
     let mut content_type = None;
@@ -97,7 +87,7 @@ pub async fn upload_media(
         )));
     }
 
-    // Upload to Cloudinary
+    // Upload to media storage
     let resource_type = match media_type.as_str() {
         "image" => "image",
         "video" => "video",
@@ -105,15 +95,7 @@ pub async fn upload_media(
         _ => "auto",
     };
 
-    let upload_result = cloudinary.upload()
-        .resource_type(resource_type)
-        .file(&file)
-        .execute()
-        .await
-        .map_err(|e| {
-            error!("Failed to upload to Cloudinary: {}", e);
-            AppError::InternalServerError(format!("Failed to upload to Cloudinary: {}", e))
-        })?;
+    let upload_result = state.media_storage.upload(&file, resource_type).await?;
 
     // Store media info in database
     let media_id = Uuid::new_v4();
@@ -125,10 +107,10 @@ pub async fn upload_media(
         media_id,
         claims.sub,
         media_type,
-        upload_result.secure_url,
+        upload_result.url,
         upload_result.public_id
     )
-    .execute(&pool)
+    .execute(&state.pool)
     .await
     .map_err(|e| {
         error!("Failed to store media info: {}", e);
@@ -138,13 +120,13 @@ pub async fn upload_media(
     info!("Media uploaded successfully: {}", media_id);
     Ok(Json(MediaUploadResponse {
         id: media_id,
-        url: upload_result.secure_url,
+        url: upload_result.url,
         type_: media_type,
     }))
 }
 
 pub async fn delete_media(
-    State(pool): State<PgPool>,
+    State(state): State<Arc<AppState>>,
     claims: Claims,
     Path(media_id): Path<Uuid>,
 ) -> Result<StatusCode, StatusCode> {
@@ -156,7 +138,7 @@ pub async fn delete_media(
         "#,
         media_id
     )
-    .fetch_optional(&pool)
+    .fetch_optional(&state.pool)
     .await
     .map_err(|e| {
         error!("Failed to fetch media info: {}", e);
@@ -172,21 +154,11 @@ pub async fn delete_media(
         return Err(StatusCode::FORBIDDEN);
     }
 
-    // Delete from Cloudinary
-    let cloudinary = Cloudinary::new(
-        std::env::var("CLOUDINARY_CLOUD_NAME").unwrap_or_default(),
-        std::env::var("CLOUDINARY_API_KEY").unwrap_or_default(),
-        std::env::var("CLOUDINARY_API_SECRET").unwrap_or_default(),
-    );
-
-    cloudinary.delete()
-        .public_id(&media.public_id)
-        .execute()
+    // Delete from media storage
+    state.media_storage
+        .delete(&media.public_id)
         .await
-        .map_err(|e| {
-            error!("Failed to delete from Cloudinary: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     // Delete from database
     sqlx::query!(
@@ -196,7 +168,7 @@ pub async fn delete_media(
         "#,
         media_id
     )
-    .execute(&pool)
+    .execute(&state.pool)
     .await
     .map_err(|e| {
         error!("Failed to delete media from database: {}", e);
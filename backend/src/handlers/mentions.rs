@@ -0,0 +1,473 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+use sqlx::{types::Json as SqlJson, Postgres, Transaction};
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::{
+    AppState,
+    error::AppError,
+    models::{
+        conversation::Conversation,
+        group::GroupPermission,
+        message::{ChatContext, MentionEntity, MentionKind, Message, MentionResponse, UnreadMentionCount},
+        pagination::{Cursor, MessagePage, Paginated},
+    },
+    auth::Claims,
+    handlers::conversations,
+    websocket::{fanout, validation::WebSocketMessage},
+};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 100;
+const MAX_MENTIONS: usize = 50;
+
+#[derive(Debug, Deserialize)]
+pub struct MentionsQuery {
+    /// Cursor to load older mentions from
+    pub before: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Mentions of the requesting user across all chats, newest first.
+pub async fn get_mentions(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Query(query): Query<MentionsQuery>,
+) -> Result<Json<MessagePage<MentionResponse>>, AppError> {
+    let before = query.before.as_deref().map(Cursor::decode).transpose()?;
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+    let rows = sqlx::query!(
+        r#"
+        SELECT m.id, m.content, m.media_url, m.created_at, m.sender_id, mr.read_at,
+            u.display_name as sender_name, u.avatar_url as sender_avatar,
+            g.id as "group_id?", g.name as "group_name?", g.avatar_url as "group_avatar?",
+            message_mention_entities(m.id) as "mentions!: SqlJson<Vec<MentionEntity>>"
+        FROM mention_recipients mr
+        JOIN messages m ON m.id = mr.message_id
+        -- Mentions in groups I've left drop out
+        JOIN conversation_members cm ON cm.conversation_id = m.conversation_id AND cm.user_id = $1
+        JOIN users u ON u.id = m.sender_id
+        LEFT JOIN groups g ON g.id = m.conversation_id
+        WHERE mr.user_id = $1
+            AND NOT m.is_deleted
+            AND (m.expires_at IS NULL OR m.expires_at > NOW())
+            AND NOT EXISTS (SELECT 1 FROM hidden_messages h WHERE h.message_id = m.id AND h.user_id = $1)
+            AND ($2::timestamptz IS NULL OR (m.created_at, m.id) < ($2, $3::uuid))
+        ORDER BY m.created_at DESC, m.id DESC
+        LIMIT $4
+        "#,
+        claims.sub,
+        before.map(|cursor| cursor.created_at),
+        before.map(|cursor| cursor.id),
+        limit + 1
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    let has_more_before = rows.len() as i64 > limit;
+    let messages: Vec<MentionResponse> = rows
+        .into_iter()
+        .take(limit as usize)
+        .map(|row| {
+            // In a direct chat the other side is whoever mentioned me
+            let chat = match row.group_id {
+                Some(group_id) => ChatContext {
+                    id: group_id,
+                    name: row.group_name.unwrap_or_default(),
+                    avatar_url: row.group_avatar,
+                    is_group: true,
+                },
+                None => ChatContext {
+                    id: row.sender_id,
+                    name: row.sender_name.clone(),
+                    avatar_url: row.sender_avatar.clone(),
+                    is_group: false,
+                },
+            };
+
+            MentionResponse {
+                id: row.id,
+                content: row.content,
+                media_url: row.media_url,
+                created_at: row.created_at,
+                sender_id: row.sender_id,
+                sender_name: row.sender_name,
+                sender_avatar: row.sender_avatar,
+                chat,
+                mentions: row.mentions.0,
+                is_read: row.read_at.is_some(),
+            }
+        })
+        .collect();
+
+    Ok(Json(MessagePage {
+        before_cursor: messages.last().map(|message| message.cursor().encode()),
+        after_cursor: None,
+        has_more_before,
+        has_more_after: false,
+        messages,
+    }))
+}
+
+/// Unread mentions per chat, for chats that have any.
+pub async fn get_unread_mentions(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+) -> Result<Json<Vec<UnreadMentionCount>>, AppError> {
+    let counts = sqlx::query_as!(
+        UnreadMentionCount,
+        r#"
+        SELECT
+            CASE WHEN g.id IS NULL THEN m.sender_id ELSE g.id END as "chat_id!",
+            g.id IS NOT NULL as "is_group!",
+            COUNT(*) as "count!"
+        FROM mention_recipients mr
+        JOIN messages m ON m.id = mr.message_id
+        JOIN conversation_members cm ON cm.conversation_id = m.conversation_id AND cm.user_id = $1
+        LEFT JOIN groups g ON g.id = m.conversation_id
+        WHERE mr.user_id = $1
+            AND mr.read_at IS NULL
+            AND NOT m.is_deleted
+            AND (m.expires_at IS NULL OR m.expires_at > NOW())
+            AND NOT EXISTS (SELECT 1 FROM hidden_messages h WHERE h.message_id = m.id AND h.user_id = $1)
+        GROUP BY 1, 2
+        "#,
+        claims.sub
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(Json(counts))
+}
+
+/// Marks every mention of the user in a chat as read. `chat_id` is the group,
+/// or the other side of a direct chat.
+pub async fn mark_mentions_read(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(chat_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    mark_read(&state, claims.sub, chat_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub(crate) async fn mark_read(state: &AppState, user_id: Uuid, chat_id: Uuid) -> Result<(), AppError> {
+    let Some(conversation) = conversations::find_conversation(state, user_id, chat_id).await? else {
+        return Ok(());
+    };
+
+    let result = sqlx::query!(
+        r#"
+        UPDATE mention_recipients mr
+        SET read_at = NOW()
+        FROM messages m
+        WHERE m.id = mr.message_id
+            AND mr.user_id = $1
+            AND mr.read_at IS NULL
+            AND m.conversation_id = $2
+        "#,
+        user_id,
+        conversation.id
+    )
+    .execute(&state.pool)
+    .await?;
+
+    if result.rows_affected() > 0 {
+        fanout::send_to_users(state, &[user_id], &WebSocketMessage::MentionsRead { chat_id }).await?;
+    }
+
+    Ok(())
+}
+
+/// Mentions of a message, validated, and everyone they reach.
+#[derive(Debug, Default)]
+pub(crate) struct ResolvedMentions {
+    pub entities: Vec<MentionEntity>,
+    pub recipients: Vec<Uuid>,
+}
+
+struct Participant {
+    username: String,
+    is_admin: bool,
+}
+
+/// Validates the mentions a sender supplied, or parses them from the content
+/// when none were supplied.
+pub(crate) async fn resolve_mentions(
+    state: &AppState,
+    sender_id: Uuid,
+    conversation: &Conversation,
+    content: &str,
+    supplied: Option<Vec<MentionEntity>>,
+) -> Result<ResolvedMentions, AppError> {
+    if supplied.as_ref().is_some_and(|mentions| mentions.is_empty())
+        || (supplied.is_none() && !content.contains('@'))
+    {
+        return Ok(ResolvedMentions::default());
+    }
+
+    let is_group = conversation.is_group();
+    let (participants, can_mention_all) = load_participants(state, sender_id, conversation).await?;
+
+    let entities = match supplied {
+        Some(entities) => {
+            validate_mentions(content, &entities, &participants, is_group, can_mention_all)?;
+            entities
+        }
+        None => parse_mentions(content, &participants, is_group, can_mention_all),
+    };
+
+    let mut recipients: Vec<Uuid> = Vec::new();
+    for entity in &entities {
+        let reached: Vec<Uuid> = match entity.kind {
+            MentionKind::User => entity.user_id.into_iter().collect(),
+            MentionKind::All => participants.keys().copied().collect(),
+            MentionKind::Admins => participants
+                .iter()
+                .filter(|(_, participant)| participant.is_admin)
+                .map(|(id, _)| *id)
+                .collect(),
+        };
+        for user_id in reached {
+            if user_id != sender_id && !recipients.contains(&user_id) {
+                recipients.push(user_id);
+            }
+        }
+    }
+
+    Ok(ResolvedMentions { entities, recipients })
+}
+
+/// Replaces the stored mentions of a message. Recipients who are no longer
+/// mentioned are dropped; those still mentioned keep their read state.
+/// Returns the newly mentioned users.
+pub(crate) async fn store_mentions(
+    tx: &mut Transaction<'_, Postgres>,
+    message_id: Uuid,
+    mentions: &ResolvedMentions,
+) -> Result<Vec<Uuid>, AppError> {
+    sqlx::query!("DELETE FROM message_mentions WHERE message_id = $1", message_id)
+        .execute(&mut **tx)
+        .await?;
+
+    for entity in &mentions.entities {
+        sqlx::query!(
+            r#"
+            INSERT INTO message_mentions (message_id, kind, user_id, utf16_offset, utf16_length)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            message_id,
+            entity.kind as MentionKind,
+            entity.user_id,
+            entity.offset,
+            entity.length
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    sqlx::query!(
+        "DELETE FROM mention_recipients WHERE message_id = $1 AND user_id <> ALL($2)",
+        message_id,
+        &mentions.recipients
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    let added = sqlx::query_scalar!(
+        r#"
+        INSERT INTO mention_recipients (message_id, user_id)
+        SELECT $1, UNNEST($2::uuid[])
+        ON CONFLICT (message_id, user_id) DO NOTHING
+        RETURNING user_id
+        "#,
+        message_id,
+        &mentions.recipients
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    Ok(added)
+}
+
+/// Tells mentioned users about the message, unless they've muted the chat.
+pub(crate) async fn notify_mentioned(
+    state: &AppState,
+    recipients: &[Uuid],
+    message: &Message,
+) -> Result<(), AppError> {
+    let recipients = conversations::unmuted(state, recipients, message.conversation_id).await?;
+    if recipients.is_empty() {
+        return Ok(());
+    }
+
+    fanout::send_to_users(state, &recipients, &WebSocketMessage::Mentioned {
+        message_id: message.id,
+        receiver_id: message.receiver_id,
+        sender_id: message.sender_id,
+    })
+    .await
+}
+
+/// Everyone who can be mentioned, keyed by user id, and whether the sender
+/// may use `@all` and `@admins`.
+async fn load_participants(
+    state: &AppState,
+    sender_id: Uuid,
+    conversation: &Conversation,
+) -> Result<(HashMap<Uuid, Participant>, bool), AppError> {
+    // Roles and the @all permission only exist in groups
+    let members = sqlx::query!(
+        r#"
+        SELECT cm.user_id, u.username,
+            COALESCE(gm.role IN ('owner', 'admin'), false) as "is_admin!",
+            g.mention_all_permission as "mention_all_permission?: GroupPermission"
+        FROM conversation_members cm
+        JOIN users u ON u.id = cm.user_id
+        LEFT JOIN groups g ON g.id = cm.conversation_id
+        LEFT JOIN group_members gm ON gm.group_id = g.id AND gm.user_id = cm.user_id
+        WHERE cm.conversation_id = $1
+        "#,
+        conversation.id
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    let can_mention_all = conversation.is_group()
+        && members.iter().any(|member| {
+            member.user_id == sender_id
+                && (member.is_admin || member.mention_all_permission == Some(GroupPermission::Members))
+        });
+    let participants = members
+        .into_iter()
+        .map(|member| (member.user_id, Participant { username: member.username, is_admin: member.is_admin }))
+        .collect();
+
+    Ok((participants, can_mention_all))
+}
+
+fn validate_mentions(
+    content: &str,
+    entities: &[MentionEntity],
+    participants: &HashMap<Uuid, Participant>,
+    is_group: bool,
+    can_mention_all: bool,
+) -> Result<(), AppError> {
+    if entities.len() > MAX_MENTIONS {
+        return Err(AppError::BadRequest(format!(
+            "A message cannot have more than {} mentions",
+            MAX_MENTIONS
+        )));
+    }
+
+    let units: Vec<u16> = content.encode_utf16().collect();
+    let mut spans: Vec<(i32, i32)> = Vec::with_capacity(entities.len());
+    for entity in entities {
+        let in_bounds = entity.offset >= 0
+            && entity.length > 1
+            && (entity.offset as usize + entity.length as usize) <= units.len();
+        if !in_bounds || units[entity.offset as usize] != u16::from(b'@') {
+            return Err(AppError::BadRequest("Mention does not match the message content".into()));
+        }
+
+        match (entity.kind, entity.user_id) {
+            (MentionKind::User, Some(user_id)) if participants.contains_key(&user_id) => {}
+            (MentionKind::User, Some(_)) => {
+                return Err(AppError::BadRequest("Mentioned user is not in this chat".into()));
+            }
+            (MentionKind::User, None) => {
+                return Err(AppError::BadRequest("User mentions need a user_id".into()));
+            }
+            (_, Some(_)) => {
+                return Err(AppError::BadRequest("Only user mentions can have a user_id".into()));
+            }
+            (_, None) if !is_group => {
+                return Err(AppError::BadRequest("@all and @admins can only be used in groups".into()));
+            }
+            (_, None) if !can_mention_all => {
+                return Err(AppError::Forbidden("Only admins can use @all and @admins in this group".into()));
+            }
+            (_, None) => {}
+        }
+
+        spans.push((entity.offset, entity.offset + entity.length));
+    }
+
+    spans.sort_unstable();
+    if spans.windows(2).any(|pair| pair[0].1 > pair[1].0) {
+        return Err(AppError::BadRequest("Mentions cannot overlap".into()));
+    }
+
+    Ok(())
+}
+
+/// Finds `@username`, `@all` and `@admins` in the content. Anything that
+/// doesn't name someone the sender can mention is left as plain text.
+fn parse_mentions(
+    content: &str,
+    participants: &HashMap<Uuid, Participant>,
+    is_group: bool,
+    can_mention_all: bool,
+) -> Vec<MentionEntity> {
+    let is_name_char = |c: char| c.is_alphanumeric() || c == '_' || c == '.' || c == '-';
+
+    let mut entities = Vec::new();
+    let mut offset = 0usize;
+    let mut previous: Option<char> = None;
+    let mut chars = content.char_indices();
+    while let Some((index, c)) = chars.next() {
+        let starts_mention = c == '@' && !previous.is_some_and(is_name_char);
+        previous = Some(c);
+        if !starts_mention {
+            offset += c.len_utf16();
+            continue;
+        }
+
+        let rest = &content[index + 1..];
+        let name = rest
+            .split(|c: char| !is_name_char(c))
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches('.');
+
+        let mention = match name.to_lowercase().as_str() {
+            "" => None,
+            "all" if is_group && can_mention_all => Some((MentionKind::All, None)),
+            "admins" if is_group && can_mention_all => Some((MentionKind::Admins, None)),
+            lowered => participants
+                .iter()
+                .find(|(_, participant)| participant.username.to_lowercase() == lowered)
+                .map(|(id, _)| (MentionKind::User, Some(*id))),
+        };
+
+        let Some((kind, user_id)) = mention else {
+            offset += c.len_utf16();
+            continue;
+        };
+
+        let length = 1 + name.encode_utf16().count();
+        entities.push(MentionEntity {
+            kind,
+            user_id,
+            offset: offset as i32,
+            length: length as i32,
+        });
+        if entities.len() == MAX_MENTIONS {
+            break;
+        }
+
+        // Skip past the name so it isn't scanned again
+        offset += length;
+        for _ in 0..name.chars().count() {
+            previous = chars.next().map(|(_, c)| c);
+        }
+    }
+
+    entities
+}
@@ -1,67 +1,127 @@
 use axum::{
     extract::{Path, State, Query},
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
+use chrono::{DateTime, Duration, Utc};
 use serde::Deserialize;
-use sqlx::PgPool;
+use sqlx::{types::Json as SqlJson, PgPool};
 use uuid::Uuid;
 use crate::{
     AppState,
     error::AppError,
     models::{
+        conversation::{Conversation, ConversationKind},
         message::{
             Message, MessageResponse, GroupMessageResponse,
-            CreateMessageRequest, UpdateMessageRequest,
+            CreateMessageRequest, UpdateMessageRequest, MentionEntity, FormattingEntity, LinkPreview,
+            MessageThreadResponse, MessageVersion, QuotedMessage,
+            ScheduledMessage, ScheduledMessageStatus, UpdateScheduledMessageRequest,
         },
+        pagination::{Cursor, MessagePage, Paginated},
+        status::StatusReplyPreview,
     },
     auth::Claims,
+    handlers::{conversations, mentions, status::status_reply_preview},
+    services::{formatting::validate_formatting, idempotency, link_preview, media_cleanup},
+    websocket::{fanout, validation::WebSocketMessage},
 };
+use std::future::Future;
 use std::sync::Arc;
 
 const MAX_MESSAGE_LENGTH: usize = 4000;
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 100;
+const MAX_THREAD_SIZE: i64 = 500;
+const MAX_SCHEDULE_AHEAD_DAYS: i64 = 365;
+const MAX_PENDING_SCHEDULED_MESSAGES: i64 = 100;
 
 #[derive(Debug, Deserialize)]
 pub struct MessageQuery {
-    pub before: Option<chrono::DateTime<chrono::Utc>>,
+    /// Cursor to load older messages from
+    pub before: Option<String>,
+    /// Cursor to load newer messages from
+    pub after: Option<String>,
+    /// Message to center the page on, e.g. a search hit
+    pub around: Option<Uuid>,
     pub limit: Option<i64>,
 }
 
+impl MessageQuery {
+    fn cursors(&self) -> Result<(Option<Cursor>, Option<Cursor>), AppError> {
+        let given = [self.before.is_some(), self.after.is_some(), self.around.is_some()];
+        if given.iter().filter(|given| **given).count() > 1 {
+            return Err(AppError::BadRequest("Only one of before, after and around can be used".into()));
+        }
+
+        Ok((
+            self.before.as_deref().map(Cursor::decode).transpose()?,
+            self.after.as_deref().map(Cursor::decode).transpose()?,
+        ))
+    }
+
+    fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeleteScope {
+    /// Hide the message for the requesting user only
+    Me,
+    /// Replace the message with a tombstone for everyone
+    #[default]
+    Everyone,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteMessageQuery {
+    #[serde(default)]
+    pub scope: DeleteScope,
+}
+
+/// Bounds of a single query against a conversation. Both bounds are exclusive.
+struct PageBounds {
+    before: Option<Cursor>,
+    after: Option<Cursor>,
+    ascending: bool,
+    limit: i64,
+}
+
 pub async fn send_message(
     State(state): State<Arc<AppState>>,
     claims: Claims,
     Path(receiver_id): Path<Uuid>,
-    Json(req): Json<CreateMessageRequest>,
-) -> Result<Json<MessageResponse>, AppError> {
-    // Input validation
-    if req.content.trim().is_empty() {
-        return Err(AppError::BadRequest("Message content cannot be empty".into()));
-    }
-    if req.content.len() > MAX_MESSAGE_LENGTH {
-        return Err(AppError::BadRequest(format!(
-            "Message content exceeds maximum length of {} characters",
-            MAX_MESSAGE_LENGTH
-        )));
+    headers: HeaderMap,
+    Json(mut req): Json<CreateMessageRequest>,
+) -> Result<Response, AppError> {
+    req.client_message_id = idempotency::client_message_id(&headers, req.client_message_id)?;
+    if let Some(send_at) = req.send_at {
+        let scheduled = schedule_message(&state, claims.sub, receiver_id, false, req, send_at).await?;
+        return Ok((StatusCode::ACCEPTED, Json(scheduled)).into_response());
     }
 
-    let message_id = Uuid::new_v4();
+    let message = deliver_direct_message(&state, claims.sub, receiver_id, Uuid::new_v4(), req).await?;
+    Ok(Json(message).into_response())
+}
 
-    // Save message to database
-    let message = sqlx::query_as!(
-        Message,
-        r#"
-        INSERT INTO messages (id, sender_id, receiver_id, content, media_url, created_at)
-        VALUES ($1, $2, $3, $4, $5, NOW())
-        RETURNING *
-        "#,
-        message_id,
-        claims.sub,
-        receiver_id,
-        req.content,
-        req.media_url
-    )
-    .fetch_one(&state.pool)
-    .await?;
+/// Sends a direct message, starting the chat if it's the first. Scheduled
+/// messages are sent through here too, with their id reused as the message id.
+pub(crate) async fn deliver_direct_message(
+    state: &AppState,
+    sender_id: Uuid,
+    receiver_id: Uuid,
+    message_id: Uuid,
+    req: CreateMessageRequest,
+) -> Result<MessageResponse, AppError> {
+    let conversation = conversations::direct_conversation(state, sender_id, receiver_id).await?;
+    let sent = match store_message(state, sender_id, receiver_id, &conversation, message_id, req).await? {
+        Stored::New(sent) => sent,
+        Stored::Replayed(original) => return replayed_direct_message(state, original).await,
+    };
+    announce_message(state, &conversation, &sent).await?;
 
     // Get sender info
     let sender = sqlx::query!(
@@ -70,12 +130,13 @@ pub async fn send_message(
         FROM users
         WHERE id = $1
         "#,
-        claims.sub
+        sender_id
     )
     .fetch_one(&state.pool)
     .await?;
 
-    Ok(Json(MessageResponse {
+    let message = sent.message;
+    Ok(MessageResponse {
         id: message.id,
         sender_id: message.sender_id,
         receiver_id: message.receiver_id,
@@ -85,89 +146,339 @@ pub async fn send_message(
         updated_at: message.updated_at,
         is_edited: message.is_edited,
         is_deleted: message.is_deleted,
+        is_system: message.is_system,
+        is_imported: message.is_imported,
+        expires_at: message.expires_at,
         sender_name: sender.display_name,
         sender_avatar: sender.avatar_url,
-    }))
+        reply_to: sent.reply_to,
+        reply_count: 0,
+        mentions: sent.mentions,
+        formatting: message.formatting.0,
+        link_preview: None,
+        status_reply: sent.status_reply,
+    })
 }
 
 pub async fn send_group_message(
     State(state): State<Arc<AppState>>,
     claims: Claims,
     Path(group_id): Path<Uuid>,
-    Json(req): Json<CreateMessageRequest>,
-) -> Result<Json<GroupMessageResponse>, AppError> {
-    // Input validation
-    if req.content.trim().is_empty() {
-        return Err(AppError::BadRequest("Message content cannot be empty".into()));
-    }
-    if req.content.len() > MAX_MESSAGE_LENGTH {
-        return Err(AppError::BadRequest(format!(
-            "Message content exceeds maximum length of {} characters",
-            MAX_MESSAGE_LENGTH
-        )));
+    headers: HeaderMap,
+    Json(mut req): Json<CreateMessageRequest>,
+) -> Result<Response, AppError> {
+    req.client_message_id = idempotency::client_message_id(&headers, req.client_message_id)?;
+    if let Some(send_at) = req.send_at {
+        let scheduled = schedule_message(&state, claims.sub, group_id, true, req, send_at).await?;
+        return Ok((StatusCode::ACCEPTED, Json(scheduled)).into_response());
     }
 
-    // Verify user is a member of the group
-    let is_member = sqlx::query!(
+    let message = deliver_group_message(&state, claims.sub, group_id, Uuid::new_v4(), req).await?;
+    Ok(Json(message).into_response())
+}
+
+/// Sends a message to a group the sender is in.
+pub(crate) async fn deliver_group_message(
+    state: &AppState,
+    sender_id: Uuid,
+    group_id: Uuid,
+    message_id: Uuid,
+    req: CreateMessageRequest,
+) -> Result<GroupMessageResponse, AppError> {
+    let conversation = conversations::ensure_group_member(state, sender_id, group_id).await?;
+    let sent = match store_message(state, sender_id, group_id, &conversation, message_id, req).await? {
+        Stored::New(sent) => sent,
+        Stored::Replayed(original) => return replayed_group_message(state, original).await,
+    };
+    announce_message(state, &conversation, &sent).await?;
+
+    // Get sender info
+    let sender = sqlx::query!(
+        r#"
+        SELECT display_name, avatar_url
+        FROM users
+        WHERE id = $1
+        "#,
+        sender_id
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    // Get group info
+    let group = sqlx::query!(
         r#"
-        SELECT 1 FROM group_members
-        WHERE group_id = $1 AND user_id = $2
+        SELECT name, avatar_url
+        FROM groups
+        WHERE id = $1
         "#,
-        group_id,
-        claims.sub
+        group_id
     )
-    .fetch_optional(&state.pool)
-    .await?
-    .is_some();
+    .fetch_one(&state.pool)
+    .await?;
 
-    if !is_member {
-        return Err(AppError::Forbidden("Not a member of this group".into()));
+    let message = sent.message;
+    Ok(GroupMessageResponse {
+        id: message.id,
+        sender_id: message.sender_id,
+        group_id: message.receiver_id,
+        content: message.content,
+        media_url: message.media_url,
+        created_at: message.created_at,
+        updated_at: message.updated_at,
+        is_edited: message.is_edited,
+        is_deleted: message.is_deleted,
+        is_system: message.is_system,
+        is_imported: message.is_imported,
+        expires_at: message.expires_at,
+        sender_name: sender.display_name,
+        sender_avatar: sender.avatar_url,
+        group_name: group.name,
+        group_avatar: group.avatar_url,
+        reply_to: sent.reply_to,
+        reply_count: 0,
+        mentions: sent.mentions,
+        formatting: message.formatting.0,
+        link_preview: None,
+    })
+}
+
+/// A message just stored, with what its response and delivery need.
+pub(crate) struct SentMessage {
+    pub message: Message,
+    pub reply_to: Option<QuotedMessage>,
+    pub mentions: Vec<MentionEntity>,
+    /// Users newly mentioned by the message
+    pub mentioned: Vec<Uuid>,
+    pub status_reply: Option<StatusReplyPreview>,
+}
+
+pub(crate) enum Stored {
+    New(SentMessage),
+    /// A retry of a send that went through, with the message it stored
+    Replayed(Message),
+}
+
+/// Validates and stores a message in a conversation the sender is in.
+/// Direct and group messages, whether sent over REST, the WebSocket or on a
+/// schedule, all go through here; `announce_message` then delivers them.
+/// `receiver_id` is how the sender addressed the conversation.
+pub(crate) async fn store_message(
+    state: &AppState,
+    sender_id: Uuid,
+    receiver_id: Uuid,
+    conversation: &Conversation,
+    message_id: Uuid,
+    req: CreateMessageRequest,
+) -> Result<Stored, AppError> {
+    // A retry of a send that went through gets the original message back
+    if let Some(key) = &req.client_message_id {
+        if let Some(original) = idempotency::find_sent_message(state, sender_id, conversation.id, key).await? {
+            return Ok(Stored::Replayed(original));
+        }
     }
 
-    let message_id = Uuid::new_v4();
+    validate_content(&req.content)?;
+
+    // Replies to a status must target one of the receiver's statuses the sender can see
+    let status_reply = match req.status_id {
+        Some(_) if conversation.is_group() => {
+            return Err(AppError::BadRequest("Status replies can only be sent as direct messages".into()));
+        }
+        Some(status_id) => Some(status_reply_preview(state, status_id, sender_id, receiver_id).await?),
+        None => None,
+    };
+    let reply_to = match req.reply_to_id {
+        Some(reply_to_id) => Some(reply_target(state, reply_to_id, conversation.id).await?),
+        None => None,
+    };
+    validate_formatting(&req.content, &req.formatting)?;
+    let mentions = mentions::resolve_mentions(state, sender_id, conversation, &req.content, req.mentions).await?;
+
+    let mut tx = state.pool.begin().await?;
 
     // Save message to database
     let message = sqlx::query_as!(
         Message,
         r#"
-        INSERT INTO messages (id, sender_id, receiver_id, content, media_url, created_at)
-        VALUES ($1, $2, $3, $4, $5, NOW())
+        INSERT INTO messages (id, sender_id, receiver_id, conversation_id, content, media_url, created_at,
+                              reply_to_id, formatting)
+        VALUES ($1, $2, $3, $4, $5, $6, NOW(), $7, $8)
         RETURNING *
         "#,
         message_id,
-        claims.sub,
-        group_id,
+        sender_id,
+        receiver_id,
+        conversation.id,
         req.content,
-        req.media_url
+        req.media_url,
+        req.reply_to_id,
+        SqlJson(&req.formatting) as _
     )
-    .fetch_one(&state.pool)
+    .fetch_one(&mut *tx)
     .await?;
 
-    // Get sender info
-    let sender = sqlx::query!(
+    if let Some(key) = &req.client_message_id {
+        if !idempotency::claim(&mut tx, state, sender_id, key, message_id).await? {
+            // A concurrent retry sent it first
+            tx.rollback().await?;
+            let original = idempotency::find_sent_message(state, sender_id, conversation.id, key)
+                .await?
+                .ok_or_else(|| AppError::InternalServerError("Original message not found".into()))?;
+            return Ok(Stored::Replayed(original));
+        }
+    }
+
+    // Keep a preview of the status, which outlives the status itself
+    if let Some(preview) = &status_reply {
+        sqlx::query!(
+            r#"
+            INSERT INTO status_replies (message_id, status_id, status_author_id, content,
+                                        media_url, media_type, background_color)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            message_id,
+            preview.status_id,
+            preview.author_id,
+            preview.content,
+            preview.media_url,
+            preview.media_type,
+            preview.background_color
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let mentioned = mentions::store_mentions(&mut tx, message_id, &mentions).await?;
+
+    tx.commit().await?;
+
+    Ok(Stored::New(SentMessage {
+        message,
+        reply_to,
+        mentions: mentions.entities,
+        mentioned,
+        status_reply,
+    }))
+}
+
+/// Delivers a newly stored message to everyone in its conversation, tells
+/// whoever it mentions and queues its link preview.
+pub(crate) async fn announce_message(
+    state: &AppState,
+    conversation: &Conversation,
+    sent: &SentMessage,
+) -> Result<(), AppError> {
+    let message = &sent.message;
+    let event = match conversation.kind {
+        ConversationKind::Direct => WebSocketMessage::DirectMessage(message.clone()),
+        ConversationKind::Group => WebSocketMessage::GroupMessage {
+            group_id: conversation.id,
+            message: message.clone(),
+        },
+    };
+
+    let members = conversations::conversation_members(state, conversation.id).await?;
+    fanout::send_to_users(state, &members, &event).await?;
+    mentions::notify_mentioned(state, &sent.mentioned, message).await?;
+    link_preview::enqueue(state, message);
+
+    Ok(())
+}
+
+/// What a send response shows beyond the stored message itself.
+struct SentMessageDetails {
+    sender_name: String,
+    sender_avatar: Option<String>,
+    mentions: Vec<MentionEntity>,
+    link_preview: Option<LinkPreview>,
+    reply_to: Option<QuotedMessage>,
+    reply_count: i64,
+}
+
+async fn load_sent_message_details(state: &AppState, message: &Message) -> Result<SentMessageDetails, AppError> {
+    let details = sqlx::query!(
         r#"
-        SELECT display_name, avatar_url
-        FROM users
-        WHERE id = $1
+        SELECT u.display_name as sender_name, u.avatar_url as sender_avatar,
+            message_mention_entities($1) as "mentions!: SqlJson<Vec<MentionEntity>>",
+            lp.url as "preview_url?",
+            lp.title as "preview_title?",
+            lp.description as "preview_description?",
+            lp.image_url as "preview_image_url?",
+            lp.site_name as "preview_site_name?"
+        FROM users u
+        LEFT JOIN message_link_previews mlp ON mlp.message_id = $1
+        LEFT JOIN link_previews lp ON lp.url = mlp.url AND lp.is_available
+        WHERE u.id = $2
         "#,
-        claims.sub
+        message.id,
+        message.sender_id
     )
     .fetch_one(&state.pool)
     .await?;
 
-    // Get group info
+    Ok(SentMessageDetails {
+        sender_name: details.sender_name,
+        sender_avatar: details.sender_avatar,
+        mentions: details.mentions.0,
+        link_preview: LinkPreview::from_columns(
+            details.preview_url,
+            details.preview_title,
+            details.preview_description,
+            details.preview_image_url,
+            details.preview_site_name,
+        ),
+        reply_to: match message.reply_to_id {
+            Some(reply_to_id) => load_quoted_message(state, reply_to_id).await?,
+            None => None,
+        },
+        reply_count: count_replies(state, message.id).await?,
+    })
+}
+
+/// The response to a retried direct message send, describing the message the
+/// first attempt stored. Nothing is delivered again.
+async fn replayed_direct_message(state: &AppState, message: Message) -> Result<MessageResponse, AppError> {
+    let details = load_sent_message_details(state, &message).await?;
+
+    Ok(MessageResponse {
+        id: message.id,
+        sender_id: message.sender_id,
+        receiver_id: message.receiver_id,
+        content: message.content,
+        media_url: message.media_url,
+        created_at: message.created_at,
+        updated_at: message.updated_at,
+        is_edited: message.is_edited,
+        is_deleted: message.is_deleted,
+        is_system: message.is_system,
+        is_imported: message.is_imported,
+        expires_at: message.expires_at,
+        sender_name: details.sender_name,
+        sender_avatar: details.sender_avatar,
+        reply_to: details.reply_to,
+        reply_count: details.reply_count,
+        mentions: details.mentions,
+        formatting: message.formatting.0,
+        link_preview: details.link_preview,
+        status_reply: load_status_reply(state, message.id).await?,
+    })
+}
+
+/// The response to a retried group message send, see `replayed_direct_message`.
+async fn replayed_group_message(state: &AppState, message: Message) -> Result<GroupMessageResponse, AppError> {
+    let details = load_sent_message_details(state, &message).await?;
     let group = sqlx::query!(
         r#"
         SELECT name, avatar_url
         FROM groups
         WHERE id = $1
         "#,
-        group_id
+        message.receiver_id
     )
     .fetch_one(&state.pool)
     .await?;
 
-    Ok(Json(GroupMessageResponse {
+    Ok(GroupMessageResponse {
         id: message.id,
         sender_id: message.sender_id,
         group_id: message.receiver_id,
@@ -177,119 +488,253 @@ pub async fn send_group_message(
         updated_at: message.updated_at,
         is_edited: message.is_edited,
         is_deleted: message.is_deleted,
-        sender_name: sender.display_name,
-        sender_avatar: sender.avatar_url,
+        is_system: message.is_system,
+        is_imported: message.is_imported,
+        expires_at: message.expires_at,
+        sender_name: details.sender_name,
+        sender_avatar: details.sender_avatar,
         group_name: group.name,
         group_avatar: group.avatar_url,
-    }))
+        reply_to: details.reply_to,
+        reply_count: details.reply_count,
+        mentions: details.mentions,
+        formatting: message.formatting.0,
+        link_preview: details.link_preview,
+    })
 }
 
-pub async fn get_messages(
+/// My scheduled messages that haven't been sent yet, soonest first.
+pub async fn get_scheduled_messages(
     State(state): State<Arc<AppState>>,
     claims: Claims,
-    Path(receiver_id): Path<Uuid>,
-    Query(query): Query<MessageQuery>,
-) -> Result<Json<Vec<MessageResponse>>, AppError> {
-    let messages = sqlx::query!(
+) -> Result<Json<Vec<ScheduledMessage>>, AppError> {
+    let scheduled = sqlx::query_as!(
+        ScheduledMessage,
         r#"
-        SELECT 
-            m.*,
-            u.display_name as sender_name,
-            u.avatar_url as sender_avatar
-        FROM messages m
-        JOIN users u ON u.id = m.sender_id
-        WHERE 
-            ((m.sender_id = $1 AND m.receiver_id = $2) OR
-            (m.sender_id = $2 AND m.receiver_id = $1))
-            AND ($3::timestamptz IS NULL OR m.created_at < $3)
-        ORDER BY m.created_at DESC
-        LIMIT $4
+        SELECT id, sender_id, receiver_id, is_group, content, media_url, reply_to_id, send_at,
+               status as "status: _", last_error, created_at, updated_at,
+               formatting as "formatting: _"
+        FROM scheduled_messages
+        WHERE sender_id = $1
+        ORDER BY send_at, id
         "#,
-        claims.sub,
-        receiver_id,
-        query.before,
-        query.limit.unwrap_or(50)
+        claims.sub
     )
     .fetch_all(&state.pool)
     .await?;
 
-    Ok(Json(messages.into_iter().map(|m| MessageResponse {
-        id: m.id,
-        sender_id: m.sender_id,
-        receiver_id: m.receiver_id,
-        content: m.content,
-        media_url: m.media_url,
-        created_at: m.created_at,
-        updated_at: m.updated_at,
-        is_edited: m.is_edited,
-        is_deleted: m.is_deleted,
-        sender_name: m.sender_name,
-        sender_avatar: m.sender_avatar,
-    }).collect()))
+    Ok(Json(scheduled))
 }
 
-pub async fn get_group_messages(
+pub async fn update_scheduled_message(
     State(state): State<Arc<AppState>>,
     claims: Claims,
-    Path(group_id): Path<Uuid>,
-    Query(query): Query<MessageQuery>,
-) -> Result<Json<Vec<GroupMessageResponse>>, AppError> {
-    // Verify user is a member of the group
-    let is_member = sqlx::query!(
+    Path(scheduled_id): Path<Uuid>,
+    Json(req): Json<UpdateScheduledMessageRequest>,
+) -> Result<Json<ScheduledMessage>, AppError> {
+    if let Some(content) = &req.content {
+        validate_content(content)?;
+        validate_formatting(content, req.formatting.as_deref().unwrap_or_default())?;
+    } else if req.formatting.is_some() {
+        return Err(AppError::BadRequest("Formatting can only be changed along with the content".into()));
+    }
+    if let Some(send_at) = req.send_at {
+        validate_send_at(send_at)?;
+    }
+
+    // Messages being sent right now are locked, so this waits and then finds nothing
+    let scheduled = sqlx::query_as!(
+        ScheduledMessage,
         r#"
-        SELECT 1 FROM group_members
-        WHERE group_id = $1 AND user_id = $2
+        UPDATE scheduled_messages
+        SET content = COALESCE($3, content),
+            formatting = CASE WHEN $3::text IS NULL THEN formatting ELSE $5 END,
+            send_at = COALESCE($4, send_at),
+            status = 'pending',
+            last_error = NULL
+        WHERE id = $1 AND sender_id = $2
+        RETURNING id, sender_id, receiver_id, is_group, content, media_url, reply_to_id, send_at,
+                  status as "status: _", last_error, created_at, updated_at,
+                  formatting as "formatting: _"
         "#,
-        group_id,
-        claims.sub
+        scheduled_id,
+        claims.sub,
+        req.content,
+        req.send_at,
+        SqlJson(req.formatting.unwrap_or_default()) as _
     )
     .fetch_optional(&state.pool)
     .await?
-    .is_some();
+    .ok_or_else(|| AppError::NotFound("Scheduled message not found".into()))?;
+
+    Ok(Json(scheduled))
+}
+
+pub async fn cancel_scheduled_message(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(scheduled_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let deleted = sqlx::query!(
+        r#"
+        DELETE FROM scheduled_messages
+        WHERE id = $1 AND sender_id = $2
+        "#,
+        scheduled_id,
+        claims.sub
+    )
+    .execute(&state.pool)
+    .await?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(AppError::NotFound("Scheduled message not found".into()));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_messages(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(receiver_id): Path<Uuid>,
+    Query(query): Query<MessageQuery>,
+) -> Result<Json<MessagePage<MessageResponse>>, AppError> {
+    let conversation = conversations::find_conversation(&state, claims.sub, receiver_id)
+        .await?
+        .filter(|conversation| !conversation.is_group());
+
+    // Someone I've never messaged has no history with me yet
+    let Some(conversation) = conversation else {
+        query.cursors()?;
+        if query.around.is_some() {
+            return Err(AppError::NotFound("Message not found".into()));
+        }
+        return Ok(Json(MessagePage {
+            messages: Vec::new(),
+            before_cursor: None,
+            after_cursor: None,
+            has_more_before: false,
+            has_more_after: false,
+        }));
+    };
+
+    let page = load_page(&state, claims.sub, conversation.id, &query, MessageRow::into_direct).await?;
+    Ok(Json(page))
+}
 
-    if !is_member {
-        return Err(AppError::Forbidden("Not a member of this group".into()));
+pub async fn get_group_messages(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(group_id): Path<Uuid>,
+    Query(query): Query<MessageQuery>,
+) -> Result<Json<MessagePage<GroupMessageResponse>>, AppError> {
+    let conversation = conversations::ensure_group_member(&state, claims.sub, group_id).await?;
+
+    let page = load_page(&state, claims.sub, conversation.id, &query, MessageRow::into_group).await?;
+    Ok(Json(page))
+}
+
+/// A message together with every reply below it, directly or nested.
+pub async fn get_message_thread(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(message_id): Path<Uuid>,
+) -> Result<Json<MessageThreadResponse>, AppError> {
+    if !can_view_message(&state, message_id, claims.sub).await? {
+        return Err(AppError::NotFound("Message not found".into()));
     }
 
     let messages = sqlx::query!(
         r#"
+        WITH RECURSIVE thread AS (
+            SELECT id FROM messages WHERE id = $1
+            UNION
+            SELECT m.id FROM messages m JOIN thread t ON m.reply_to_id = t.id
+        )
         SELECT 
             m.*,
             u.display_name as sender_name,
             u.avatar_url as sender_avatar,
-            g.name as group_name,
-            g.avatar_url as group_avatar
-        FROM messages m
+            q.id as "quoted_id?",
+            q.sender_id as "quoted_sender_id?",
+            qu.display_name as "quoted_sender_name?",
+            q.content as "quoted_content?",
+            q.media_url as "quoted_media_url?",
+            q.is_deleted as "quoted_is_deleted?",
+            (SELECT COUNT(*) FROM messages r WHERE r.reply_to_id = m.id AND NOT r.is_deleted) as "reply_count!",
+            message_mention_entities(m.id) as "mentions!: SqlJson<Vec<MentionEntity>>",
+            m.formatting as "formatting_entities: SqlJson<Vec<FormattingEntity>>",
+            lp.url as "preview_url?",
+            lp.title as "preview_title?",
+            lp.description as "preview_description?",
+            lp.image_url as "preview_image_url?",
+            lp.site_name as "preview_site_name?"
+        FROM thread t
+        JOIN messages m ON m.id = t.id
         JOIN users u ON u.id = m.sender_id
-        JOIN groups g ON g.id = m.receiver_id
-        WHERE 
-            m.receiver_id = $1
-            AND ($2::timestamptz IS NULL OR m.created_at < $2)
-        ORDER BY m.created_at DESC
-        LIMIT $3
+        LEFT JOIN messages q ON q.id = m.reply_to_id
+        LEFT JOIN users qu ON qu.id = q.sender_id
+        LEFT JOIN message_link_previews mlp ON mlp.message_id = m.id
+        LEFT JOIN link_previews lp ON lp.url = mlp.url AND lp.is_available
+        WHERE NOT EXISTS (SELECT 1 FROM hidden_messages h WHERE h.message_id = m.id AND h.user_id = $3)
+            AND (m.expires_at IS NULL OR m.expires_at > NOW())
+        ORDER BY m.created_at, m.id
+        LIMIT $2
         "#,
-        group_id,
-        query.before,
-        query.limit.unwrap_or(50)
+        message_id,
+        MAX_THREAD_SIZE,
+        claims.sub
     )
     .fetch_all(&state.pool)
     .await?;
 
-    Ok(Json(messages.into_iter().map(|m| GroupMessageResponse {
-        id: m.id,
-        sender_id: m.sender_id,
-        group_id: m.receiver_id,
-        content: m.content,
-        media_url: m.media_url,
-        created_at: m.created_at,
-        updated_at: m.updated_at,
-        is_edited: m.is_edited,
-        is_deleted: m.is_deleted,
-        sender_name: m.sender_name,
-        sender_avatar: m.sender_avatar,
-        group_name: m.group_name,
-        group_avatar: m.group_avatar,
-    }).collect()))
+    let mut root = None;
+    let mut replies = Vec::with_capacity(messages.len());
+    for m in messages {
+        let message = MessageResponse {
+            id: m.id,
+            sender_id: m.sender_id,
+            receiver_id: m.receiver_id,
+            content: m.content,
+            media_url: m.media_url,
+            created_at: m.created_at,
+            updated_at: m.updated_at,
+            is_edited: m.is_edited,
+            is_deleted: m.is_deleted,
+            is_system: m.is_system,
+            is_imported: m.is_imported,
+            expires_at: m.expires_at,
+            sender_name: m.sender_name,
+            sender_avatar: m.sender_avatar,
+            reply_to: QuotedMessage::from_columns(
+                m.quoted_id,
+                m.quoted_sender_id,
+                m.quoted_sender_name,
+                m.quoted_content,
+                m.quoted_media_url,
+                m.quoted_is_deleted,
+            ),
+            reply_count: m.reply_count,
+            mentions: m.mentions.0,
+            formatting: m.formatting_entities.0,
+            link_preview: LinkPreview::from_columns(
+                m.preview_url,
+                m.preview_title,
+                m.preview_description,
+                m.preview_image_url,
+                m.preview_site_name,
+            ),
+            status_reply: None,
+        };
+        if message.id == message_id {
+            root = Some(message);
+        } else {
+            replies.push(message);
+        }
+    }
+
+    let root = root.ok_or_else(|| AppError::NotFound("Message not found".into()))?;
+    Ok(Json(MessageThreadResponse { root, replies }))
 }
 
 pub async fn update_message(
@@ -308,17 +753,21 @@ pub async fn update_message(
             MAX_MESSAGE_LENGTH
         )));
     }
+    validate_formatting(&req.content, &req.formatting)?;
 
-    // Get the message
+    let mut tx = state.pool.begin().await?;
+
+    // Get the message, locking it so concurrent edits are recorded in order
     let message = sqlx::query_as!(
         Message,
         r#"
         SELECT * FROM messages
         WHERE id = $1
+        FOR UPDATE
         "#,
         message_id
     )
-    .fetch_optional(&state.pool)
+    .fetch_optional(&mut *tx)
     .await?
     .ok_or_else(|| AppError::NotFound("Message not found".into()))?;
 
@@ -326,22 +775,74 @@ pub async fn update_message(
     if message.sender_id != claims.sub {
         return Err(AppError::Forbidden("Cannot edit another user's message".into()));
     }
+    if message.is_deleted {
+        return Err(AppError::BadRequest("Cannot edit a deleted message".into()));
+    }
+    if message.is_system {
+        return Err(AppError::BadRequest("Cannot edit a system message".into()));
+    }
+    if Utc::now() - message.created_at > state.messaging.edit_window {
+        return Err(AppError::Forbidden("Message can no longer be edited".into()));
+    }
+
+    let conversation = sqlx::query_as!(
+        Conversation,
+        r#"SELECT id, kind as "kind: ConversationKind" FROM conversations WHERE id = $1"#,
+        message.conversation_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    let mentions = mentions::resolve_mentions(&state, claims.sub, &conversation, &req.content, req.mentions).await?;
+
+    // Keep the version being replaced
+    sqlx::query!(
+        r#"
+        INSERT INTO message_edits (id, message_id, content, edited_at)
+        VALUES ($1, $2, $3, NOW())
+        "#,
+        Uuid::new_v4(),
+        message_id,
+        message.content
+    )
+    .execute(&mut *tx)
+    .await?;
 
     // Update the message
     let updated_message = sqlx::query_as!(
         Message,
         r#"
         UPDATE messages
-        SET content = $1, updated_at = NOW(), is_edited = true
+        SET content = $1, formatting = $3, updated_at = NOW(), is_edited = true
         WHERE id = $2
         RETURNING *
         "#,
         req.content,
-        message_id
+        message_id,
+        SqlJson(&req.formatting) as _
     )
-    .fetch_one(&state.pool)
+    .fetch_one(&mut *tx)
     .await?;
 
+    // Offsets refer to the old content, so the mentions are replaced too
+    let mentioned = mentions::store_mentions(&mut tx, message_id, &mentions).await?;
+    // The preview is regenerated for whatever link the new content has
+    sqlx::query!("DELETE FROM message_link_previews WHERE message_id = $1", message_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    let members = conversations::conversation_members(&state, conversation.id).await?;
+    fanout::send_to_users(&state, &members, &WebSocketMessage::MessageEdited {
+        message_id,
+        receiver_id: updated_message.receiver_id,
+        content: updated_message.content.clone(),
+        edited_at: updated_message.updated_at.unwrap_or_else(Utc::now),
+    })
+    .await?;
+    mentions::notify_mentioned(&state, &mentioned, &updated_message).await?;
+    link_preview::enqueue(&state, &updated_message);
+
     // Get sender info
     let sender = sqlx::query!(
         r#"
@@ -364,32 +865,171 @@ pub async fn update_message(
         updated_at: updated_message.updated_at,
         is_edited: updated_message.is_edited,
         is_deleted: updated_message.is_deleted,
+        is_system: updated_message.is_system,
+        is_imported: updated_message.is_imported,
+        expires_at: updated_message.expires_at,
         sender_name: sender.display_name,
         sender_avatar: sender.avatar_url,
+        reply_to: match updated_message.reply_to_id {
+            Some(reply_to_id) => load_quoted_message(&state, reply_to_id).await?,
+            None => None,
+        },
+        reply_count: count_replies(&state, message_id).await?,
+        mentions: mentions.entities,
+        formatting: updated_message.formatting.0,
+        link_preview: None,
+        status_reply: load_status_reply(&state, message_id).await?,
     }))
 }
 
+/// Every version of a message, oldest first.
+pub async fn get_message_history(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(message_id): Path<Uuid>,
+) -> Result<Json<Vec<MessageVersion>>, AppError> {
+    if !can_view_message(&state, message_id, claims.sub).await? {
+        return Err(AppError::NotFound("Message not found".into()));
+    }
+
+    let message = sqlx::query!(
+        r#"
+        SELECT content, created_at, is_deleted FROM messages
+        WHERE id = $1
+        "#,
+        message_id
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    // Deleted messages keep no history
+    if message.is_deleted {
+        return Err(AppError::NotFound("Message not found".into()));
+    }
+
+    let edits = sqlx::query!(
+        r#"
+        SELECT content, edited_at FROM message_edits
+        WHERE message_id = $1
+        ORDER BY edited_at, id
+        "#,
+        message_id
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    // Each edit holds the content it replaced, which was written when the previous edit was made
+    let mut versions = Vec::with_capacity(edits.len() + 1);
+    let mut written_at = message.created_at;
+    for edit in edits {
+        versions.push(MessageVersion {
+            content: edit.content,
+            written_at,
+            is_current: false,
+        });
+        written_at = edit.edited_at;
+    }
+    versions.push(MessageVersion {
+        content: message.content,
+        written_at,
+        is_current: true,
+    });
+
+    Ok(Json(versions))
+}
+
 pub async fn delete_message(
     State(state): State<Arc<AppState>>,
     claims: Claims,
     Path(message_id): Path<Uuid>,
+    Query(query): Query<DeleteMessageQuery>,
 ) -> Result<StatusCode, AppError> {
-    // Get the message
+    if !can_view_message(&state, message_id, claims.sub).await? {
+        return Err(AppError::NotFound("Message not found".into()));
+    }
+
+    match query.scope {
+        DeleteScope::Me => delete_for_me(&state, message_id, claims.sub).await?,
+        DeleteScope::Everyone => delete_for_everyone(&state, message_id, claims.sub).await?,
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Hides a message from the user's own view of the conversation.
+async fn delete_for_me(state: &AppState, message_id: Uuid, user_id: Uuid) -> Result<(), AppError> {
+    let receiver_id = sqlx::query_scalar!(
+        r#"
+        SELECT receiver_id FROM messages
+        WHERE id = $1
+        "#,
+        message_id
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO hidden_messages (user_id, message_id, hidden_at)
+        VALUES ($1, $2, NOW())
+        ON CONFLICT (user_id, message_id) DO NOTHING
+        "#,
+        user_id,
+        message_id
+    )
+    .execute(&state.pool)
+    .await?;
+
+    // Only the user's other devices need to know
+    fanout::send_to_users(state, &[user_id], &WebSocketMessage::MessageDeleted {
+        message_id,
+        receiver_id,
+        for_everyone: false,
+    })
+    .await
+}
+
+/// Replaces a message with a tombstone for everyone in the conversation and
+/// removes everything attached to it.
+async fn delete_for_everyone(state: &AppState, message_id: Uuid, user_id: Uuid) -> Result<(), AppError> {
+    let mut tx = state.pool.begin().await?;
+
     let message = sqlx::query_as!(
         Message,
         r#"
         SELECT * FROM messages
         WHERE id = $1
+        FOR UPDATE
         "#,
         message_id
     )
-    .fetch_optional(&state.pool)
-    .await?
-    .ok_or_else(|| AppError::NotFound("Message not found".into()))?;
+    .fetch_one(&mut *tx)
+    .await?;
 
-    // Check if user is the sender
-    if message.sender_id != claims.sub {
-        return Err(AppError::Forbidden("Cannot delete another user's message".into()));
+    if message.is_deleted {
+        return Ok(());
+    }
+
+    // Senders can delete their own messages, group admins anyone's
+    if message.sender_id != user_id {
+        let is_admin = sqlx::query!(
+            r#"
+            SELECT 1 as "exists!" FROM group_members
+            WHERE group_id = $1 AND user_id = $2 AND role IN ('owner', 'admin')
+            "#,
+            message.conversation_id,
+            user_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .is_some();
+
+        if !is_admin {
+            return Err(AppError::Forbidden("Cannot delete another user's message".into()));
+        }
+    }
+    if Utc::now() - message.created_at > state.messaging.delete_window {
+        return Err(AppError::Forbidden("Message can no longer be deleted for everyone".into()));
     }
 
     // Soft delete the message
@@ -401,8 +1041,619 @@ pub async fn delete_message(
         "#,
         message_id
     )
-    .execute(&state.pool)
+    .execute(&mut *tx)
     .await?;
 
-    Ok(StatusCode::NO_CONTENT)
-} 
\ No newline at end of file
+    // Nothing that reveals the original content survives the tombstone
+    sqlx::query!("DELETE FROM message_reactions WHERE message_id = $1", message_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM message_deliveries WHERE message_id = $1", message_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM message_edits WHERE message_id = $1", message_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM status_replies WHERE message_id = $1", message_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM pinned_messages WHERE message_id = $1", message_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM starred_messages WHERE message_id = $1", message_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM message_mentions WHERE message_id = $1", message_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM mention_recipients WHERE message_id = $1", message_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM message_link_previews WHERE message_id = $1", message_id)
+        .execute(&mut *tx)
+        .await?;
+
+    if let Some(media_url) = message.media_url.clone() {
+        media_cleanup::release_media(&mut tx, &[(media_url, message.sender_id)]).await?;
+    }
+
+    tx.commit().await?;
+
+    let members = conversations::conversation_members(state, message.conversation_id).await?;
+    fanout::send_to_users(state, &members, &WebSocketMessage::MessageDeleted {
+        message_id,
+        receiver_id: message.receiver_id,
+        for_everyone: true,
+    })
+    .await
+}
+
+async fn load_status_reply(
+    state: &AppState,
+    message_id: Uuid,
+) -> Result<Option<StatusReplyPreview>, AppError> {
+    let preview = sqlx::query_as!(
+        StatusReplyPreview,
+        r#"
+        SELECT status_id, status_author_id as author_id, content, media_url, media_type, background_color
+        FROM status_replies
+        WHERE message_id = $1
+        "#,
+        message_id
+    )
+    .fetch_optional(&state.pool)
+    .await?;
+
+    Ok(preview)
+}
+
+/// Loads a page of messages, newest first. Each query fetches one extra row to
+/// find out whether there is more in that direction, and a one-row probe
+/// checks the other side of a `before` or `after` cursor.
+async fn paginate<T, F, Fut>(
+    limit: i64,
+    before: Option<Cursor>,
+    after: Option<Cursor>,
+    anchor: Option<Cursor>,
+    fetch: F,
+) -> Result<MessagePage<T>, AppError>
+where
+    T: Paginated,
+    F: Fn(PageBounds) -> Fut,
+    Fut: Future<Output = Result<Vec<T>, AppError>>,
+{
+    let (messages, has_more_before, has_more_after) = if let Some(anchor) = anchor {
+        // The anchor and older messages fill one half of the page, newer ones the other
+        let newer_limit = limit / 2;
+        let older_limit = limit - newer_limit;
+        let mut older = fetch(PageBounds {
+            before: Some(anchor.inclusive()),
+            after: None,
+            ascending: false,
+            limit: older_limit + 1,
+        })
+        .await?;
+        let mut newer = fetch(PageBounds {
+            before: None,
+            after: Some(anchor),
+            ascending: true,
+            limit: newer_limit + 1,
+        })
+        .await?;
+
+        let has_more_before = older.len() as i64 > older_limit;
+        let has_more_after = newer.len() as i64 > newer_limit;
+        older.truncate(older_limit as usize);
+        newer.truncate(newer_limit as usize);
+        newer.reverse();
+        newer.extend(older);
+        (newer, has_more_before, has_more_after)
+    } else if let Some(after) = after {
+        let mut messages = fetch(PageBounds {
+            before: None,
+            after: Some(after),
+            ascending: true,
+            limit: limit + 1,
+        })
+        .await?;
+
+        let has_more_after = messages.len() as i64 > limit;
+        messages.truncate(limit as usize);
+        messages.reverse();
+        // The cursor's own message may since have been hidden or expired
+        let older = fetch(PageBounds {
+            before: Some(after.inclusive()),
+            after: None,
+            ascending: false,
+            limit: 1,
+        })
+        .await?;
+        (messages, !older.is_empty(), has_more_after)
+    } else {
+        let mut messages = fetch(PageBounds {
+            before,
+            after: None,
+            ascending: false,
+            limit: limit + 1,
+        })
+        .await?;
+
+        let has_more_before = messages.len() as i64 > limit;
+        messages.truncate(limit as usize);
+        let has_more_after = match before {
+            Some(before) => !fetch(PageBounds {
+                before: None,
+                after: Some(before.inclusive_after()),
+                ascending: true,
+                limit: 1,
+            })
+            .await?
+            .is_empty(),
+            None => false,
+        };
+        (messages, has_more_before, has_more_after)
+    };
+
+    Ok(MessagePage {
+        before_cursor: messages.last().map(|m| m.cursor().encode()),
+        after_cursor: messages.first().map(|m| m.cursor().encode()),
+        messages,
+        has_more_before,
+        has_more_after,
+    })
+}
+
+/// A page of a conversation's history as `user_id` sees it.
+async fn load_page<T: Paginated>(
+    state: &AppState,
+    user_id: Uuid,
+    conversation_id: Uuid,
+    query: &MessageQuery,
+    into_response: fn(MessageRow) -> T,
+) -> Result<MessagePage<T>, AppError> {
+    let (before, after) = query.cursors()?;
+
+    // The anchor must be part of this conversation
+    let anchor = match query.around {
+        Some(message_id) => {
+            let anchor = sqlx::query!(
+                r#"
+                SELECT id, created_at FROM messages
+                WHERE id = $1 AND conversation_id = $2
+                "#,
+                message_id,
+                conversation_id
+            )
+            .fetch_optional(&state.pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Message not found".into()))?;
+            Some(Cursor::new(anchor.created_at, anchor.id))
+        }
+        None => None,
+    };
+
+    paginate(query.limit(), before, after, anchor, move |bounds| async move {
+        let rows = fetch_messages(state, user_id, conversation_id, bounds).await?;
+        Ok(rows.into_iter().map(into_response).collect())
+    })
+    .await
+}
+
+/// A message as history shows it, in a direct chat or a group.
+struct MessageRow {
+    id: Uuid,
+    sender_id: Uuid,
+    receiver_id: Uuid,
+    conversation_id: Uuid,
+    content: String,
+    media_url: Option<String>,
+    created_at: DateTime<Utc>,
+    updated_at: Option<DateTime<Utc>>,
+    is_edited: bool,
+    is_deleted: bool,
+    is_system: bool,
+    is_imported: bool,
+    expires_at: Option<DateTime<Utc>>,
+    formatting: SqlJson<Vec<FormattingEntity>>,
+    sender_name: String,
+    sender_avatar: Option<String>,
+    group_name: Option<String>,
+    group_avatar: Option<String>,
+    quoted_id: Option<Uuid>,
+    quoted_sender_id: Option<Uuid>,
+    quoted_sender_name: Option<String>,
+    quoted_content: Option<String>,
+    quoted_media_url: Option<String>,
+    quoted_is_deleted: Option<bool>,
+    reply_count: i64,
+    mentions: SqlJson<Vec<MentionEntity>>,
+    preview_url: Option<String>,
+    preview_title: Option<String>,
+    preview_description: Option<String>,
+    preview_image_url: Option<String>,
+    preview_site_name: Option<String>,
+    reply_status_id: Option<Uuid>,
+    reply_status_author_id: Option<Uuid>,
+    reply_status_content: Option<String>,
+    reply_status_media_url: Option<String>,
+    reply_status_media_type: Option<String>,
+    reply_status_background_color: Option<String>,
+}
+
+impl MessageRow {
+    fn into_direct(self) -> MessageResponse {
+        MessageResponse {
+            id: self.id,
+            sender_id: self.sender_id,
+            receiver_id: self.receiver_id,
+            content: self.content,
+            media_url: self.media_url,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            is_edited: self.is_edited,
+            is_deleted: self.is_deleted,
+            is_system: self.is_system,
+            is_imported: self.is_imported,
+            expires_at: self.expires_at,
+            sender_name: self.sender_name,
+            sender_avatar: self.sender_avatar,
+            reply_to: QuotedMessage::from_columns(
+                self.quoted_id,
+                self.quoted_sender_id,
+                self.quoted_sender_name,
+                self.quoted_content,
+                self.quoted_media_url,
+                self.quoted_is_deleted,
+            ),
+            reply_count: self.reply_count,
+            mentions: self.mentions.0,
+            formatting: self.formatting.0,
+            link_preview: LinkPreview::from_columns(
+                self.preview_url,
+                self.preview_title,
+                self.preview_description,
+                self.preview_image_url,
+                self.preview_site_name,
+            ),
+            status_reply: self.reply_status_author_id.map(|author_id| StatusReplyPreview {
+                status_id: self.reply_status_id,
+                author_id,
+                content: self.reply_status_content,
+                media_url: self.reply_status_media_url,
+                media_type: self.reply_status_media_type,
+                background_color: self.reply_status_background_color,
+            }),
+        }
+    }
+
+    fn into_group(self) -> GroupMessageResponse {
+        GroupMessageResponse {
+            id: self.id,
+            sender_id: self.sender_id,
+            group_id: self.conversation_id,
+            content: self.content,
+            media_url: self.media_url,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            is_edited: self.is_edited,
+            is_deleted: self.is_deleted,
+            is_system: self.is_system,
+            is_imported: self.is_imported,
+            expires_at: self.expires_at,
+            sender_name: self.sender_name,
+            sender_avatar: self.sender_avatar,
+            group_name: self.group_name.unwrap_or_default(),
+            group_avatar: self.group_avatar,
+            reply_to: QuotedMessage::from_columns(
+                self.quoted_id,
+                self.quoted_sender_id,
+                self.quoted_sender_name,
+                self.quoted_content,
+                self.quoted_media_url,
+                self.quoted_is_deleted,
+            ),
+            reply_count: self.reply_count,
+            mentions: self.mentions.0,
+            formatting: self.formatting.0,
+            link_preview: LinkPreview::from_columns(
+                self.preview_url,
+                self.preview_title,
+                self.preview_description,
+                self.preview_image_url,
+                self.preview_site_name,
+            ),
+        }
+    }
+}
+
+async fn fetch_messages(
+    state: &AppState,
+    user_id: Uuid,
+    conversation_id: Uuid,
+    bounds: PageBounds,
+) -> Result<Vec<MessageRow>, AppError> {
+    // Each branch walks (conversation_id, created_at, id) in index order, and
+    // only the branch for this direction runs
+    let messages = sqlx::query_as!(
+        MessageRow,
+        r#"
+        WITH page AS (
+            (
+                SELECT m.id FROM messages m
+                WHERE $6
+                    AND m.conversation_id = $1
+                    AND (m.created_at, m.id) < (COALESCE($2::timestamptz, 'infinity'), COALESCE($3::uuid, 'ffffffff-ffff-ffff-ffff-ffffffffffff'))
+                    AND (m.created_at, m.id) > (COALESCE($4::timestamptz, '-infinity'), COALESCE($5::uuid, '00000000-0000-0000-0000-000000000000'))
+                    AND NOT EXISTS (SELECT 1 FROM hidden_messages h WHERE h.message_id = m.id AND h.user_id = $8)
+                    AND (m.expires_at IS NULL OR m.expires_at > NOW())
+                ORDER BY m.created_at ASC, m.id ASC
+                LIMIT $7
+            )
+            UNION ALL
+            (
+                SELECT m.id FROM messages m
+                WHERE NOT $6
+                    AND m.conversation_id = $1
+                    AND (m.created_at, m.id) < (COALESCE($2::timestamptz, 'infinity'), COALESCE($3::uuid, 'ffffffff-ffff-ffff-ffff-ffffffffffff'))
+                    AND (m.created_at, m.id) > (COALESCE($4::timestamptz, '-infinity'), COALESCE($5::uuid, '00000000-0000-0000-0000-000000000000'))
+                    AND NOT EXISTS (SELECT 1 FROM hidden_messages h WHERE h.message_id = m.id AND h.user_id = $8)
+                    AND (m.expires_at IS NULL OR m.expires_at > NOW())
+                ORDER BY m.created_at DESC, m.id DESC
+                LIMIT $7
+            )
+        )
+        SELECT
+            m.id, m.sender_id, m.receiver_id, m.conversation_id, m.content, m.media_url,
+            m.created_at, m.updated_at, m.is_edited, m.is_deleted, m.is_system, m.is_imported,
+            m.expires_at,
+            m.formatting as "formatting: SqlJson<Vec<FormattingEntity>>",
+            COALESCE(u.display_name, u.username) as "sender_name!",
+            u.avatar_url as sender_avatar,
+            g.name as "group_name?",
+            g.avatar_url as "group_avatar?",
+            q.id as "quoted_id?",
+            q.sender_id as "quoted_sender_id?",
+            qu.display_name as "quoted_sender_name?",
+            q.content as "quoted_content?",
+            q.media_url as "quoted_media_url?",
+            q.is_deleted as "quoted_is_deleted?",
+            (SELECT COUNT(*) FROM messages r WHERE r.reply_to_id = m.id AND NOT r.is_deleted) as "reply_count!",
+            message_mention_entities(m.id) as "mentions!: SqlJson<Vec<MentionEntity>>",
+            lp.url as "preview_url?",
+            lp.title as "preview_title?",
+            lp.description as "preview_description?",
+            lp.image_url as "preview_image_url?",
+            lp.site_name as "preview_site_name?",
+            sr.status_id as "reply_status_id?",
+            sr.status_author_id as "reply_status_author_id?",
+            sr.content as "reply_status_content?",
+            sr.media_url as "reply_status_media_url?",
+            sr.media_type as "reply_status_media_type?",
+            sr.background_color as "reply_status_background_color?"
+        FROM page
+        JOIN messages m ON m.id = page.id
+        JOIN users u ON u.id = m.sender_id
+        LEFT JOIN groups g ON g.id = m.conversation_id
+        LEFT JOIN messages q ON q.id = m.reply_to_id
+        LEFT JOIN users qu ON qu.id = q.sender_id
+        LEFT JOIN message_link_previews mlp ON mlp.message_id = m.id
+        LEFT JOIN link_previews lp ON lp.url = mlp.url AND lp.is_available
+        LEFT JOIN status_replies sr ON sr.message_id = m.id
+        ORDER BY
+            CASE WHEN $6 THEN m.created_at END ASC,
+            CASE WHEN $6 THEN m.id END ASC,
+            m.created_at DESC,
+            m.id DESC
+        "#,
+        conversation_id,
+        bounds.before.map(|c| c.created_at),
+        bounds.before.map(|c| c.id),
+        bounds.after.map(|c| c.created_at),
+        bounds.after.map(|c| c.id),
+        bounds.ascending,
+        bounds.limit,
+        user_id
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(messages)
+}
+
+/// Checks that a reply targets a message in the same conversation that hasn't
+/// been deleted, and returns the quote to show with the reply.
+pub(crate) async fn reply_target(
+    state: &AppState,
+    reply_to_id: Uuid,
+    conversation_id: Uuid,
+) -> Result<QuotedMessage, AppError> {
+    let original = sqlx::query!(
+        r#"
+        SELECT m.id, m.sender_id, m.content, m.media_url, m.is_deleted, u.display_name
+        FROM messages m
+        JOIN users u ON u.id = m.sender_id
+        WHERE m.id = $1 AND m.conversation_id = $2
+        "#,
+        reply_to_id,
+        conversation_id
+    )
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Replied message not found".into()))?;
+
+    if original.is_deleted {
+        return Err(AppError::BadRequest("Cannot reply to a deleted message".into()));
+    }
+
+    Ok(QuotedMessage::new(
+        original.id,
+        original.sender_id,
+        original.display_name,
+        &original.content,
+        original.media_url.as_deref(),
+        original.is_deleted,
+    ))
+}
+
+async fn load_quoted_message(
+    state: &AppState,
+    message_id: Uuid,
+) -> Result<Option<QuotedMessage>, AppError> {
+    let original = sqlx::query!(
+        r#"
+        SELECT m.id, m.sender_id, m.content, m.media_url, m.is_deleted, u.display_name
+        FROM messages m
+        JOIN users u ON u.id = m.sender_id
+        WHERE m.id = $1
+        "#,
+        message_id
+    )
+    .fetch_optional(&state.pool)
+    .await?;
+
+    Ok(original.map(|original| QuotedMessage::new(
+        original.id,
+        original.sender_id,
+        original.display_name,
+        &original.content,
+        original.media_url.as_deref(),
+        original.is_deleted,
+    )))
+}
+
+async fn count_replies(state: &AppState, message_id: Uuid) -> Result<i64, AppError> {
+    let count = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!" FROM messages
+        WHERE reply_to_id = $1 AND NOT is_deleted
+        "#,
+        message_id
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    Ok(count)
+}
+
+/// Whether the user is part of the conversation the message belongs to.
+pub(crate) async fn can_view_message(
+    state: &AppState,
+    message_id: Uuid,
+    user_id: Uuid,
+) -> Result<bool, AppError> {
+    let can_view = sqlx::query!(
+        r#"
+        SELECT 1 as "exists!" FROM messages m
+        JOIN conversation_members cm ON cm.conversation_id = m.conversation_id
+        WHERE m.id = $1 AND cm.user_id = $2
+        "#,
+        message_id,
+        user_id
+    )
+    .fetch_optional(&state.pool)
+    .await?
+    .is_some();
+
+    Ok(can_view)
+}
+
+/// Stores a message for the dispatcher to send at `send_at`. Everything that
+/// can be checked up front is; the rest is checked again when it is sent.
+async fn schedule_message(
+    state: &AppState,
+    sender_id: Uuid,
+    receiver_id: Uuid,
+    is_group: bool,
+    req: CreateMessageRequest,
+    send_at: DateTime<Utc>,
+) -> Result<ScheduledMessage, AppError> {
+    validate_content(&req.content)?;
+    validate_formatting(&req.content, &req.formatting)?;
+    validate_send_at(send_at)?;
+    if req.status_id.is_some() {
+        return Err(AppError::BadRequest("Status replies cannot be scheduled".into()));
+    }
+
+    let conversation = if is_group {
+        Some(conversations::ensure_group_member(state, sender_id, receiver_id).await?)
+    } else {
+        conversations::find_conversation(state, sender_id, receiver_id)
+            .await?
+            .filter(|conversation| !conversation.is_group())
+    };
+    if let Some(reply_to_id) = req.reply_to_id {
+        // A direct chat that hasn't started has nothing to reply to
+        let conversation = conversation.ok_or_else(|| AppError::NotFound("Replied message not found".into()))?;
+        reply_target(state, reply_to_id, conversation.id).await?;
+    }
+
+    let pending = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!" FROM scheduled_messages
+        WHERE sender_id = $1 AND status = 'pending'
+        "#,
+        sender_id
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    if pending >= MAX_PENDING_SCHEDULED_MESSAGES {
+        return Err(AppError::BadRequest(format!(
+            "Cannot have more than {} scheduled messages",
+            MAX_PENDING_SCHEDULED_MESSAGES
+        )));
+    }
+
+    let scheduled = sqlx::query_as!(
+        ScheduledMessage,
+        r#"
+        INSERT INTO scheduled_messages (id, sender_id, receiver_id, is_group, content, media_url,
+                                        reply_to_id, send_at, status, formatting)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        RETURNING id, sender_id, receiver_id, is_group, content, media_url, reply_to_id, send_at,
+                  status as "status: _", last_error, created_at, updated_at,
+                  formatting as "formatting: _"
+        "#,
+        Uuid::new_v4(),
+        sender_id,
+        receiver_id,
+        is_group,
+        req.content,
+        req.media_url,
+        req.reply_to_id,
+        send_at,
+        ScheduledMessageStatus::Pending as ScheduledMessageStatus,
+        SqlJson(&req.formatting) as _
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    Ok(scheduled)
+}
+
+fn validate_content(content: &str) -> Result<(), AppError> {
+    if content.trim().is_empty() {
+        return Err(AppError::BadRequest("Message content cannot be empty".into()));
+    }
+    if content.len() > MAX_MESSAGE_LENGTH {
+        return Err(AppError::BadRequest(format!(
+            "Message content exceeds maximum length of {} characters",
+            MAX_MESSAGE_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+fn validate_send_at(send_at: DateTime<Utc>) -> Result<(), AppError> {
+    let now = Utc::now();
+    if send_at <= now {
+        return Err(AppError::BadRequest("Scheduled time must be in the future".into()));
+    }
+    if send_at > now + Duration::days(MAX_SCHEDULE_AHEAD_DAYS) {
+        return Err(AppError::BadRequest(format!(
+            "Messages cannot be scheduled more than {} days ahead",
+            MAX_SCHEDULE_AHEAD_DAYS
+        )));
+    }
+    Ok(())
+}
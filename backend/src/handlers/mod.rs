@@ -15,21 +15,39 @@ pub mod users;
 pub mod media;
 pub mod groups;
 pub mod messages;
-pub mod chat;
-pub mod group_chat;
 pub mod message_actions;
-pub mod message_reactions;
 pub mod ws;
+pub mod presence;
+pub mod privacy;
+pub mod status;
+pub mod disappearing;
+pub mod pins;
+pub mod starred;
+pub mod search;
+pub mod mentions;
+pub mod receipts;
+pub mod conversations;
+pub mod exports;
+pub mod imports;
 
 pub use auth::*;
 pub use users::*;
 pub use media::*;
 pub use groups::*;
 pub use messages::*;
-pub use chat::*;
-pub use group_chat::*;
 pub use message_actions::*;
-pub use message_reactions::*;
 pub use ws::*;
+pub use presence::*;
+pub use privacy::*;
+pub use status::*;
+pub use disappearing::*;
+pub use pins::*;
+pub use starred::*;
+pub use search::*;
+pub use mentions::*;
+pub use receipts::*;
+pub use conversations::*;
+pub use exports::*;
+pub use imports::*;
 
 // use crate::{config::Config, database::Database};
\ No newline at end of file
@@ -0,0 +1,260 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use sqlx::{Postgres, Transaction};
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::{
+    AppState,
+    error::AppError,
+    models::{
+        group::GroupPermission,
+        message::{PinnedMessageResponse, QuotedMessage},
+    },
+    auth::Claims,
+    handlers::conversations::{self, conversation_members},
+    websocket::{fanout, validation::WebSocketMessage},
+};
+
+const MAX_PINNED_MESSAGES: i64 = 3;
+
+pub async fn pin_message(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(message_id): Path<Uuid>,
+) -> Result<Json<PinnedMessageResponse>, AppError> {
+    let mut tx = state.pool.begin().await?;
+    let target = pin_target(&mut tx, message_id, claims.sub).await?;
+
+    if target.is_deleted || target.is_system {
+        return Err(AppError::BadRequest("This message cannot be pinned".into()));
+    }
+
+    // Serialize pins within the conversation so the limit holds
+    sqlx::query!(
+        "SELECT pg_advisory_xact_lock(hashtextextended($1, 0))",
+        format!("pins:{}", target.conversation_id)
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let pinned = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM pinned_messages p
+        JOIN messages m ON m.id = p.message_id
+        WHERE m.conversation_id = $1
+        "#,
+        target.conversation_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if pinned >= MAX_PINNED_MESSAGES {
+        return Err(AppError::BadRequest(format!(
+            "A chat cannot have more than {} pinned messages",
+            MAX_PINNED_MESSAGES
+        )));
+    }
+
+    let pin = sqlx::query!(
+        r#"
+        INSERT INTO pinned_messages (message_id, pinned_by, pinned_at)
+        VALUES ($1, $2, NOW())
+        ON CONFLICT (message_id) DO NOTHING
+        RETURNING pinned_at
+        "#,
+        message_id,
+        claims.sub
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| AppError::BadRequest("Message is already pinned".into()))?;
+
+    tx.commit().await?;
+
+    let members = conversation_members(&state, target.conversation_id).await?;
+    fanout::send_to_users(&state, &members, &WebSocketMessage::MessagePinned {
+        message_id,
+        receiver_id: target.receiver_id,
+        pinned_by: claims.sub,
+        pinned_at: pin.pinned_at,
+    })
+    .await?;
+
+    Ok(Json(PinnedMessageResponse {
+        message: target.quote(),
+        pinned_by: claims.sub,
+        pinned_at: pin.pinned_at,
+    }))
+}
+
+pub async fn unpin_message(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(message_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let mut tx = state.pool.begin().await?;
+    let target = pin_target(&mut tx, message_id, claims.sub).await?;
+
+    let result = sqlx::query!("DELETE FROM pinned_messages WHERE message_id = $1", message_id)
+        .execute(&mut *tx)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Message is not pinned".into()));
+    }
+
+    tx.commit().await?;
+
+    let members = conversation_members(&state, target.conversation_id).await?;
+    fanout::send_to_users(&state, &members, &WebSocketMessage::MessageUnpinned {
+        message_id,
+        receiver_id: target.receiver_id,
+        unpinned_by: claims.sub,
+    })
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_group_pins(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(group_id): Path<Uuid>,
+) -> Result<Json<Vec<PinnedMessageResponse>>, AppError> {
+    let conversation = conversations::ensure_group_member(&state, claims.sub, group_id).await?;
+    Ok(Json(load_pins(&state, claims.sub, conversation.id).await?))
+}
+
+pub async fn get_direct_pins(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<Vec<PinnedMessageResponse>>, AppError> {
+    let conversation = conversations::find_conversation(&state, claims.sub, user_id)
+        .await?
+        .filter(|conversation| !conversation.is_group());
+
+    match conversation {
+        Some(conversation) => Ok(Json(load_pins(&state, claims.sub, conversation.id).await?)),
+        None => Ok(Json(Vec::new())),
+    }
+}
+
+struct PinTarget {
+    id: Uuid,
+    sender_id: Uuid,
+    receiver_id: Uuid,
+    conversation_id: Uuid,
+    content: String,
+    media_url: Option<String>,
+    is_deleted: bool,
+    is_system: bool,
+    sender_name: String,
+}
+
+impl PinTarget {
+    fn quote(&self) -> QuotedMessage {
+        QuotedMessage::new(
+            self.id,
+            self.sender_id,
+            self.sender_name.clone(),
+            &self.content,
+            self.media_url.as_deref(),
+            self.is_deleted,
+        )
+    }
+}
+
+/// Loads a message the user may pin or unpin: any message in their direct
+/// chats, or in groups whose pin permission they have.
+async fn pin_target(
+    tx: &mut Transaction<'_, Postgres>,
+    message_id: Uuid,
+    user_id: Uuid,
+) -> Result<PinTarget, AppError> {
+    let message = sqlx::query!(
+        r#"
+        SELECT m.id, m.sender_id, m.receiver_id, m.conversation_id, m.content, m.media_url,
+            m.is_deleted, m.is_system, u.display_name,
+            g.pin_permission as "pin_permission?: GroupPermission",
+            gm.role::text as "role?"
+        FROM messages m
+        JOIN conversation_members cm ON cm.conversation_id = m.conversation_id AND cm.user_id = $2
+        JOIN users u ON u.id = m.sender_id
+        LEFT JOIN groups g ON g.id = m.conversation_id
+        LEFT JOIN group_members gm ON gm.group_id = g.id AND gm.user_id = $2
+        WHERE m.id = $1
+            AND (m.expires_at IS NULL OR m.expires_at > NOW())
+            AND NOT EXISTS (SELECT 1 FROM hidden_messages h WHERE h.message_id = m.id AND h.user_id = $2)
+        FOR UPDATE OF m
+        "#,
+        message_id,
+        user_id
+    )
+    .fetch_optional(&mut **tx)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Message not found".into()))?;
+
+    if message.pin_permission == Some(GroupPermission::Admins)
+        && !matches!(message.role.as_deref(), Some("owner" | "admin"))
+    {
+        return Err(AppError::Forbidden("Only admins can pin messages in this group".into()));
+    }
+
+    Ok(PinTarget {
+        id: message.id,
+        sender_id: message.sender_id,
+        receiver_id: message.receiver_id,
+        conversation_id: message.conversation_id,
+        content: message.content,
+        media_url: message.media_url,
+        is_deleted: message.is_deleted,
+        is_system: message.is_system,
+        sender_name: message.display_name,
+    })
+}
+
+/// Pins of a conversation, most recently pinned first.
+async fn load_pins(
+    state: &AppState,
+    user_id: Uuid,
+    conversation_id: Uuid,
+) -> Result<Vec<PinnedMessageResponse>, AppError> {
+    let pins = sqlx::query!(
+        r#"
+        SELECT m.id, m.sender_id, m.content, m.media_url, m.is_deleted,
+            u.display_name, p.pinned_by, p.pinned_at
+        FROM pinned_messages p
+        JOIN messages m ON m.id = p.message_id
+        JOIN users u ON u.id = m.sender_id
+        WHERE m.conversation_id = $2
+            AND (m.expires_at IS NULL OR m.expires_at > NOW())
+            AND NOT EXISTS (SELECT 1 FROM hidden_messages h WHERE h.message_id = m.id AND h.user_id = $1)
+        ORDER BY p.pinned_at DESC
+        "#,
+        user_id,
+        conversation_id
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(pins
+        .into_iter()
+        .map(|pin| PinnedMessageResponse {
+            message: QuotedMessage::new(
+                pin.id,
+                pin.sender_id,
+                pin.display_name,
+                &pin.content,
+                pin.media_url.as_deref(),
+                pin.is_deleted,
+            ),
+            pinned_by: pin.pinned_by,
+            pinned_at: pin.pinned_at,
+        })
+        .collect())
+}
@@ -0,0 +1,162 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::{
+    AppState,
+    error::AppError,
+    models::{privacy::PrivacySetting, user::PresenceResponse},
+    auth::Claims,
+    services::{cache::CacheTag, privacy::PrivacyRules},
+    websocket::{fanout, validation::WebSocketMessage},
+};
+
+const MAX_PRESENCE_IDS: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct PresenceQuery {
+    /// Comma separated list of user ids
+    pub ids: String,
+}
+
+pub async fn get_presence(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Query(query): Query<PresenceQuery>,
+) -> Result<Json<Vec<PresenceResponse>>, AppError> {
+    let ids = query
+        .ids
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(Uuid::parse_str)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if ids.len() > MAX_PRESENCE_IDS {
+        return Err(AppError::BadRequest(format!(
+            "Cannot query presence for more than {} users at once",
+            MAX_PRESENCE_IDS
+        )));
+    }
+
+    let online = state.presence.online_users(&ids).await?;
+    let rules = PrivacyRules::load(&state.pool, &ids, &[claims.sub]).await?;
+
+    let users = sqlx::query!(
+        r#"
+        SELECT id, last_seen
+        FROM users
+        WHERE id = ANY($1)
+        "#,
+        &ids
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(Json(users.into_iter().map(|u| PresenceResponse {
+        user_id: u.id,
+        is_online: online.contains(&u.id)
+            && rules.allows(u.id, claims.sub, PrivacySetting::Online),
+        last_seen: u.last_seen
+            .filter(|_| rules.allows(u.id, claims.sub, PrivacySetting::LastSeen)),
+    }).collect()))
+}
+
+/// Marks a user online when their first connection on any node opens.
+pub async fn connection_opened(
+    state: &AppState,
+    user_id: Uuid,
+    connection_id: Uuid,
+) -> Result<(), AppError> {
+    if !state.presence.connect(user_id, connection_id).await? {
+        return Ok(());
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET is_online = true
+        WHERE id = $1
+        "#,
+        user_id
+    )
+    .execute(&state.pool)
+    .await?;
+
+    // The profile and the directory both show presence
+    state.cache.invalidate(&[CacheTag::User(user_id), CacheTag::UserDirectory]).await;
+    notify_contacts(state, user_id, true, None).await
+}
+
+/// Marks a user offline and persists `last_seen` once their last connection closes.
+pub async fn connection_closed(
+    state: &AppState,
+    user_id: Uuid,
+    connection_id: Uuid,
+) -> Result<(), AppError> {
+    if !state.presence.disconnect(user_id, connection_id).await? {
+        return Ok(());
+    }
+
+    let user = sqlx::query!(
+        r#"
+        UPDATE users
+        SET is_online = false, last_seen = NOW()
+        WHERE id = $1
+        RETURNING last_seen
+        "#,
+        user_id
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    state.cache.invalidate(&[CacheTag::User(user_id), CacheTag::UserDirectory]).await;
+    notify_contacts(state, user_id, false, user.last_seen).await
+}
+
+async fn notify_contacts(
+    state: &AppState,
+    user_id: Uuid,
+    is_online: bool,
+    last_seen: Option<DateTime<Utc>>,
+) -> Result<(), AppError> {
+    // Everyone who has this user in their contact list gets the update
+    let watchers: Vec<Uuid> = sqlx::query!(
+        r#"
+        SELECT user_id FROM contacts
+        WHERE contact_id = $1
+        "#,
+        user_id
+    )
+    .fetch_all(&state.pool)
+    .await?
+    .into_iter()
+    .map(|row| row.user_id)
+    .collect();
+
+    // Watchers who may not see online status get nothing at all; those who may
+    // see it but not last seen get the event without the timestamp
+    let rules = PrivacyRules::load(&state.pool, &[user_id], &watchers).await?;
+    let (with_last_seen, without_last_seen): (Vec<Uuid>, Vec<Uuid>) = watchers
+        .into_iter()
+        .filter(|watcher| rules.allows(user_id, *watcher, PrivacySetting::Online))
+        .partition(|watcher| rules.allows(user_id, *watcher, PrivacySetting::LastSeen));
+
+    fanout::send_to_users(state, &with_last_seen, &WebSocketMessage::Presence {
+        user_id,
+        is_online,
+        last_seen,
+    })
+    .await?;
+
+    fanout::send_to_users(state, &without_last_seen, &WebSocketMessage::Presence {
+        user_id,
+        is_online,
+        last_seen: None,
+    })
+    .await
+}
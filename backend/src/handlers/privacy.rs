@@ -0,0 +1,143 @@
+use axum::{
+    extract::State,
+    Json,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::{
+    AppState,
+    error::AppError,
+    models::privacy::{
+        PrivacyResponse, PrivacySetting, PrivacySettings, UpdatePrivacyRequest,
+    },
+    auth::Claims,
+};
+
+const MAX_EXCEPTIONS_PER_SETTING: usize = 1000;
+
+pub async fn get_privacy(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+) -> Result<Json<PrivacyResponse>, AppError> {
+    Ok(Json(load_privacy(&state, claims.sub).await?))
+}
+
+pub async fn update_privacy(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Json(req): Json<UpdatePrivacyRequest>,
+) -> Result<Json<PrivacyResponse>, AppError> {
+    if let Some(exceptions) = &req.exceptions {
+        if exceptions.values().any(|ids| ids.len() > MAX_EXCEPTIONS_PER_SETTING) {
+            return Err(AppError::BadRequest(format!(
+                "A privacy setting cannot have more than {} exceptions",
+                MAX_EXCEPTIONS_PER_SETTING
+            )));
+        }
+    }
+
+    let mut tx = state.pool.begin().await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO privacy_settings (user_id, last_seen, online, avatar, status, read_receipts)
+        VALUES ($1, COALESCE($2, 'everyone'), COALESCE($3, 'everyone'), COALESCE($4, 'everyone'),
+                COALESCE($5, 'everyone'), COALESCE($6, 'everyone'))
+        ON CONFLICT (user_id) DO UPDATE SET
+            last_seen = COALESCE($2, privacy_settings.last_seen),
+            online = COALESCE($3, privacy_settings.online),
+            avatar = COALESCE($4, privacy_settings.avatar),
+            status = COALESCE($5, privacy_settings.status),
+            read_receipts = COALESCE($6, privacy_settings.read_receipts)
+        "#,
+        claims.sub,
+        req.last_seen as _,
+        req.online as _,
+        req.avatar as _,
+        req.status as _,
+        req.read_receipts as _
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    for (setting, user_ids) in req.exceptions.unwrap_or_default() {
+        sqlx::query!(
+            r#"
+            DELETE FROM privacy_exceptions
+            WHERE user_id = $1 AND setting = $2
+            "#,
+            claims.sub,
+            setting.as_str()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO privacy_exceptions (user_id, setting, excluded_user_id)
+            SELECT $1, $2, excluded FROM UNNEST($3::uuid[]) AS excluded
+            WHERE excluded <> $1
+            ON CONFLICT DO NOTHING
+            "#,
+            claims.sub,
+            setting.as_str(),
+            &user_ids
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(Json(load_privacy(&state, claims.sub).await?))
+}
+
+async fn load_privacy(state: &AppState, user_id: Uuid) -> Result<PrivacyResponse, AppError> {
+    let settings = sqlx::query_as!(
+        PrivacySettings,
+        r#"
+        SELECT
+            user_id,
+            last_seen as "last_seen: _",
+            online as "online: _",
+            avatar as "avatar: _",
+            status as "status: _",
+            read_receipts as "read_receipts: _",
+            updated_at
+        FROM privacy_settings
+        WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_optional(&state.pool)
+    .await?
+    .unwrap_or_else(|| PrivacySettings::default_for(user_id));
+
+    let rows = sqlx::query!(
+        r#"
+        SELECT setting, excluded_user_id FROM privacy_exceptions
+        WHERE user_id = $1
+        ORDER BY created_at
+        "#,
+        user_id
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    let mut exceptions: HashMap<PrivacySetting, Vec<Uuid>> = HashMap::new();
+    for row in rows {
+        if let Some(setting) = PrivacySetting::parse(&row.setting) {
+            exceptions.entry(setting).or_default().push(row.excluded_user_id);
+        }
+    }
+
+    Ok(PrivacyResponse {
+        last_seen: settings.last_seen,
+        online: settings.online,
+        avatar: settings.avatar,
+        status: settings.status,
+        read_receipts: settings.read_receipts,
+        exceptions,
+    })
+}
@@ -0,0 +1,311 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::{
+    AppState,
+    error::AppError,
+    metrics::MESSAGES_DELIVERED,
+    models::{
+        message::{
+            AckDeliveryRequest, DeliveryStatus, MessageStatus, MessageStatusResponse, RecipientStatus,
+        },
+        privacy::PrivacySetting,
+    },
+    auth::Claims,
+    handlers::conversations::conversation_members,
+    services::{privacy::PrivacyRules, read_marks},
+    websocket::{fanout, validation::WebSocketMessage},
+};
+
+const MAX_ACKS_PER_REQUEST: usize = 100;
+/// Senders are told about at most this many of the messages a read catches up on
+const MAX_STATUS_UPDATES_PER_READ: i64 = 100;
+
+/// Acknowledges that messages reached one of my devices. Clients send this on
+/// their own as messages arrive; messages I can't see are skipped.
+pub async fn ack_delivered(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Json(req): Json<AckDeliveryRequest>,
+) -> Result<StatusCode, AppError> {
+    record_deliveries(&state, claims.sub, &req.message_ids).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Delivery and read state of one of my messages, per recipient.
+pub async fn get_message_status(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(message_id): Path<Uuid>,
+) -> Result<Json<MessageStatusResponse>, AppError> {
+    let sender_id = sqlx::query_scalar!("SELECT sender_id FROM messages WHERE id = $1", message_id)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Message not found".into()))?;
+
+    if sender_id != claims.sub {
+        return Err(AppError::Forbidden("Only the sender can see a message's status".into()));
+    }
+
+    let statuses = load_statuses(&state, &[message_id]).await?;
+    let (status, recipients) = match statuses.into_iter().next() {
+        Some(loaded) => (loaded.status, loaded.recipients),
+        // Nobody else was in the group when it was sent
+        None => (summarize(message_id, &[]), Vec::new()),
+    };
+
+    Ok(Json(MessageStatusResponse { status, recipients }))
+}
+
+/// Stores delivery acks from a recipient and tells the senders of the
+/// messages whose status changed.
+pub(crate) async fn record_deliveries(
+    state: &AppState,
+    user_id: Uuid,
+    message_ids: &[Uuid],
+) -> Result<(), AppError> {
+    if message_ids.len() > MAX_ACKS_PER_REQUEST {
+        return Err(AppError::BadRequest(format!(
+            "Cannot acknowledge more than {} messages at once",
+            MAX_ACKS_PER_REQUEST
+        )));
+    }
+
+    let delivered = sqlx::query_scalar!(
+        r#"
+        INSERT INTO message_deliveries (message_id, user_id, delivered_at)
+        SELECT m.id, $1, NOW() FROM messages m
+        JOIN conversation_members cm ON cm.conversation_id = m.conversation_id AND cm.user_id = $1
+        WHERE m.id = ANY($2)
+            AND m.sender_id <> $1
+            AND NOT m.is_system
+        ON CONFLICT (message_id, user_id) DO NOTHING
+        RETURNING message_id
+        "#,
+        user_id,
+        message_ids
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    MESSAGES_DELIVERED.inc_by(delivered.len() as u64);
+    notify_status_changed(state, &delivered).await
+}
+
+/// Sends the current status of each message to its sender's devices.
+pub(crate) async fn notify_status_changed(state: &AppState, message_ids: &[Uuid]) -> Result<(), AppError> {
+    if message_ids.is_empty() {
+        return Ok(());
+    }
+
+    for loaded in load_statuses(state, message_ids).await? {
+        fanout::send_to_users(state, &[loaded.sender_id], &WebSocketMessage::MessageStatusChanged {
+            receiver_id: loaded.receiver_id,
+            status: loaded.status,
+        })
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Moves the user's read watermark in the message's conversation up to the
+/// message, so it and everything before it count as read. Conversation
+/// members the reader shares read receipts with are told, as are the senders
+/// of the messages it caught up on.
+pub(crate) async fn mark_read_up_to(state: &AppState, user_id: Uuid, message_id: Uuid) -> Result<(), AppError> {
+    let message = sqlx::query!(
+        r#"
+        SELECT m.sender_id, m.receiver_id, m.conversation_id, m.created_at,
+            c.kind = 'group' as "is_group!"
+        FROM messages m
+        JOIN conversations c ON c.id = m.conversation_id
+        JOIN conversation_members cm ON cm.conversation_id = m.conversation_id AND cm.user_id = $2
+        WHERE m.id = $1
+        "#,
+        message_id,
+        user_id
+    )
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Message not found".into()))?;
+
+    let mut tx = state.pool.begin().await?;
+
+    let previous = sqlx::query_scalar!(
+        r#"
+        SELECT last_read_at FROM conversation_members
+        WHERE conversation_id = $1 AND user_id = $2
+        FOR UPDATE
+        "#,
+        message.conversation_id,
+        user_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if previous >= Some(message.created_at) {
+        return Ok(());
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE conversation_members
+        SET last_read_at = $3
+        WHERE conversation_id = $1 AND user_id = $2
+        "#,
+        message.conversation_id,
+        user_id,
+        message.created_at
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    read_marks::record(&mut tx, message.conversation_id, user_id, previous, message.created_at).await?;
+
+    tx.commit().await?;
+
+    let newly_read = sqlx::query_scalar!(
+        r#"
+        SELECT id FROM messages
+        WHERE conversation_id = $2
+            AND sender_id <> $1
+            AND ($3::timestamptz IS NULL OR created_at > $3)
+            AND created_at <= $4
+        ORDER BY created_at DESC
+        LIMIT $5
+        "#,
+        user_id,
+        message.conversation_id,
+        previous,
+        message.created_at,
+        MAX_STATUS_UPDATES_PER_READ
+    )
+    .fetch_all(&state.pool)
+    .await?;
+    notify_status_changed(state, &newly_read).await?;
+
+    let members = conversation_members(state, message.conversation_id).await?;
+    let rules = PrivacyRules::load(&state.pool, &[user_id], &members).await?;
+    let recipients: Vec<Uuid> = members
+        .into_iter()
+        .filter(|&member| rules.allows(user_id, member, PrivacySetting::ReadReceipts))
+        .collect();
+    let event = if message.is_group {
+        WebSocketMessage::GroupRead { group_id: message.conversation_id, user_id, message_id }
+    } else {
+        // The other side of the direct chat
+        let chat_id = if message.sender_id == user_id { message.receiver_id } else { message.sender_id };
+        WebSocketMessage::Read { user_id, chat_id, message_id }
+    };
+    fanout::send_to_users(state, &recipients, &event).await?;
+
+    Ok(())
+}
+
+struct LoadedStatus {
+    sender_id: Uuid,
+    receiver_id: Uuid,
+    status: MessageStatus,
+    recipients: Vec<RecipientStatus>,
+}
+
+/// Recipients of each message with their delivery and read times, as the
+/// sender may see them. A message was read when the reader's watermark first
+/// reached it, to within a few minutes, and reading a message counts as
+/// delivering it.
+async fn load_statuses(state: &AppState, message_ids: &[Uuid]) -> Result<Vec<LoadedStatus>, AppError> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT m.id as message_id, m.sender_id, m.receiver_id,
+            u.id as "user_id!", u.display_name,
+            COALESCE(d.delivered_at, read.read_at) as "delivered_at?",
+            read.read_at as "read_at?"
+        FROM messages m
+        JOIN conversation_members cm ON cm.conversation_id = m.conversation_id
+            AND cm.user_id <> m.sender_id AND cm.joined_at <= m.created_at
+        JOIN users u ON u.id = cm.user_id
+        LEFT JOIN message_deliveries d ON d.message_id = m.id AND d.user_id = u.id
+        LEFT JOIN LATERAL (
+            SELECT GREATEST(rm.marked_at, m.created_at) as read_at
+            FROM read_marks rm
+            WHERE rm.conversation_id = cm.conversation_id AND rm.user_id = cm.user_id
+                AND rm.read_up_to >= m.created_at
+            ORDER BY rm.read_up_to
+            LIMIT 1
+        ) read ON TRUE
+        WHERE m.id = ANY($1)
+        ORDER BY m.id, u.display_name
+        "#,
+        message_ids
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    let readers: Vec<Uuid> = rows.iter().map(|row| row.user_id).collect();
+    let senders: Vec<Uuid> = rows.iter().map(|row| row.sender_id).collect();
+    let rules = PrivacyRules::load(&state.pool, &readers, &senders).await?;
+
+    let mut loaded: Vec<LoadedStatus> = Vec::new();
+    let mut index: HashMap<Uuid, usize> = HashMap::new();
+    for row in rows {
+        let read_at = row
+            .read_at
+            .filter(|_| rules.allows(row.user_id, row.sender_id, PrivacySetting::ReadReceipts));
+        let recipient = RecipientStatus {
+            user_id: row.user_id,
+            display_name: row.display_name,
+            delivered_at: row.delivered_at,
+            read_at,
+        };
+
+        match index.get(&row.message_id) {
+            Some(&i) => loaded[i].recipients.push(recipient),
+            None => {
+                index.insert(row.message_id, loaded.len());
+                loaded.push(LoadedStatus {
+                    sender_id: row.sender_id,
+                    receiver_id: row.receiver_id,
+                    status: summarize(row.message_id, &[]),
+                    recipients: vec![recipient],
+                });
+            }
+        }
+    }
+
+    for entry in &mut loaded {
+        entry.status = summarize(entry.status.message_id, &entry.recipients);
+    }
+    Ok(loaded)
+}
+
+fn summarize(message_id: Uuid, recipients: &[RecipientStatus]) -> MessageStatus {
+    let count = |at: fn(&RecipientStatus) -> Option<DateTime<Utc>>| {
+        recipients.iter().filter(|r| at(r).is_some()).count() as i64
+    };
+    let recipient_count = recipients.len() as i64;
+    let delivered_count = count(|r| r.delivered_at);
+    let read_count = count(|r| r.read_at);
+
+    let status = if recipient_count > 0 && read_count == recipient_count {
+        DeliveryStatus::Read
+    } else if recipient_count > 0 && delivered_count == recipient_count {
+        DeliveryStatus::Delivered
+    } else {
+        DeliveryStatus::Sent
+    };
+
+    MessageStatus {
+        message_id,
+        status,
+        recipient_count,
+        delivered_count,
+        read_count,
+    }
+}
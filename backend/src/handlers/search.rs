@@ -0,0 +1,132 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::Deserialize;
+use sqlx::types::Json as SqlJson;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::{
+    AppState,
+    error::AppError,
+    models::{
+        message::{ChatContext, FormattingEntity, SearchResultResponse},
+        pagination::{Cursor, MessagePage, Paginated},
+    },
+    auth::Claims,
+};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 100;
+const MAX_QUERY_LENGTH: usize = 200;
+
+#[derive(Debug, Deserialize)]
+pub struct SearchMessagesQuery {
+    pub q: String,
+    /// Group, or the other side of a direct chat, to search in
+    pub chat_id: Option<Uuid>,
+    /// Cursor to load older matches from
+    pub before: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Messages matching a full-text query across the user's chats, newest first.
+/// Open a result in its chat with the `around` parameter of message history.
+pub async fn search_messages(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Query(query): Query<SearchMessagesQuery>,
+) -> Result<Json<MessagePage<SearchResultResponse>>, AppError> {
+    let terms = query.q.trim();
+    if terms.is_empty() {
+        return Err(AppError::BadRequest("Search query cannot be empty".into()));
+    }
+    if terms.chars().count() > MAX_QUERY_LENGTH {
+        return Err(AppError::BadRequest(format!(
+            "Search query cannot be longer than {} characters",
+            MAX_QUERY_LENGTH
+        )));
+    }
+    let before = query.before.as_deref().map(Cursor::decode).transpose()?;
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+    // Deleted messages have no search entry; expired and hidden ones, and
+    // those in groups the user has left, are left out here
+    let rows = sqlx::query!(
+        r#"
+        SELECT m.id, m.content, m.media_url, m.created_at, m.is_edited,
+            m.sender_id, m.receiver_id,
+            m.formatting as "formatting: SqlJson<Vec<FormattingEntity>>",
+            COALESCE(u.display_name, u.username) as "sender_name!",
+            u.avatar_url as sender_avatar,
+            g.id as "group_id?", g.name as "group_name?", g.avatar_url as "group_avatar?",
+            o.id as "other_id?", o.display_name as "other_name?", o.avatar_url as "other_avatar?"
+        FROM message_search ms
+        JOIN messages m ON m.id = ms.message_id
+        JOIN conversation_members cm ON cm.conversation_id = m.conversation_id AND cm.user_id = $1
+        JOIN users u ON u.id = m.sender_id
+        LEFT JOIN groups g ON g.id = m.conversation_id
+        LEFT JOIN users o ON g.id IS NULL
+            AND o.id = CASE WHEN m.sender_id = $1 THEN m.receiver_id ELSE m.sender_id END
+        WHERE ms.search_vector @@ websearch_to_tsquery('english', $2)
+            AND NOT m.is_system
+            AND (m.expires_at IS NULL OR m.expires_at > NOW())
+            AND NOT EXISTS (SELECT 1 FROM hidden_messages h WHERE h.message_id = m.id AND h.user_id = $1)
+            AND ($3::uuid IS NULL OR g.id = $3 OR o.id = $3)
+            AND ($4::timestamptz IS NULL OR (m.created_at, m.id) < ($4, $5::uuid))
+        ORDER BY m.created_at DESC, m.id DESC
+        LIMIT $6
+        "#,
+        claims.sub,
+        terms,
+        query.chat_id,
+        before.map(|cursor| cursor.created_at),
+        before.map(|cursor| cursor.id),
+        limit + 1
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    let has_more_before = rows.len() as i64 > limit;
+    let messages: Vec<SearchResultResponse> = rows
+        .into_iter()
+        .take(limit as usize)
+        .map(|row| {
+            let chat = match row.group_id {
+                Some(group_id) => ChatContext {
+                    id: group_id,
+                    name: row.group_name.unwrap_or_default(),
+                    avatar_url: row.group_avatar,
+                    is_group: true,
+                },
+                None => ChatContext {
+                    id: row.other_id.unwrap_or(row.receiver_id),
+                    name: row.other_name.unwrap_or_default(),
+                    avatar_url: row.other_avatar,
+                    is_group: false,
+                },
+            };
+
+            SearchResultResponse {
+                id: row.id,
+                content: row.content,
+                media_url: row.media_url,
+                created_at: row.created_at,
+                is_edited: row.is_edited,
+                sender_id: row.sender_id,
+                sender_name: row.sender_name,
+                sender_avatar: row.sender_avatar,
+                chat,
+                formatting: row.formatting.0,
+            }
+        })
+        .collect();
+
+    Ok(Json(MessagePage {
+        before_cursor: messages.last().map(|message| message.cursor().encode()),
+        after_cursor: None,
+        has_more_before,
+        has_more_after: false,
+        messages,
+    }))
+}
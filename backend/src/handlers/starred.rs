@@ -0,0 +1,168 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::{
+    AppState,
+    error::AppError,
+    models::{
+        message::{ChatContext, StarredMessageResponse},
+        pagination::{Cursor, MessagePage, Paginated},
+    },
+    auth::Claims,
+};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct StarredMessagesQuery {
+    /// Cursor to load earlier starred messages from
+    pub before: Option<String>,
+    pub limit: Option<i64>,
+}
+
+pub async fn star_message(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(message_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    // Only messages the user can currently see can be starred
+    let message = sqlx::query!(
+        r#"
+        SELECT m.is_deleted, m.is_system FROM messages m
+        JOIN conversation_members cm ON cm.conversation_id = m.conversation_id AND cm.user_id = $2
+        WHERE m.id = $1
+            AND (m.expires_at IS NULL OR m.expires_at > NOW())
+            AND NOT EXISTS (SELECT 1 FROM hidden_messages h WHERE h.message_id = m.id AND h.user_id = $2)
+        "#,
+        message_id,
+        claims.sub
+    )
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Message not found".into()))?;
+
+    if message.is_deleted || message.is_system {
+        return Err(AppError::BadRequest("This message cannot be starred".into()));
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO starred_messages (user_id, message_id, starred_at)
+        VALUES ($1, $2, NOW())
+        ON CONFLICT (user_id, message_id) DO NOTHING
+        "#,
+        claims.sub,
+        message_id
+    )
+    .execute(&state.pool)
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn unstar_message(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(message_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    sqlx::query!(
+        "DELETE FROM starred_messages WHERE user_id = $1 AND message_id = $2",
+        claims.sub,
+        message_id
+    )
+    .execute(&state.pool)
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Starred messages across all chats, most recently starred first.
+pub async fn get_starred_messages(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Query(query): Query<StarredMessagesQuery>,
+) -> Result<Json<MessagePage<StarredMessageResponse>>, AppError> {
+    let before = query.before.as_deref().map(Cursor::decode).transpose()?;
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+    // Messages since deleted, expired or hidden, and those in groups the
+    // user has left, drop out of the list
+    let rows = sqlx::query!(
+        r#"
+        SELECT m.id, m.content, m.media_url, m.created_at, m.is_edited,
+            m.sender_id, m.receiver_id, s.starred_at,
+            u.display_name as sender_name, u.avatar_url as sender_avatar,
+            g.id as "group_id?", g.name as "group_name?", g.avatar_url as "group_avatar?",
+            o.id as "other_id?", o.display_name as "other_name?", o.avatar_url as "other_avatar?"
+        FROM starred_messages s
+        JOIN messages m ON m.id = s.message_id
+        JOIN conversation_members cm ON cm.conversation_id = m.conversation_id AND cm.user_id = $1
+        JOIN users u ON u.id = m.sender_id
+        LEFT JOIN groups g ON g.id = m.conversation_id
+        LEFT JOIN users o ON g.id IS NULL
+            AND o.id = CASE WHEN m.sender_id = $1 THEN m.receiver_id ELSE m.sender_id END
+        WHERE s.user_id = $1
+            AND NOT m.is_deleted
+            AND (m.expires_at IS NULL OR m.expires_at > NOW())
+            AND NOT EXISTS (SELECT 1 FROM hidden_messages h WHERE h.message_id = m.id AND h.user_id = $1)
+            AND ($2::timestamptz IS NULL OR (s.starred_at, m.id) < ($2, $3::uuid))
+        ORDER BY s.starred_at DESC, m.id DESC
+        LIMIT $4
+        "#,
+        claims.sub,
+        before.map(|cursor| cursor.created_at),
+        before.map(|cursor| cursor.id),
+        limit + 1
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    let has_more_before = rows.len() as i64 > limit;
+    let messages: Vec<StarredMessageResponse> = rows
+        .into_iter()
+        .take(limit as usize)
+        .map(|row| {
+            let chat = match row.group_id {
+                Some(group_id) => ChatContext {
+                    id: group_id,
+                    name: row.group_name.unwrap_or_default(),
+                    avatar_url: row.group_avatar,
+                    is_group: true,
+                },
+                None => ChatContext {
+                    id: row.other_id.unwrap_or(row.receiver_id),
+                    name: row.other_name.unwrap_or_default(),
+                    avatar_url: row.other_avatar,
+                    is_group: false,
+                },
+            };
+
+            StarredMessageResponse {
+                id: row.id,
+                content: row.content,
+                media_url: row.media_url,
+                created_at: row.created_at,
+                is_edited: row.is_edited,
+                sender_id: row.sender_id,
+                sender_name: row.sender_name,
+                sender_avatar: row.sender_avatar,
+                chat,
+                starred_at: row.starred_at,
+            }
+        })
+        .collect();
+
+    Ok(Json(MessagePage {
+        before_cursor: messages.last().map(|message| message.cursor().encode()),
+        after_cursor: None,
+        has_more_before,
+        has_more_after: false,
+        messages,
+    }))
+}
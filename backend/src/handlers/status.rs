@@ -0,0 +1,549 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{Duration, Utc};
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::{
+    AppState,
+    error::AppError,
+    models::{
+        privacy::PrivacySetting,
+        status::{
+            CreateStatusRequest, MyStatusResponse, StatusAudienceMode, StatusAudienceResponse,
+            StatusFeedEntry, StatusReplyPreview, StatusResponse, StatusUpdate,
+            StatusViewerResponse, UpdateStatusAudienceRequest,
+        },
+    },
+    auth::Claims,
+    services::{media_cleanup, privacy::PrivacyRules},
+    websocket::{fanout, validation::WebSocketMessage},
+};
+
+const MAX_STATUS_CONTENT_LENGTH: usize = 700;
+const MAX_FONT_STYLE_LENGTH: usize = 50;
+const STATUS_LIFETIME_HOURS: i64 = 24;
+const MAX_AUDIENCE_LIST_SIZE: usize = 1000;
+const REPLY_PREVIEW_LENGTH: usize = 100;
+
+pub async fn create_status(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Json(req): Json<CreateStatusRequest>,
+) -> Result<Json<StatusResponse>, AppError> {
+    // Input validation
+    let content = req.content.filter(|c| !c.trim().is_empty());
+    if content.is_none() && req.media_id.is_none() {
+        return Err(AppError::BadRequest("Status must have content or media".into()));
+    }
+    if content.as_ref().map_or(false, |c| c.chars().count() > MAX_STATUS_CONTENT_LENGTH) {
+        return Err(AppError::BadRequest(format!(
+            "Status content exceeds maximum length of {} characters",
+            MAX_STATUS_CONTENT_LENGTH
+        )));
+    }
+    if let Some(color) = &req.background_color {
+        let valid = color.len() == 7
+            && color.starts_with('#')
+            && color[1..].chars().all(|c| c.is_ascii_hexdigit());
+        if !valid {
+            return Err(AppError::BadRequest("Background color must be a #RRGGBB hex value".into()));
+        }
+    }
+    if req.font_style.as_ref().map_or(false, |f| f.len() > MAX_FONT_STYLE_LENGTH) {
+        return Err(AppError::BadRequest(format!(
+            "Font style exceeds maximum length of {} characters",
+            MAX_FONT_STYLE_LENGTH
+        )));
+    }
+
+    // Media must be an image or video the author uploaded
+    let media = match req.media_id {
+        Some(media_id) => {
+            let media = sqlx::query!(
+                r#"
+                SELECT url, type_ FROM media
+                WHERE id = $1 AND user_id = $2
+                "#,
+                media_id,
+                claims.sub
+            )
+            .fetch_optional(&state.pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Media not found".into()))?;
+
+            if media.type_ != "image" && media.type_ != "video" {
+                return Err(AppError::BadRequest("Status media must be an image or video".into()));
+            }
+            Some(media)
+        }
+        None => None,
+    };
+
+    let mode = sqlx::query_scalar!(
+        r#"
+        SELECT mode as "mode: StatusAudienceMode" FROM status_audience_settings
+        WHERE user_id = $1
+        "#,
+        claims.sub
+    )
+    .fetch_optional(&state.pool)
+    .await?
+    .unwrap_or(StatusAudienceMode::Contacts);
+
+    let mut tx = state.pool.begin().await?;
+
+    let status = sqlx::query_as!(
+        StatusUpdate,
+        r#"
+        INSERT INTO status_updates (id, user_id, content, media_id, media_url, media_type,
+                                    created_at, expires_at, background_color, font_style, audience_mode)
+        VALUES ($1, $2, $3, $4, $5, $6, NOW(), $7, $8, $9, $10)
+        RETURNING id, user_id as "user_id!", content, media_id, media_url, media_type,
+                  created_at as "created_at!", expires_at as "expires_at!",
+                  background_color, font_style
+        "#,
+        Uuid::new_v4(),
+        claims.sub,
+        content,
+        req.media_id,
+        media.as_ref().map(|m| m.url.clone()),
+        media.as_ref().map(|m| m.type_.clone()),
+        Utc::now() + Duration::hours(STATUS_LIFETIME_HOURS),
+        req.background_color,
+        req.font_style,
+        mode as StatusAudienceMode
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    // The audience is fixed when posting; later list changes don't affect this status
+    let audience = sqlx::query_scalar!(
+        r#"
+        INSERT INTO status_recipients (status_id, user_id)
+        SELECT $1, theirs.user_id
+        FROM contacts mine
+        JOIN contacts theirs ON theirs.user_id = mine.contact_id AND theirs.contact_id = mine.user_id
+        WHERE mine.user_id = $2
+            AND CASE $3::status_audience_mode
+                WHEN 'except' THEN NOT EXISTS(
+                    SELECT 1 FROM status_audience_lists l
+                    WHERE l.user_id = $2 AND l.list = 'except' AND l.member_id = theirs.user_id
+                )
+                WHEN 'only' THEN EXISTS(
+                    SELECT 1 FROM status_audience_lists l
+                    WHERE l.user_id = $2 AND l.list = 'only' AND l.member_id = theirs.user_id
+                )
+                ELSE true
+            END
+        RETURNING user_id
+        "#,
+        status.id,
+        claims.sub,
+        mode as StatusAudienceMode
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    let status = StatusResponse::from(status);
+    fanout::send_to_users(&state, &audience, &WebSocketMessage::NewStatus {
+        status: status.clone(),
+    })
+    .await?;
+
+    Ok(Json(status))
+}
+
+/// Active statuses shared with me, grouped by author with the most recent first.
+pub async fn get_status_feed(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+) -> Result<Json<Vec<StatusFeedEntry>>, AppError> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            s.id,
+            s.user_id as "user_id!",
+            s.content,
+            s.media_url,
+            s.media_type,
+            s.background_color,
+            s.font_style,
+            s.created_at as "created_at!",
+            s.expires_at as "expires_at!",
+            u.display_name,
+            u.avatar_url,
+            EXISTS(
+                SELECT 1 FROM status_views v
+                WHERE v.status_id = s.id AND v.viewer_id = $1
+            ) as "viewed!"
+        FROM status_updates s
+        JOIN users u ON u.id = s.user_id
+        JOIN status_recipients r ON r.status_id = s.id AND r.user_id = $1
+        WHERE s.expires_at > NOW()
+        ORDER BY s.created_at
+        "#,
+        claims.sub
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    let authors: Vec<Uuid> = rows.iter().map(|r| r.user_id).collect();
+    let rules = PrivacyRules::load(&state.pool, &authors, &[claims.sub]).await?;
+
+    let mut feed: HashMap<Uuid, StatusFeedEntry> = HashMap::new();
+    for row in rows {
+        let entry = feed.entry(row.user_id).or_insert_with(|| StatusFeedEntry {
+            user_id: row.user_id,
+            display_name: row.display_name.clone(),
+            avatar_url: row.avatar_url
+                .clone()
+                .filter(|_| rules.allows(row.user_id, claims.sub, PrivacySetting::Avatar)),
+            has_unviewed: false,
+            latest_at: row.created_at,
+            statuses: Vec::new(),
+        });
+        entry.has_unviewed |= !row.viewed;
+        entry.latest_at = entry.latest_at.max(row.created_at);
+        entry.statuses.push(StatusResponse {
+            id: row.id,
+            user_id: row.user_id,
+            content: row.content,
+            media_url: row.media_url,
+            media_type: row.media_type,
+            background_color: row.background_color,
+            font_style: row.font_style,
+            created_at: row.created_at,
+            expires_at: row.expires_at,
+            viewed: row.viewed,
+        });
+    }
+
+    let mut feed: Vec<StatusFeedEntry> = feed.into_values().collect();
+    feed.sort_by(|a, b| b.latest_at.cmp(&a.latest_at));
+
+    Ok(Json(feed))
+}
+
+pub async fn get_my_statuses(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+) -> Result<Json<Vec<MyStatusResponse>>, AppError> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            s.id,
+            s.content,
+            s.media_url,
+            s.media_type,
+            s.background_color,
+            s.font_style,
+            s.created_at as "created_at!",
+            s.expires_at as "expires_at!",
+            (SELECT COUNT(*) FROM status_views v WHERE v.status_id = s.id) as "view_count!"
+        FROM status_updates s
+        WHERE s.user_id = $1 AND s.expires_at > NOW()
+        ORDER BY s.created_at
+        "#,
+        claims.sub
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(Json(rows.into_iter().map(|row| MyStatusResponse {
+        status: StatusResponse {
+            id: row.id,
+            user_id: claims.sub,
+            content: row.content,
+            media_url: row.media_url,
+            media_type: row.media_type,
+            background_color: row.background_color,
+            font_style: row.font_style,
+            created_at: row.created_at,
+            expires_at: row.expires_at,
+            viewed: true,
+        },
+        view_count: row.view_count,
+    }).collect()))
+}
+
+pub async fn view_status(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(status_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let status = visible_status(&state, status_id, claims.sub).await?;
+
+    // Authors looking at their own status are not counted as viewers
+    if status.user_id != claims.sub {
+        sqlx::query!(
+            r#"
+            INSERT INTO status_views (status_id, viewer_id, viewed_at)
+            VALUES ($1, $2, NOW())
+            ON CONFLICT (status_id, viewer_id) DO NOTHING
+            "#,
+            status_id,
+            claims.sub
+        )
+        .execute(&state.pool)
+        .await?;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_status_viewers(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(status_id): Path<Uuid>,
+) -> Result<Json<Vec<StatusViewerResponse>>, AppError> {
+    let status = visible_status(&state, status_id, claims.sub).await?;
+    if status.user_id != claims.sub {
+        return Err(AppError::Forbidden("Only the author can see who viewed a status".into()));
+    }
+
+    let viewers = sqlx::query!(
+        r#"
+        SELECT
+            v.viewer_id as "viewer_id!",
+            v.viewed_at as "viewed_at!",
+            u.display_name,
+            u.avatar_url
+        FROM status_views v
+        JOIN users u ON u.id = v.viewer_id
+        WHERE v.status_id = $1
+        ORDER BY v.viewed_at DESC
+        "#,
+        status_id
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(Json(viewers.into_iter().map(|v| StatusViewerResponse {
+        user_id: v.viewer_id,
+        display_name: v.display_name,
+        avatar_url: v.avatar_url,
+        viewed_at: v.viewed_at,
+    }).collect()))
+}
+
+pub async fn delete_status(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(status_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let mut tx = state.pool.begin().await?;
+
+    // Recipients go away with the status, so collect them first
+    let audience = sqlx::query_scalar!(
+        r#"
+        SELECT user_id FROM status_recipients
+        WHERE status_id = $1
+        "#,
+        status_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let deleted = sqlx::query!(
+        r#"
+        WITH deleted AS (
+            DELETE FROM status_updates
+            WHERE id = $1 AND user_id = $2
+            RETURNING media_id
+        )
+        SELECT md.url as "media_url?"
+        FROM deleted
+        LEFT JOIN media md ON md.id = deleted.media_id
+        "#,
+        status_id,
+        claims.sub
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Status not found".into()))?;
+
+    if let Some(media_url) = deleted.media_url {
+        media_cleanup::release_media(&mut tx, &[(media_url, claims.sub)]).await?;
+    }
+
+    tx.commit().await?;
+
+    fanout::send_to_users(&state, &audience, &WebSocketMessage::StatusDeleted {
+        user_id: claims.sub,
+        status_id,
+    })
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_status_audience(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+) -> Result<Json<StatusAudienceResponse>, AppError> {
+    Ok(Json(load_status_audience(&state, claims.sub).await?))
+}
+
+pub async fn update_status_audience(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Json(req): Json<UpdateStatusAudienceRequest>,
+) -> Result<Json<StatusAudienceResponse>, AppError> {
+    let lists = [("except", &req.except), ("only", &req.only)];
+    if lists.iter().any(|(_, ids)| ids.as_ref().map_or(false, |ids| ids.len() > MAX_AUDIENCE_LIST_SIZE)) {
+        return Err(AppError::BadRequest(format!(
+            "A status audience list cannot have more than {} users",
+            MAX_AUDIENCE_LIST_SIZE
+        )));
+    }
+
+    let mut tx = state.pool.begin().await?;
+
+    if let Some(mode) = req.mode {
+        sqlx::query!(
+            r#"
+            INSERT INTO status_audience_settings (user_id, mode)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE SET mode = $2
+            "#,
+            claims.sub,
+            mode as StatusAudienceMode
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for (list, user_ids) in lists {
+        let Some(user_ids) = user_ids else {
+            continue;
+        };
+
+        sqlx::query!(
+            r#"
+            DELETE FROM status_audience_lists
+            WHERE user_id = $1 AND list = $2
+            "#,
+            claims.sub,
+            list
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO status_audience_lists (user_id, list, member_id)
+            SELECT $1, $2, member FROM UNNEST($3::uuid[]) AS member
+            WHERE member <> $1
+            ON CONFLICT DO NOTHING
+            "#,
+            claims.sub,
+            list,
+            user_ids
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(Json(load_status_audience(&state, claims.sub).await?))
+}
+
+/// Validates a reply to a status and captures what the status looks like now,
+/// for `send_message` to store alongside the reply.
+pub(crate) async fn status_reply_preview(
+    state: &AppState,
+    status_id: Uuid,
+    sender_id: Uuid,
+    receiver_id: Uuid,
+) -> Result<StatusReplyPreview, AppError> {
+    let status = visible_status(state, status_id, sender_id).await?;
+    if status.user_id != receiver_id {
+        return Err(AppError::BadRequest("Status replies must be sent to the status author".into()));
+    }
+    if status.user_id == sender_id {
+        return Err(AppError::BadRequest("Cannot reply to your own status".into()));
+    }
+
+    Ok(StatusReplyPreview {
+        status_id: Some(status.id),
+        author_id: status.user_id,
+        content: status.content.map(|c| c.chars().take(REPLY_PREVIEW_LENGTH).collect()),
+        media_url: status.media_url,
+        media_type: status.media_type,
+        background_color: status.background_color,
+    })
+}
+
+async fn load_status_audience(state: &AppState, user_id: Uuid) -> Result<StatusAudienceResponse, AppError> {
+    let mode = sqlx::query_scalar!(
+        r#"
+        SELECT mode as "mode: StatusAudienceMode" FROM status_audience_settings
+        WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_optional(&state.pool)
+    .await?
+    .unwrap_or(StatusAudienceMode::Contacts);
+
+    let rows = sqlx::query!(
+        r#"
+        SELECT list, member_id FROM status_audience_lists
+        WHERE user_id = $1
+        ORDER BY created_at
+        "#,
+        user_id
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    let mut audience = StatusAudienceResponse {
+        mode,
+        except: Vec::new(),
+        only: Vec::new(),
+    };
+    for row in rows {
+        match row.list.as_str() {
+            "except" => audience.except.push(row.member_id),
+            "only" => audience.only.push(row.member_id),
+            _ => {}
+        }
+    }
+
+    Ok(audience)
+}
+
+/// Loads an active status if the viewer is its author or one of its recipients.
+async fn visible_status(
+    state: &AppState,
+    status_id: Uuid,
+    viewer_id: Uuid,
+) -> Result<StatusUpdate, AppError> {
+    sqlx::query_as!(
+        StatusUpdate,
+        r#"
+        SELECT s.id, s.user_id as "user_id!", s.content, s.media_id, s.media_url, s.media_type,
+               s.created_at as "created_at!", s.expires_at as "expires_at!",
+               s.background_color, s.font_style
+        FROM status_updates s
+        WHERE s.id = $1
+            AND s.expires_at > NOW()
+            AND (
+                s.user_id = $2
+                OR EXISTS(SELECT 1 FROM status_recipients WHERE status_id = s.id AND user_id = $2)
+            )
+        "#,
+        status_id,
+        viewer_id
+    )
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Status not found".into()))
+}
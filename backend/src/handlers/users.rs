@@ -7,48 +7,46 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use std::sync::Arc;
 use uuid::Uuid;
-use redis::{AsyncCommands, Commands};
 
 use crate::{
     AppState,
+    auth::Claims,
     error::AppError,
     models::{
         Contact,
-        UserResponse,
-        User,
+        user::{User, UserResponse},
+    },
+    services::{
+        cache::{CacheKey, CacheTag},
+        privacy::PrivacyRules,
     },
 };
 
 pub async fn get_user(
     State(state): State<Arc<AppState>>,
+    claims: Claims,
     Path(user_id): Path<Uuid>,
 ) -> Result<Json<UserResponse>, AppError> {
-    let cache_key = format!("user:{}", user_id);
-    
-    if let Ok(Some(cached)) = state.redis.get::<_, Option<String>>(&cache_key) {
-        if let Ok(user) = serde_json::from_str::<UserResponse>(&cached) {
-            return Ok(Json(user));
-        }
-    }
-
-    let user = sqlx::query_as!(
-        User,
-        r#"
-        SELECT * FROM users
-        WHERE id = $1
-        "#,
-        user_id
-    )
-    .fetch_one(&state.pool)
+    // The cache holds the unfiltered profile, privacy is applied per viewer
+    let mut user = state.cache.get_or_load(CacheKey::User(user_id), || async {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            SELECT * FROM users
+            WHERE id = $1
+            "#,
+            user_id
+        )
+        .fetch_one(&state.pool)
+        .await?;
+
+        Ok(UserResponse::from(user))
+    })
     .await?;
 
-    let user_response = UserResponse::from(user);
-    
-    if let Ok(json) = serde_json::to_string(&user_response) {
-        let _ = state.redis.set_ex(&cache_key, &json, 3600); // Cache for 1 hour
-    }
+    PrivacyRules::load(&state.pool, &[user_id], &[claims.sub]).await?.apply(claims.sub, &mut user);
 
-    Ok(Json(user_response))
+    Ok(Json(user))
 }
 
 pub async fn update_user(
@@ -72,73 +70,68 @@ pub async fn update_user(
     .fetch_one(&state.pool)
     .await?;
 
-    let user_response = UserResponse::from(user);
-    let cache_key = format!("user:{}", user_id);
-    
-    if let Ok(json) = serde_json::to_string(&user_response) {
-        let _ = state.redis.set_ex(&cache_key, &json, 3600); // Cache for 1 hour
-    }
+    // Drops the profile, the directory and any group member list showing this user
+    state.cache.invalidate(&[CacheTag::User(user_id), CacheTag::UserDirectory]).await;
 
-    Ok(Json(user_response))
+    Ok(Json(UserResponse::from(user)))
 }
 
 pub async fn get_users(
     State(state): State<Arc<AppState>>,
+    claims: Claims,
 ) -> Result<Json<Vec<UserResponse>>, AppError> {
-    let cache_key = "users:all";
-    
-    if let Ok(Some(cached)) = state.redis.get::<_, Option<String>>(&cache_key) {
-        if let Ok(users) = serde_json::from_str::<Vec<UserResponse>>(&cached) {
-            return Ok(Json(users));
-        }
-    }
-
-    let users = sqlx::query_as!(
-        User,
-        r#"
-        SELECT * FROM users
-        ORDER BY created_at DESC
-        "#,
-    )
-    .fetch_all(&state.pool)
+    let users = state.cache.get_or_load(CacheKey::UserDirectory, || async {
+        let users = sqlx::query_as!(
+            User,
+            r#"
+            SELECT * FROM users
+            WHERE NOT is_placeholder
+            ORDER BY created_at DESC
+            "#,
+        )
+        .fetch_all(&state.pool)
+        .await?;
+
+        Ok(users.into_iter().map(UserResponse::from).collect::<Vec<_>>())
+    })
     .await?;
 
-    let user_responses: Vec<UserResponse> = users.into_iter().map(UserResponse::from).collect();
-    
-    if let Ok(json) = serde_json::to_string(&user_responses) {
-        let _ = state.redis.set_ex(&cache_key, &json, 300); // Cache for 5 minutes
-    }
+    Ok(Json(apply_privacy(&state, claims.sub, users).await?))
+}
 
-    Ok(Json(user_responses))
+async fn apply_privacy(
+    state: &AppState,
+    viewer: Uuid,
+    mut users: Vec<UserResponse>,
+) -> Result<Vec<UserResponse>, AppError> {
+    let owners: Vec<Uuid> = users.iter().map(|u| u.id).collect();
+    let rules = PrivacyRules::load(&state.pool, &owners, &[viewer]).await?;
+    for user in &mut users {
+        rules.apply(viewer, user);
+    }
+    Ok(users)
 }
 
 pub async fn get_contacts(
     State(state): State<Arc<AppState>>,
     Path(user_id): Path<Uuid>,
 ) -> Result<Json<Vec<Contact>>, AppError> {
-    let cache_key = format!("user:{}:contacts", user_id);
-    
-    if let Ok(Some(cached)) = state.redis.get::<_, Option<String>>(&cache_key) {
-        if let Ok(contacts) = serde_json::from_str::<Vec<Contact>>(&cached) {
-            return Ok(Json(contacts));
-        }
-    }
-
-    let contacts = sqlx::query_as!(
-        Contact,
-        r#"
-        SELECT * FROM contacts
-        WHERE user_id = $1
-        ORDER BY created_at DESC
-        "#,
-        user_id
-    )
-    .fetch_all(&state.pool)
+    let contacts = state.cache.get_or_load(CacheKey::Contacts(user_id), || async {
+        let contacts = sqlx::query_as!(
+            Contact,
+            r#"
+            SELECT * FROM contacts
+            WHERE user_id = $1
+            ORDER BY created_at DESC
+            "#,
+            user_id
+        )
+        .fetch_all(&state.pool)
+        .await?;
+
+        Ok(contacts)
+    })
     .await?;
-    
-    if let Ok(json) = serde_json::to_string(&contacts) {
-        let _ = state.redis.set_ex(&cache_key, &json, 300); // Cache for 5 minutes
-    }
 
     Ok(Json(contacts))
 }
@@ -161,8 +154,7 @@ pub async fn add_contact(
     .fetch_one(&state.pool)
     .await?;
 
-    let cache_key = format!("user:{}:contacts", user_id);
-    let _ = state.redis.del(&cache_key);
+    state.cache.invalidate(&[CacheTag::Contacts(user_id)]).await;
 
     Ok(Json(contact))
 }
@@ -182,8 +174,7 @@ pub async fn remove_contact(
     .execute(&state.pool)
     .await?;
 
-    let cache_key = format!("user:{}:contacts", user_id);
-    let _ = state.redis.del(&cache_key);
+    state.cache.invalidate(&[CacheTag::Contacts(user_id)]).await;
 
     Ok(StatusCode::NO_CONTENT)
-}
\ No newline at end of file
+}
@@ -1,11 +1,12 @@
 use axum::{
+    extract::DefaultBodyLimit,
     routing::{get, post, put, delete},
     Router,
 };
 use redis::Client as RedisClient;
 use sqlx::PgPool;
 use std::sync::Arc;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, mpsc};
 use tower_http::cors::CorsLayer;
 
 mod auth;
@@ -17,9 +18,15 @@ pub mod middleware;
 pub mod services;
 pub mod config;
 pub mod database;
+pub mod metrics;
 
 pub use auth::AuthUser;
 pub use error::AppError;
+use config::{Config, MessagingConfig};
+use services::cache::CacheService;
+use services::link_preview::PreviewJob;
+use services::media_storage::{CloudinaryStorage, MediaStorage};
+use services::presence::PresenceService;
 use websocket::WebSocketManager;
 use websocket::handler::ws_handler;
 
@@ -29,16 +36,40 @@ pub struct AppState {
     pub redis: RedisClient,
     pub ws_tx: broadcast::Sender<String>,
     pub ws_manager: Arc<WebSocketManager>,
+    pub presence: Arc<PresenceService>,
+    pub cache: Arc<CacheService>,
+    pub config: Config,
+    pub media_storage: Arc<dyn MediaStorage>,
+    pub messaging: MessagingConfig,
+    pub link_previews: mpsc::Sender<PreviewJob>,
 }
 
 pub fn create_app(pool: PgPool, redis: RedisClient) -> Router<Arc<AppState>> {
     let (ws_tx, _) = broadcast::channel(100);
+    let (link_previews, preview_jobs) = mpsc::channel(services::link_preview::QUEUE_SIZE);
+    let ws_manager = Arc::new(WebSocketManager::new());
+    let media_storage: Arc<dyn MediaStorage> = Arc::new(CloudinaryStorage::from_env());
+    websocket::fanout::spawn_relay(redis.clone(), ws_manager.clone());
+    services::status_expiry::spawn_status_expiry_worker(pool.clone(), media_storage.clone());
+
     let state = Arc::new(AppState {
         pool,
+        presence: Arc::new(PresenceService::new(redis.clone())),
+        cache: Arc::new(CacheService::new(&redis).expect("Failed to create Redis pool")),
         redis,
+        config: Config::from_env().expect("Failed to load configuration"),
         ws_tx,
-        ws_manager: Arc::new(WebSocketManager::new()),
+        ws_manager,
+        media_storage,
+        messaging: MessagingConfig::from_env(),
+        link_previews,
     });
+    services::scheduled_messages::spawn_scheduled_message_dispatcher(state.clone());
+    services::message_expiry::spawn_message_expiry_worker(state.clone());
+    services::link_preview::spawn_link_preview_worker(state.clone(), preview_jobs);
+    services::idempotency::spawn_idempotency_key_pruner(state.clone());
+    services::read_marks::spawn_read_mark_pruner(state.clone());
+    services::chat_export::spawn_chat_export_worker(state.clone());
 
     Router::new()
         .route("/auth/register", post(handlers::auth::register))
@@ -48,20 +79,70 @@ pub fn create_app(pool: PgPool, redis: RedisClient) -> Router<Arc<AppState>> {
         .route("/users", get(handlers::users::get_users))
         .route("/users/:id", get(handlers::users::get_user))
         .route("/users/:id", put(handlers::users::update_user))
+        .route("/users/:id/timer", get(handlers::disappearing::get_direct_timer))
+        .route("/users/:id/timer", put(handlers::disappearing::update_direct_timer))
+        .route("/users/:id/pins", get(handlers::pins::get_direct_pins))
         .route("/users/:id/contacts", get(handlers::users::get_contacts))
         .route("/users/:id/contacts/:contact_id", post(handlers::users::add_contact))
         .route("/users/:id/contacts/:contact_id", delete(handlers::users::remove_contact))
+        .route("/presence", get(handlers::presence::get_presence))
+        .route("/privacy", get(handlers::privacy::get_privacy))
+        .route("/privacy", put(handlers::privacy::update_privacy))
+        .route("/statuses", post(handlers::status::create_status))
+        .route("/statuses", get(handlers::status::get_status_feed))
+        .route("/statuses/me", get(handlers::status::get_my_statuses))
+        .route("/statuses/audience", get(handlers::status::get_status_audience))
+        .route("/statuses/audience", put(handlers::status::update_status_audience))
+        .route("/statuses/:id", delete(handlers::status::delete_status))
+        .route("/statuses/:id/view", post(handlers::status::view_status))
+        .route("/statuses/:id/viewers", get(handlers::status::get_status_viewers))
+        .route("/mentions", get(handlers::mentions::get_mentions))
+        .route("/mentions/unread", get(handlers::mentions::get_unread_mentions))
+        .route("/mentions/:chat_id/read", post(handlers::mentions::mark_mentions_read))
+        .route("/conversations", get(handlers::conversations::get_conversations))
+        .route("/conversations/:chat_id/archive", post(handlers::conversations::archive_conversation))
+        .route("/conversations/:chat_id/archive", delete(handlers::conversations::unarchive_conversation))
+        .route("/conversations/:chat_id/mute", post(handlers::conversations::mute_conversation))
+        .route("/conversations/:chat_id/mute", delete(handlers::conversations::unmute_conversation))
+        .route("/conversations/:chat_id/pin", post(handlers::conversations::pin_conversation))
+        .route("/conversations/:chat_id/pin", delete(handlers::conversations::unpin_conversation))
+        .route("/conversations/:chat_id/exports", post(handlers::exports::create_export))
+        .route(
+            "/conversations/:chat_id/imports",
+            post(handlers::imports::import_chat).layer(DefaultBodyLimit::max(handlers::imports::MAX_IMPORT_BYTES)),
+        )
+        .route("/exports", get(handlers::exports::get_exports))
+        .route("/exports/:id", get(handlers::exports::get_export))
+        .route("/exports/:id/download", get(handlers::exports::download_export))
         .route("/ws", get(ws_handler))
         .route("/media", post(handlers::media::upload_media))
         .route("/media/:id", delete(handlers::media::delete_media))
         .route("/groups", post(handlers::groups::create_group))
         .route("/groups/:id", get(handlers::groups::get_group))
         .route("/groups/:id", put(handlers::groups::update_group))
+        .route("/groups/:id/timer", get(handlers::disappearing::get_group_timer))
+        .route("/groups/:id/timer", put(handlers::disappearing::update_group_timer))
+        .route("/groups/:id/pins", get(handlers::pins::get_group_pins))
         .route("/groups/:id/members", get(handlers::groups::get_group_members))
         .route("/groups/:id/members/:user_id", post(handlers::groups::add_group_member))
         .route("/groups/:id/members/:user_id", delete(handlers::groups::remove_group_member))
+        .route("/messages/starred", get(handlers::starred::get_starred_messages))
+        .route("/messages/search", get(handlers::search::search_messages))
+        .route("/messages/delivered", post(handlers::receipts::ack_delivered))
+        .route("/messages/scheduled", get(handlers::messages::get_scheduled_messages))
+        .route("/messages/scheduled/:id", put(handlers::messages::update_scheduled_message))
+        .route("/messages/scheduled/:id", delete(handlers::messages::cancel_scheduled_message))
         .route("/messages/:receiver_id", post(handlers::messages::send_message))
         .route("/messages/:receiver_id", get(handlers::messages::get_messages))
+        .route("/messages/:id/thread", get(handlers::messages::get_message_thread))
+        .route("/messages/:id/history", get(handlers::messages::get_message_history))
+        .route("/messages/:id/status", get(handlers::receipts::get_message_status))
+        .route("/messages/:id/read", post(handlers::message_actions::mark_as_read))
+        .route("/messages/:id/receipts", get(handlers::message_actions::get_read_receipts))
+        .route("/messages/:id/pin", post(handlers::pins::pin_message))
+        .route("/messages/:id/pin", delete(handlers::pins::unpin_message))
+        .route("/messages/:id/star", post(handlers::starred::star_message))
+        .route("/messages/:id/star", delete(handlers::starred::unstar_message))
         .route("/messages/:id", put(handlers::messages::update_message))
         .route("/messages/:id", delete(handlers::messages::delete_message))
         .route("/groups/:id/messages", post(handlers::messages::send_group_message))
@@ -20,14 +20,6 @@ use crate::{
     auth::AuthUser,
     handlers::{
         auth::{login, register},
-        chat::{
-            create_chat, delete_message, get_chat, get_chats, get_messages,
-            send_message,
-        },
-        group_chat::{
-            add_member, create_group, get_group, get_groups, get_members,
-            remove_member, update_member_role,
-        },
         media::{delete_media, upload_media},
         message_actions::{
             forward_message, get_read_receipts, mark_as_read,
@@ -1,6 +1,6 @@
 use lazy_static::lazy_static;
 use prometheus::{
-    Counter, Gauge, Histogram, HistogramOpts, IntCounter, IntGauge, Registry,
+    Counter, Gauge, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, Registry,
     opts,
 };
 use std::sync::Arc;
@@ -48,6 +48,22 @@ lazy_static! {
         )
     ).unwrap();
 
+    // Cache metrics
+    pub static ref CACHE_HITS: IntCounterVec = IntCounterVec::new(
+        opts!("cache_hits_total", "Total number of cache hits"),
+        &["kind"]
+    ).unwrap();
+
+    pub static ref CACHE_MISSES: IntCounterVec = IntCounterVec::new(
+        opts!("cache_misses_total", "Total number of cache misses"),
+        &["kind"]
+    ).unwrap();
+
+    pub static ref CACHE_INVALIDATIONS: IntCounter = IntCounter::new(
+        "cache_invalidations_total",
+        "Total number of cache tag invalidations"
+    ).unwrap();
+
     // Message metrics
     pub static ref MESSAGES_SENT: IntCounter = IntCounter::new(
         "messages_sent_total",
@@ -59,6 +75,63 @@ lazy_static! {
         "Total number of messages delivered"
     ).unwrap();
 
+    // Status expiry metrics
+    pub static ref STATUSES_PURGED: IntCounter = IntCounter::new(
+        "statuses_purged_total",
+        "Total number of expired statuses deleted"
+    ).unwrap();
+
+    pub static ref STATUS_VIEWS_PURGED: IntCounter = IntCounter::new(
+        "status_views_purged_total",
+        "Total number of status views deleted with expired statuses"
+    ).unwrap();
+
+    pub static ref STATUS_EXPIRY_RUN_DURATION: Histogram = Histogram::with_opts(
+        HistogramOpts::new(
+            "status_expiry_run_duration_seconds",
+            "Status expiry run duration in seconds"
+        )
+    ).unwrap();
+
+    // Scheduled message metrics
+    pub static ref SCHEDULED_MESSAGES_SENT: IntCounter = IntCounter::new(
+        "scheduled_messages_sent_total",
+        "Total number of scheduled messages sent"
+    ).unwrap();
+
+    pub static ref SCHEDULED_MESSAGES_FAILED: IntCounter = IntCounter::new(
+        "scheduled_messages_failed_total",
+        "Total number of scheduled messages that could not be sent"
+    ).unwrap();
+
+    // Disappearing message metrics
+    pub static ref MESSAGES_EXPIRED: IntCounter = IntCounter::new(
+        "messages_expired_total",
+        "Total number of disappearing messages purged"
+    ).unwrap();
+
+    // Media metrics
+    pub static ref MEDIA_DELETED: IntCounter = IntCounter::new(
+        "media_deleted_total",
+        "Total number of media objects deleted from storage"
+    ).unwrap();
+
+    pub static ref MEDIA_DELETE_FAILURES: IntCounter = IntCounter::new(
+        "media_delete_failures_total",
+        "Total number of attempts to delete media from storage that failed"
+    ).unwrap();
+
+    // Link preview metrics
+    pub static ref LINK_PREVIEWS_FETCHED: IntCounter = IntCounter::new(
+        "link_previews_fetched_total",
+        "Total number of link previews fetched"
+    ).unwrap();
+
+    pub static ref LINK_PREVIEW_FAILURES: IntCounter = IntCounter::new(
+        "link_preview_failures_total",
+        "Total number of links that could not be previewed"
+    ).unwrap();
+
     // Error metrics
     pub static ref ERROR_COUNTER: IntCounter = IntCounter::new(
         "error_total",
@@ -73,8 +146,21 @@ pub fn register_metrics() {
     REGISTRY.register(Box::new(DB_QUERY_DURATION.clone())).unwrap();
     REGISTRY.register(Box::new(REDIS_CONNECTIONS.clone())).unwrap();
     REGISTRY.register(Box::new(REDIS_OPERATION_DURATION.clone())).unwrap();
+    REGISTRY.register(Box::new(CACHE_HITS.clone())).unwrap();
+    REGISTRY.register(Box::new(CACHE_MISSES.clone())).unwrap();
+    REGISTRY.register(Box::new(CACHE_INVALIDATIONS.clone())).unwrap();
     REGISTRY.register(Box::new(MESSAGES_SENT.clone())).unwrap();
     REGISTRY.register(Box::new(MESSAGES_DELIVERED.clone())).unwrap();
+    REGISTRY.register(Box::new(STATUSES_PURGED.clone())).unwrap();
+    REGISTRY.register(Box::new(STATUS_VIEWS_PURGED.clone())).unwrap();
+    REGISTRY.register(Box::new(STATUS_EXPIRY_RUN_DURATION.clone())).unwrap();
+    REGISTRY.register(Box::new(SCHEDULED_MESSAGES_SENT.clone())).unwrap();
+    REGISTRY.register(Box::new(SCHEDULED_MESSAGES_FAILED.clone())).unwrap();
+    REGISTRY.register(Box::new(MESSAGES_EXPIRED.clone())).unwrap();
+    REGISTRY.register(Box::new(MEDIA_DELETED.clone())).unwrap();
+    REGISTRY.register(Box::new(MEDIA_DELETE_FAILURES.clone())).unwrap();
+    REGISTRY.register(Box::new(LINK_PREVIEWS_FETCHED.clone())).unwrap();
+    REGISTRY.register(Box::new(LINK_PREVIEW_FAILURES.clone())).unwrap();
     REGISTRY.register(Box::new(ERROR_COUNTER.clone())).unwrap();
 }
 
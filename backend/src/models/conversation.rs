@@ -0,0 +1,119 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::models::{
+    message::{ChatContext, QuotedMessage},
+    pagination::{Cursor, Paginated},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "conversation_kind", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ConversationKind {
+    Direct,
+    Group,
+}
+
+/// The direct chat or group every message belongs to. A group's conversation
+/// has the group's id; clients address a direct chat by the other user's id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Conversation {
+    pub id: Uuid,
+    pub kind: ConversationKind,
+}
+
+impl Conversation {
+    pub fn is_group(&self) -> bool {
+        self.kind == ConversationKind::Group
+    }
+}
+
+/// A direct chat or group in my conversation list.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConversationSummary {
+    pub chat: ChatContext,
+    /// When the newest message was sent, or when I joined a quiet group
+    pub last_activity_at: DateTime<Utc>,
+    /// Newest message I can still see; deleted messages show as a tombstone
+    pub last_message: Option<LastMessage>,
+    /// Messages from others after my read watermark
+    pub unread_count: i64,
+    /// Of those, the ones that mention me
+    pub unread_mention_count: i64,
+    pub is_pinned: bool,
+    pub is_archived: bool,
+    pub is_muted: bool,
+    /// When a mute ends; absent for a mute that lasts until undone
+    pub muted_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LastMessage {
+    #[serde(flatten)]
+    pub message: QuotedMessage,
+    pub is_system: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Paginated for ConversationSummary {
+    fn cursor(&self) -> Cursor {
+        Cursor::new(self.last_activity_at, self.chat.id)
+    }
+}
+
+/// A window of the conversation list, most recently active first. Pinned
+/// conversations lead the first page and aren't repeated after it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConversationPage {
+    pub conversations: Vec<ConversationSummary>,
+    /// Pass as `before` to load the next page
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+}
+
+/// How long to mute a conversation for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MuteDuration {
+    #[serde(rename = "8h")]
+    EightHours,
+    #[serde(rename = "1w")]
+    OneWeek,
+    #[serde(rename = "forever")]
+    Forever,
+}
+
+impl MuteDuration {
+    /// When a mute starting at `from` ends, if it does.
+    pub fn until(self, from: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            MuteDuration::EightHours => Some(from + Duration::hours(8)),
+            MuteDuration::OneWeek => Some(from + Duration::weeks(1)),
+            MuteDuration::Forever => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MuteConversationRequest {
+    pub duration: MuteDuration,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ArchiveConversationRequest {
+    /// Stay archived when new messages arrive
+    #[serde(default)]
+    pub keep_archived: bool,
+}
+
+/// My archive, mute and pin settings for a conversation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationState {
+    pub chat_id: Uuid,
+    pub is_archived: bool,
+    pub keep_archived: bool,
+    pub is_muted: bool,
+    /// When a mute ends; absent for a mute that lasts until undone
+    pub muted_until: Option<DateTime<Utc>>,
+    pub is_pinned: bool,
+}
@@ -0,0 +1,79 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "chat_export_format", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Json,
+    /// WhatsApp-style plain text
+    Txt,
+    /// A single page with its styles inlined
+    Html,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Txt => "txt",
+            ExportFormat::Html => "html",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "application/json",
+            ExportFormat::Txt => "text/plain; charset=utf-8",
+            ExportFormat::Html => "text/html; charset=utf-8",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "chat_export_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ExportStatus {
+    Pending,
+    Running,
+    Completed,
+    /// See `last_error`
+    Failed,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ChatExport {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub chat_id: Uuid,
+    pub format: ExportFormat,
+    pub include_media: bool,
+    pub status: ExportStatus,
+    pub file_name: Option<String>,
+    pub file_size: Option<i64>,
+    pub message_count: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    /// When the finished file is deleted
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateExportRequest {
+    pub format: ExportFormat,
+    /// Zip the export together with the media it references
+    #[serde(default)]
+    pub include_media: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatExportResponse {
+    #[serde(flatten)]
+    pub export: ChatExport,
+    /// Signed link to the finished file; valid for an hour, fetch the export
+    /// again for a fresh one
+    pub download_url: Option<String>,
+}
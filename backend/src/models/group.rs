@@ -14,6 +14,14 @@ pub struct Group {
     pub updated_at: DateTime<Utc>,
     pub is_private: bool,
     pub max_members: i32,
+    /// Seconds new messages live for, if disappearing messages are on
+    pub disappearing_seconds: Option<i32>,
+    /// Who can pin messages
+    #[serde(default)]
+    pub pin_permission: GroupPermission,
+    /// Who can mention @all and @admins
+    #[serde(default)]
+    pub mention_all_permission: GroupPermission,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -23,7 +31,6 @@ pub struct GroupMember {
     pub user_id: Uuid,
     pub role: GroupRole,
     pub joined_at: DateTime<Utc>,
-    pub last_read_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, sqlx::Type)]
@@ -34,6 +41,16 @@ pub enum GroupRole {
     Member,
 }
 
+/// Who in a group may use a feature
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "group_permission", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum GroupPermission {
+    #[default]
+    Admins,
+    Members,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateGroupRequest {
     pub name: String,
@@ -51,6 +68,8 @@ pub struct UpdateGroupRequest {
     pub avatar_url: Option<String>,
     pub is_private: Option<bool>,
     pub max_members: Option<i32>,
+    pub pin_permission: Option<GroupPermission>,
+    pub mention_all_permission: Option<GroupPermission>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -64,6 +83,8 @@ pub struct GroupResponse {
     pub updated_at: DateTime<Utc>,
     pub is_private: bool,
     pub max_members: i32,
+    pub pin_permission: GroupPermission,
+    pub mention_all_permission: GroupPermission,
     pub member_count: i64,
     pub role: GroupRole,
 }
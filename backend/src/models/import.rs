@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Order of day and month in an export's dates. Years may come first too,
+/// which is recognised without a hint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DateOrder {
+    /// 31/12/2020
+    Dmy,
+    /// 12/31/2020
+    Mdy,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportChatRequest {
+    /// Contents of the exported `.txt` file
+    pub text: String,
+    /// Sender names in the export mapped to members of the chat; other names
+    /// are matched against members' display names and usernames
+    #[serde(default)]
+    pub participants: HashMap<String, Uuid>,
+    /// Detected from the dates themselves when omitted
+    pub date_order: Option<DateOrder>,
+    /// Offset from UTC of the phone that made the export
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportParticipant {
+    pub name: String,
+    pub user_id: Uuid,
+    /// Created for this import, or an earlier one, as nobody here matched
+    pub is_placeholder: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportResult {
+    pub message_count: usize,
+    pub participants: Vec<ImportParticipant>,
+}
@@ -1,29 +1,169 @@
 use serde::{Deserialize, Serialize};
-use sqlx::types::Uuid;
+use sqlx::types::{Json as SqlJson, Uuid};
 use chrono::{DateTime, Utc};
+use crate::models::{
+    pagination::{Cursor, Paginated},
+    status::StatusReplyPreview,
+};
 
-#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct Message {
     pub id: Uuid,
     pub sender_id: Uuid,
-    pub receiver_id: Uuid,  // Can be either a user_id or group_id
+    /// Where the message was sent: the group, or the other user in a direct chat
+    pub receiver_id: Uuid,
+    /// The direct chat or group the message belongs to
+    #[serde(default)]
+    pub conversation_id: Uuid,
     pub content: String,
     pub media_url: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: Option<DateTime<Utc>>,
     pub is_edited: bool,
     pub is_deleted: bool,
+    pub reply_to_id: Option<Uuid>,
+    /// Events such as timer changes, shown in the chat but not sent by a user
+    #[serde(default)]
+    pub is_system: bool,
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub formatting: SqlJson<Vec<FormattingEntity>>,
+    /// Brought in from a chat export rather than sent here
+    #[serde(default)]
+    pub is_imported: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateMessageRequest {
     pub content: String,
     pub media_url: Option<String>,
+    /// Message in the same conversation this one replies to
+    pub reply_to_id: Option<Uuid>,
+    /// Status of the receiver this message replies to
+    pub status_id: Option<Uuid>,
+    /// Schedule the message instead of sending it now
+    pub send_at: Option<DateTime<Utc>>,
+    /// Mentions in `content`; parsed from `@username`, `@all` and `@admins` when omitted
+    pub mentions: Option<Vec<MentionEntity>>,
+    /// Bold, italic, links etc. within `content`
+    #[serde(default)]
+    pub formatting: Vec<FormattingEntity>,
+    /// Generated by the client so retries don't send the message twice; the
+    /// `Idempotency-Key` header works too
+    pub client_message_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FormattingKind {
+    Bold,
+    Italic,
+    Code,
+    /// Preformatted block
+    Pre,
+    Strikethrough,
+    Spoiler,
+    Link,
+}
+
+/// A formatted span of message content, counted in UTF-16 code units like
+/// mentions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FormattingEntity {
+    #[serde(rename = "type")]
+    pub kind: FormattingKind,
+    pub offset: i32,
+    pub length: i32,
+    /// Target of `link` entities
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "mention_kind", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum MentionKind {
+    #[default]
+    User,
+    /// Everyone in the group
+    All,
+    /// The group's owner and admins
+    Admins,
+}
+
+/// A mention within message content. Offsets and lengths count UTF-16 code
+/// units, as most clients index strings that way.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MentionEntity {
+    #[serde(default)]
+    pub kind: MentionKind,
+    /// Mentioned user, for `user` mentions
+    pub user_id: Option<Uuid>,
+    pub offset: i32,
+    pub length: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "scheduled_message_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduledMessageStatus {
+    Pending,
+    /// Could not be sent, e.g. because the sender left the group
+    Failed,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ScheduledMessage {
+    pub id: Uuid,
+    pub sender_id: Uuid,
+    pub receiver_id: Uuid,  // Can be either a user_id or group_id
+    pub is_group: bool,
+    pub content: String,
+    pub media_url: Option<String>,
+    pub reply_to_id: Option<Uuid>,
+    pub send_at: DateTime<Utc>,
+    pub status: ScheduledMessageStatus,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub formatting: SqlJson<Vec<FormattingEntity>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateScheduledMessageRequest {
+    pub content: Option<String>,
+    /// Formatting of the new content; cleared when the content changes without it
+    pub formatting: Option<Vec<FormattingEntity>>,
+    pub send_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DisappearingTimerResponse {
+    /// How long new messages live for, or null when they don't disappear
+    pub seconds: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateDisappearingTimerRequest {
+    pub seconds: Option<i32>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UpdateMessageRequest {
     pub content: String,
+    /// Mentions in the new content; parsed from it when omitted
+    pub mentions: Option<Vec<MentionEntity>>,
+    /// Formatting of the new content; the old formatting is dropped
+    #[serde(default)]
+    pub formatting: Vec<FormattingEntity>,
+}
+
+/// One version of a message's content.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessageVersion {
+    pub content: String,
+    pub written_at: DateTime<Utc>,
+    pub is_current: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -37,8 +177,19 @@ pub struct MessageResponse {
     pub updated_at: Option<DateTime<Utc>>,
     pub is_edited: bool,
     pub is_deleted: bool,
+    pub is_system: bool,
+    /// Brought in from a chat export; sent at `created_at` in the original app
+    pub is_imported: bool,
+    pub expires_at: Option<DateTime<Utc>>,
     pub sender_name: String,
     pub sender_avatar: Option<String>,
+    pub reply_to: Option<QuotedMessage>,
+    pub reply_count: i64,
+    pub mentions: Vec<MentionEntity>,
+    pub formatting: Vec<FormattingEntity>,
+    /// Filled in shortly after sending, see `LinkPreviewReady`
+    pub link_preview: Option<LinkPreview>,
+    pub status_reply: Option<StatusReplyPreview>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -52,8 +203,264 @@ pub struct GroupMessageResponse {
     pub updated_at: Option<DateTime<Utc>>,
     pub is_edited: bool,
     pub is_deleted: bool,
+    pub is_system: bool,
+    /// Brought in from a chat export; sent at `created_at` in the original app
+    pub is_imported: bool,
+    pub expires_at: Option<DateTime<Utc>>,
     pub sender_name: String,
     pub sender_avatar: Option<String>,
     pub group_name: String,
     pub group_avatar: Option<String>,
-}
\ No newline at end of file
+    pub reply_to: Option<QuotedMessage>,
+    pub reply_count: i64,
+    pub mentions: Vec<MentionEntity>,
+    pub formatting: Vec<FormattingEntity>,
+    /// Filled in shortly after sending, see `LinkPreviewReady`
+    pub link_preview: Option<LinkPreview>,
+}
+
+/// Compact view of the message being replied to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuotedMessage {
+    pub id: Uuid,
+    pub sender_id: Uuid,
+    pub sender_name: String,
+    /// Start of the original content; empty once the original is deleted
+    pub content: String,
+    pub has_media: bool,
+    pub is_deleted: bool,
+}
+
+impl QuotedMessage {
+    const PREVIEW_LENGTH: usize = 100;
+
+    pub fn new(
+        id: Uuid,
+        sender_id: Uuid,
+        sender_name: String,
+        content: &str,
+        media_url: Option<&str>,
+        is_deleted: bool,
+    ) -> Self {
+        Self {
+            id,
+            sender_id,
+            sender_name,
+            content: if is_deleted {
+                String::new()
+            } else {
+                content.chars().take(Self::PREVIEW_LENGTH).collect()
+            },
+            has_media: !is_deleted && media_url.is_some(),
+            is_deleted,
+        }
+    }
+
+    /// Builds the quote from the nullable columns of a LEFT JOIN on the original.
+    pub fn from_columns(
+        id: Option<Uuid>,
+        sender_id: Option<Uuid>,
+        sender_name: Option<String>,
+        content: Option<String>,
+        media_url: Option<String>,
+        is_deleted: Option<bool>,
+    ) -> Option<Self> {
+        Some(Self::new(
+            id?,
+            sender_id?,
+            sender_name?,
+            content.as_deref().unwrap_or_default(),
+            media_url.as_deref(),
+            is_deleted?,
+        ))
+    }
+}
+
+/// What a link in a message points to, from the page's OpenGraph or Twitter
+/// card tags.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinkPreview {
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    pub site_name: Option<String>,
+}
+
+impl LinkPreview {
+    /// Builds the preview from the nullable columns of a LEFT JOIN on `link_previews`.
+    pub fn from_columns(
+        url: Option<String>,
+        title: Option<String>,
+        description: Option<String>,
+        image_url: Option<String>,
+        site_name: Option<String>,
+    ) -> Option<Self> {
+        Some(Self {
+            url: url?,
+            title,
+            description,
+            image_url,
+            site_name,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PinnedMessageResponse {
+    pub message: QuotedMessage,
+    pub pinned_by: Uuid,
+    pub pinned_at: DateTime<Utc>,
+}
+
+/// A message I starred, with enough context to show it outside its chat.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StarredMessageResponse {
+    pub id: Uuid,
+    pub content: String,
+    pub media_url: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub is_edited: bool,
+    pub sender_id: Uuid,
+    pub sender_name: String,
+    pub sender_avatar: Option<String>,
+    pub chat: ChatContext,
+    pub starred_at: DateTime<Utc>,
+}
+
+/// A message matching a search, with enough context to show it outside its chat.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchResultResponse {
+    pub id: Uuid,
+    pub content: String,
+    pub media_url: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub is_edited: bool,
+    pub sender_id: Uuid,
+    pub sender_name: String,
+    pub sender_avatar: Option<String>,
+    pub chat: ChatContext,
+    pub formatting: Vec<FormattingEntity>,
+}
+
+/// The chat a message belongs to: the group, or the other side of a direct chat.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatContext {
+    pub id: Uuid,
+    pub name: String,
+    pub avatar_url: Option<String>,
+    pub is_group: bool,
+}
+
+/// A message that mentions me, directly or through `@all` or `@admins`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MentionResponse {
+    pub id: Uuid,
+    pub content: String,
+    pub media_url: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub sender_id: Uuid,
+    pub sender_name: String,
+    pub sender_avatar: Option<String>,
+    pub chat: ChatContext,
+    pub mentions: Vec<MentionEntity>,
+    pub is_read: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UnreadMentionCount {
+    /// Group, or the other side of a direct chat
+    pub chat_id: Uuid,
+    pub is_group: bool,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    /// Stored, but not on any recipient's device yet
+    Sent,
+    /// On a device of every recipient
+    Delivered,
+    /// Read by every recipient
+    Read,
+}
+
+/// How far a message has got, as its sender sees it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageStatus {
+    pub message_id: Uuid,
+    pub status: DeliveryStatus,
+    /// 1 in direct chats; in groups, the members when it was sent other than the sender
+    pub recipient_count: i64,
+    pub delivered_count: i64,
+    pub read_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecipientStatus {
+    pub user_id: Uuid,
+    pub display_name: String,
+    pub delivered_at: Option<DateTime<Utc>>,
+    /// When they last read the chat up to or past the message; left out when
+    /// the recipient doesn't share read receipts with me
+    pub read_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessageStatusResponse {
+    #[serde(flatten)]
+    pub status: MessageStatus,
+    pub recipients: Vec<RecipientStatus>,
+}
+
+/// Someone who has read a message, derived from their read watermark.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadReceipt {
+    pub user_id: Uuid,
+    pub display_name: String,
+    /// When they last read the chat up to or past the message
+    pub read_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AckDeliveryRequest {
+    pub message_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessageThreadResponse {
+    pub root: MessageResponse,
+    /// Direct and nested replies, oldest first
+    pub replies: Vec<MessageResponse>,
+}
+
+impl Paginated for MessageResponse {
+    fn cursor(&self) -> Cursor {
+        Cursor::new(self.created_at, self.id)
+    }
+}
+
+impl Paginated for StarredMessageResponse {
+    fn cursor(&self) -> Cursor {
+        Cursor::new(self.starred_at, self.id)
+    }
+}
+
+impl Paginated for SearchResultResponse {
+    fn cursor(&self) -> Cursor {
+        Cursor::new(self.created_at, self.id)
+    }
+}
+
+impl Paginated for MentionResponse {
+    fn cursor(&self) -> Cursor {
+        Cursor::new(self.created_at, self.id)
+    }
+}
+
+impl Paginated for GroupMessageResponse {
+    fn cursor(&self) -> Cursor {
+        Cursor::new(self.created_at, self.id)
+    }
+}
@@ -2,11 +2,16 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
-use sqlx::types::JsonValue;
 
 pub mod message;
+pub mod pagination;
 pub mod user;
 pub mod group;
+pub mod privacy;
+pub mod status;
+pub mod conversation;
+pub mod export;
+pub mod import;
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct User {
@@ -86,19 +91,6 @@ pub struct UpdateProfileRequest {
     pub status: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CreateChatRequest {
-    pub name: Option<String>,
-    pub is_group: bool,
-    pub participant_ids: Vec<Uuid>,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct SendMessageRequest {
-    pub content: String,
-    pub media_url: Option<String>,
-}
-
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct Chat {
     pub id: Uuid,
@@ -108,14 +100,6 @@ pub struct Chat {
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
-pub struct ChatParticipant {
-    pub chat_id: Uuid,
-    pub user_id: Uuid,
-    pub role: String,
-    pub joined_at: DateTime<Utc>,
-}
-
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct Message {
     pub id: Uuid,
@@ -127,21 +111,6 @@ pub struct Message {
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
-pub struct MessageRead {
-    pub message_id: Uuid,
-    pub user_id: Uuid,
-    pub read_at: DateTime<Utc>,
-}
-
-#[derive(Debug, Serialize, Deserialize, FromRow)]
-pub struct MessageReaction {
-    pub message_id: Uuid,
-    pub user_id: Uuid,
-    pub emoji: String,
-    pub created_at: DateTime<Utc>,
-}
-
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Media {
     pub id: Uuid,
@@ -152,49 +121,6 @@ pub struct Media {
     pub created_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct ForwardMessageRequest {
-    pub message_id: Uuid,
-    pub chat_ids: Vec<Uuid>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct AddReactionRequest {
-    pub emoji: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct RemoveReactionRequest {
-    pub emoji: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct MessageReactionResponse {
-    pub emoji: String,
-    pub count: i64,
-    pub users: Vec<User>,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct SearchMessagesRequest {
-    pub query: String,
-    pub chat_id: Option<Uuid>,
-    pub limit: Option<i64>,
-    pub offset: Option<i64>,
-}
-
-#[derive(Debug, Serialize)]
-pub struct SearchResult {
-    pub id: Uuid,
-    pub content: String,
-    pub created_at: DateTime<Utc>,
-    pub sender: UserResponse,
-    pub chat: Chat,
-    pub media_url: Option<String>,
-    pub reactions: Option<serde_json::Value>,
-    pub read_by: Option<serde_json::Value>,
-}
-
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MediaUploadResponse {
     pub id: Uuid,
@@ -0,0 +1,85 @@
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::error::AppError;
+
+/// Position of a message in a conversation. Messages are ordered by
+/// (created_at, id) so that messages sharing a timestamp still have a
+/// stable order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+impl Cursor {
+    pub fn new(created_at: DateTime<Utc>, id: Uuid) -> Self {
+        Self { created_at, id }
+    }
+
+    /// Clients should treat this as an opaque string.
+    pub fn encode(&self) -> String {
+        let mut bytes = Vec::with_capacity(24);
+        bytes.extend_from_slice(&self.created_at.timestamp_micros().to_be_bytes());
+        bytes.extend_from_slice(self.id.as_bytes());
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    pub fn decode(value: &str) -> Result<Self, AppError> {
+        let invalid = || AppError::BadRequest("Invalid cursor".into());
+        if value.len() != 48 || !value.is_ascii() {
+            return Err(invalid());
+        }
+
+        let bytes = (0..value.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&value[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| invalid())?;
+
+        let micros = i64::from_be_bytes(bytes[..8].try_into().map_err(|_| invalid())?);
+        let created_at = Utc
+            .timestamp_opt(micros.div_euclid(1_000_000), (micros.rem_euclid(1_000_000) * 1000) as u32)
+            .single()
+            .ok_or_else(invalid)?;
+        let id = Uuid::from_slice(&bytes[8..]).map_err(|_| invalid())?;
+
+        Ok(Self { created_at, id })
+    }
+
+    /// A bound that compares below this cursor's successor, so `before` it
+    /// includes the message itself. Postgres orders uuids bytewise, which
+    /// matches their big-endian integer value.
+    pub fn inclusive(&self) -> Self {
+        Self {
+            created_at: self.created_at,
+            id: Uuid::from_u128(self.id.as_u128().saturating_add(1)),
+        }
+    }
+
+    /// The counterpart of `inclusive` for `after` bounds.
+    pub fn inclusive_after(&self) -> Self {
+        Self {
+            created_at: self.created_at,
+            id: Uuid::from_u128(self.id.as_u128().saturating_sub(1)),
+        }
+    }
+}
+
+/// Anything that can be paged through by cursor.
+pub trait Paginated {
+    fn cursor(&self) -> Cursor;
+}
+
+/// A window of messages, newest first.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessagePage<T> {
+    pub messages: Vec<T>,
+    /// Pass as `before` to load older messages
+    pub before_cursor: Option<String>,
+    /// Pass as `after` to load newer messages
+    pub after_cursor: Option<String>,
+    pub has_more_before: bool,
+    pub has_more_after: bool,
+}
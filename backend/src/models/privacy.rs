@@ -0,0 +1,109 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "privacy_visibility", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum PrivacyVisibility {
+    Everyone,
+    Contacts,
+    Nobody,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrivacySetting {
+    LastSeen,
+    Online,
+    Avatar,
+    Status,
+    ReadReceipts,
+}
+
+impl PrivacySetting {
+    pub const ALL: [PrivacySetting; 5] = [
+        PrivacySetting::LastSeen,
+        PrivacySetting::Online,
+        PrivacySetting::Avatar,
+        PrivacySetting::Status,
+        PrivacySetting::ReadReceipts,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PrivacySetting::LastSeen => "last_seen",
+            PrivacySetting::Online => "online",
+            PrivacySetting::Avatar => "avatar",
+            PrivacySetting::Status => "status",
+            PrivacySetting::ReadReceipts => "read_receipts",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|setting| setting.as_str() == value)
+    }
+
+    /// Settings where hiding your own value also hides everyone else's from you.
+    pub fn is_reciprocal(&self) -> bool {
+        matches!(self, PrivacySetting::LastSeen | PrivacySetting::ReadReceipts)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PrivacySettings {
+    pub user_id: Uuid,
+    pub last_seen: PrivacyVisibility,
+    pub online: PrivacyVisibility,
+    pub avatar: PrivacyVisibility,
+    pub status: PrivacyVisibility,
+    pub read_receipts: PrivacyVisibility,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl PrivacySettings {
+    pub fn default_for(user_id: Uuid) -> Self {
+        Self {
+            user_id,
+            last_seen: PrivacyVisibility::Everyone,
+            online: PrivacyVisibility::Everyone,
+            avatar: PrivacyVisibility::Everyone,
+            status: PrivacyVisibility::Everyone,
+            read_receipts: PrivacyVisibility::Everyone,
+            updated_at: Utc::now(),
+        }
+    }
+
+    pub fn get(&self, setting: PrivacySetting) -> PrivacyVisibility {
+        match setting {
+            PrivacySetting::LastSeen => self.last_seen,
+            PrivacySetting::Online => self.online,
+            PrivacySetting::Avatar => self.avatar,
+            PrivacySetting::Status => self.status,
+            PrivacySetting::ReadReceipts => self.read_receipts,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdatePrivacyRequest {
+    pub last_seen: Option<PrivacyVisibility>,
+    pub online: Option<PrivacyVisibility>,
+    pub avatar: Option<PrivacyVisibility>,
+    pub status: Option<PrivacyVisibility>,
+    pub read_receipts: Option<PrivacyVisibility>,
+    /// Replaces the exception list of every setting present in the map
+    pub exceptions: Option<HashMap<PrivacySetting, Vec<Uuid>>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PrivacyResponse {
+    pub last_seen: PrivacyVisibility,
+    pub online: PrivacyVisibility,
+    pub avatar: PrivacyVisibility,
+    pub status: PrivacyVisibility,
+    pub read_receipts: PrivacyVisibility,
+    pub exceptions: HashMap<PrivacySetting, Vec<Uuid>>,
+}
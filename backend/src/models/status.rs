@@ -0,0 +1,123 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "status_audience_mode", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum StatusAudienceMode {
+    /// All mutual contacts
+    Contacts,
+    /// Mutual contacts not on the except list
+    Except,
+    /// Mutual contacts on the only list
+    Only,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct StatusUpdate {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub content: Option<String>,
+    pub media_id: Option<Uuid>,
+    pub media_url: Option<String>,
+    pub media_type: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub background_color: Option<String>,
+    pub font_style: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateStatusRequest {
+    pub content: Option<String>,
+    /// Id returned by the media upload endpoint
+    pub media_id: Option<Uuid>,
+    pub background_color: Option<String>,
+    pub font_style: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusResponse {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub content: Option<String>,
+    pub media_url: Option<String>,
+    pub media_type: Option<String>,
+    pub background_color: Option<String>,
+    pub font_style: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub viewed: bool,
+}
+
+impl From<StatusUpdate> for StatusResponse {
+    fn from(status: StatusUpdate) -> Self {
+        Self {
+            id: status.id,
+            user_id: status.user_id,
+            content: status.content,
+            media_url: status.media_url,
+            media_type: status.media_type,
+            background_color: status.background_color,
+            font_style: status.font_style,
+            created_at: status.created_at,
+            expires_at: status.expires_at,
+            viewed: false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MyStatusResponse {
+    #[serde(flatten)]
+    pub status: StatusResponse,
+    pub view_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatusFeedEntry {
+    pub user_id: Uuid,
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub has_unviewed: bool,
+    pub latest_at: DateTime<Utc>,
+    pub statuses: Vec<StatusResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatusViewerResponse {
+    pub user_id: Uuid,
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub viewed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatusAudienceResponse {
+    pub mode: StatusAudienceMode,
+    pub except: Vec<Uuid>,
+    pub only: Vec<Uuid>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateStatusAudienceRequest {
+    pub mode: Option<StatusAudienceMode>,
+    /// Replaces the "contacts except" list
+    pub except: Option<Vec<Uuid>>,
+    /// Replaces the "only share with" list
+    pub only: Option<Vec<Uuid>>,
+}
+
+/// What a status looked like when a message replied to it. The status itself
+/// may have expired since, in which case `status_id` is null.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusReplyPreview {
+    pub status_id: Option<Uuid>,
+    pub author_id: Uuid,
+    pub content: Option<String>,
+    pub media_url: Option<String>,
+    pub media_type: Option<String>,
+    pub background_color: Option<String>,
+}
@@ -16,6 +16,9 @@ pub struct User {
     pub is_online: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Stands in for someone in imported history who has no account
+    #[serde(default)]
+    pub is_placeholder: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -71,4 +74,10 @@ pub struct UpdateProfileRequest {
     pub username: Option<String>,
     pub avatar_url: Option<String>,
     pub status: Option<String>,
-}
\ No newline at end of file
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PresenceResponse {
+    pub user_id: Uuid,
+    pub is_online: bool,
+    pub last_seen: Option<DateTime<Utc>>,
+}
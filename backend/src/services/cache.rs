@@ -0,0 +1,336 @@
+use deadpool_redis::{Config as PoolConfig, CreatePoolError, Pool, Runtime};
+use redis::AsyncCommands;
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    error::AppError,
+    metrics::{CACHE_HITS, CACHE_INVALIDATIONS, CACHE_MISSES},
+};
+
+/// How long a cross-node load lock is held before another node may take over.
+const LOAD_LOCK_TTL_MS: usize = 5_000;
+const LOAD_LOCK_POLL_INTERVAL: Duration = Duration::from_millis(50);
+const LOAD_LOCK_POLL_ATTEMPTS: usize = 20;
+/// Tag sets are cleaned up on invalidation; this only bounds leaks from keys
+/// that expired without ever being invalidated.
+const TAG_TTL: usize = 24 * 60 * 60;
+/// Counter bumped by every invalidation; each tag remembers the value it was
+/// last invalidated at, so a load can tell whether it raced one.
+const CLOCK_KEY: &str = "cache:clock";
+
+/// Stores a value and tags it, unless one of its tags was invalidated after
+/// `since` (when given). KEYS: the value's key, then each tag's set and
+/// generation. ARGV: value, TTL, tag TTL, `since` or -1.
+const WRITE_SCRIPT: &str = r#"
+local since = tonumber(ARGV[4])
+if since >= 0 then
+    for i = 3, #KEYS, 2 do
+        if tonumber(redis.call('GET', KEYS[i]) or '0') > since then
+            return 0
+        end
+    end
+end
+redis.call('SET', KEYS[1], ARGV[1], 'EX', ARGV[2])
+for i = 2, #KEYS, 2 do
+    redis.call('SADD', KEYS[i], KEYS[1])
+    redis.call('EXPIRE', KEYS[i], ARGV[3])
+end
+return 1
+"#;
+
+/// Moves a tag's generation past every load in flight, then drops its keys.
+/// KEYS: the clock, the tag's set and its generation. ARGV: tag TTL.
+const DROP_TAG_SCRIPT: &str = r#"
+local now = redis.call('INCR', KEYS[1])
+redis.call('SET', KEYS[3], now, 'EX', ARGV[1])
+for _, key in ipairs(redis.call('SMEMBERS', KEYS[2])) do
+    redis.call('DEL', key)
+end
+redis.call('DEL', KEYS[2])
+return now
+"#;
+
+/// Everything the application caches. The key decides the Redis key, TTL and
+/// the tags that invalidate it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CacheKey {
+    User(Uuid),
+    UserDirectory,
+    Contacts(Uuid),
+    Group(Uuid),
+    GroupMembers(Uuid),
+}
+
+impl CacheKey {
+    fn kind(&self) -> &'static str {
+        match self {
+            CacheKey::User(_) => "user",
+            CacheKey::UserDirectory => "user_directory",
+            CacheKey::Contacts(_) => "contacts",
+            CacheKey::Group(_) => "group",
+            CacheKey::GroupMembers(_) => "group_members",
+        }
+    }
+
+    fn redis_key(&self) -> String {
+        match self {
+            CacheKey::User(id) => format!("user:{}", id),
+            CacheKey::UserDirectory => "users:all".to_string(),
+            CacheKey::Contacts(id) => format!("user:{}:contacts", id),
+            CacheKey::Group(id) => format!("group:{}", id),
+            CacheKey::GroupMembers(id) => format!("group:{}:members", id),
+        }
+    }
+
+    fn ttl(&self) -> usize {
+        match self {
+            CacheKey::User(_) | CacheKey::Group(_) => 3600, // 1 hour
+            CacheKey::UserDirectory | CacheKey::Contacts(_) | CacheKey::GroupMembers(_) => 300, // 5 minutes
+        }
+    }
+
+    fn tags(&self) -> Vec<CacheTag> {
+        match self {
+            CacheKey::User(id) => vec![CacheTag::User(*id)],
+            CacheKey::UserDirectory => vec![CacheTag::UserDirectory],
+            CacheKey::Contacts(id) => vec![CacheTag::Contacts(*id)],
+            CacheKey::Group(id) | CacheKey::GroupMembers(id) => vec![CacheTag::Group(*id)],
+        }
+    }
+}
+
+/// Invalidation handles. Writes invalidate tags rather than individual keys so
+/// that every cached view of the changed entity goes away together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CacheTag {
+    User(Uuid),
+    UserDirectory,
+    Contacts(Uuid),
+    Group(Uuid),
+}
+
+impl CacheTag {
+    fn redis_key(&self) -> String {
+        match self {
+            CacheTag::User(id) => format!("tag:user:{}", id),
+            CacheTag::UserDirectory => "tag:users".to_string(),
+            CacheTag::Contacts(id) => format!("tag:contacts:{}", id),
+            CacheTag::Group(id) => format!("tag:group:{}", id),
+        }
+    }
+
+    fn generation_key(&self) -> String {
+        format!("{}:gen", self.redis_key())
+    }
+}
+
+pub struct CacheService {
+    pool: Pool,
+    /// Per-key gates so only one request per process loads a missing value
+    loading: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl CacheService {
+    pub fn new(client: &redis::Client) -> Result<Self, CreatePoolError> {
+        let pool = PoolConfig::from_connection_info(client.get_connection_info().clone())
+            .create_pool(Some(Runtime::Tokio1))?;
+        Ok(Self {
+            pool,
+            loading: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub async fn get<T: DeserializeOwned>(&self, key: CacheKey) -> Option<T> {
+        let value = self.read(key).await;
+        if value.is_some() {
+            CACHE_HITS.with_label_values(&[key.kind()]).inc();
+        } else {
+            CACHE_MISSES.with_label_values(&[key.kind()]).inc();
+        }
+        value
+    }
+
+    pub async fn set<T: Serialize>(&self, key: CacheKey, value: &T, extra_tags: &[CacheTag]) {
+        if let Err(e) = self.write(key, value, extra_tags, None).await {
+            warn!("Failed to cache {}: {}", key.redis_key(), e);
+        }
+    }
+
+    /// Returns the cached value or loads it, making sure concurrent misses for
+    /// the same key result in a single load.
+    pub async fn get_or_load<T, F, Fut>(&self, key: CacheKey, load: F) -> Result<T, AppError>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, AppError>>,
+    {
+        self.get_or_load_tagged(key, || async { Ok((load().await?, Vec::new())) }).await
+    }
+
+    /// Like `get_or_load`, but the loader can attach extra tags derived from the
+    /// loaded value (e.g. every member of a cached member list). A value whose
+    /// tags were invalidated while it loaded is returned but not cached.
+    pub async fn get_or_load_tagged<T, F, Fut>(&self, key: CacheKey, load: F) -> Result<T, AppError>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<(T, Vec<CacheTag>), AppError>>,
+    {
+        if let Some(value) = self.get(key).await {
+            return Ok(value);
+        }
+
+        let redis_key = key.redis_key();
+        let gate = self.gate(&redis_key);
+        let result = async {
+            let _guard = gate.lock().await;
+
+            // Another request in this process may have filled it while we waited
+            if let Some(value) = self.read(key).await {
+                return Ok(value);
+            }
+
+            // Across nodes, whoever holds the lock loads while the rest wait for it
+            let locked = self.try_lock(&redis_key).await;
+            if !locked {
+                for _ in 0..LOAD_LOCK_POLL_ATTEMPTS {
+                    tokio::time::sleep(LOAD_LOCK_POLL_INTERVAL).await;
+                    if let Some(value) = self.read(key).await {
+                        return Ok(value);
+                    }
+                }
+            }
+
+            let since = self.clock().await;
+            let loaded = load().await;
+            if let (Ok((value, extra_tags)), Some(since)) = (&loaded, since) {
+                if let Err(e) = self.write(key, value, extra_tags, Some(since)).await {
+                    warn!("Failed to cache {}: {}", redis_key, e);
+                }
+            }
+            if locked {
+                self.unlock(&redis_key).await;
+            }
+            loaded.map(|(value, _)| value)
+        }
+        .await;
+
+        self.release_gate(&redis_key, gate);
+        result
+    }
+
+    /// Drops every key carrying any of the given tags.
+    pub async fn invalidate(&self, tags: &[CacheTag]) {
+        for tag in tags {
+            match self.drop_tag(*tag).await {
+                Ok(()) => CACHE_INVALIDATIONS.inc(),
+                Err(e) => warn!("Failed to invalidate {}: {}", tag.redis_key(), e),
+            }
+        }
+    }
+
+    async fn read<T: DeserializeOwned>(&self, key: CacheKey) -> Option<T> {
+        let mut conn = self.pool.get().await.ok()?;
+        let cached: Option<String> = conn.get(key.redis_key()).await.ok()?;
+        cached.and_then(|json| serde_json::from_str(&json).ok())
+    }
+
+    async fn write<T: Serialize>(
+        &self,
+        key: CacheKey,
+        value: &T,
+        extra_tags: &[CacheTag],
+        since: Option<i64>,
+    ) -> Result<(), AppError> {
+        let json = serde_json::to_string(value)?;
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .map_err(|e| AppError::InternalServerError(e.to_string()))?;
+
+        let script = redis::Script::new(WRITE_SCRIPT);
+        let mut invocation = script.key(key.redis_key());
+        for tag in key.tags().iter().chain(extra_tags) {
+            invocation.key(tag.redis_key()).key(tag.generation_key());
+        }
+        invocation
+            .arg(json)
+            .arg(key.ttl())
+            .arg(TAG_TTL)
+            .arg(since.unwrap_or(-1))
+            .invoke_async::<_, i64>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn drop_tag(&self, tag: CacheTag) -> Result<(), AppError> {
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .map_err(|e| AppError::InternalServerError(e.to_string()))?;
+
+        redis::Script::new(DROP_TAG_SCRIPT)
+            .key(CLOCK_KEY)
+            .key(tag.redis_key())
+            .key(tag.generation_key())
+            .arg(TAG_TTL)
+            .invoke_async::<_, i64>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    /// The invalidation clock before a load starts, or `None` if Redis can't
+    /// be reached, in which case nothing gets cached anyway.
+    async fn clock(&self) -> Option<i64> {
+        let mut conn = self.pool.get().await.ok()?;
+        let now: Option<i64> = conn.get(CLOCK_KEY).await.ok()?;
+        Some(now.unwrap_or(0))
+    }
+
+    async fn try_lock(&self, redis_key: &str) -> bool {
+        let Ok(mut conn) = self.pool.get().await else {
+            return true;
+        };
+        let acquired: Result<Option<String>, _> = redis::cmd("SET")
+            .arg(format!("lock:{}", redis_key))
+            .arg(1)
+            .arg("NX")
+            .arg("PX")
+            .arg(LOAD_LOCK_TTL_MS)
+            .query_async(&mut conn)
+            .await;
+        // If Redis is unavailable there is nothing to coordinate on, so just load
+        acquired.map(|reply| reply.is_some()).unwrap_or(true)
+    }
+
+    async fn unlock(&self, redis_key: &str) {
+        if let Ok(mut conn) = self.pool.get().await {
+            let _: Result<(), _> = conn.del(format!("lock:{}", redis_key)).await;
+        }
+    }
+
+    fn gate(&self, redis_key: &str) -> Arc<tokio::sync::Mutex<()>> {
+        self.loading
+            .lock()
+            .unwrap()
+            .entry(redis_key.to_string())
+            .or_default()
+            .clone()
+    }
+
+    fn release_gate(&self, redis_key: &str, gate: Arc<tokio::sync::Mutex<()>>) {
+        let mut loading = self.loading.lock().unwrap();
+        // One reference is ours and one is the map's; anyone else is still waiting
+        if Arc::strong_count(&gate) <= 2 {
+            loading.remove(redis_key);
+        }
+    }
+}
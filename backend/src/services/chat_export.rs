@@ -0,0 +1,709 @@
+use chrono::{DateTime, Utc};
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json as SqlJson;
+use sqlx::PgPool;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    error::AppError,
+    handlers::conversations,
+    models::{export::ChatExport, pagination::Cursor},
+};
+
+pub use crate::models::export::ExportFormat;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const PAGE_SIZE: i64 = 500;
+/// How often a worker records that it is still running an export
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(60);
+/// Running exports whose worker hasn't beaten for this long are taken over
+const STALE_AFTER_SECS: f64 = 10.0 * 60.0;
+const MAX_MEDIA_FILE_BYTES: usize = 64 * 1024 * 1024;
+const MAX_MEDIA_TOTAL_BYTES: usize = 1024 * 1024 * 1024;
+const MEDIA_TIMEOUT: Duration = Duration::from_secs(60);
+const DOWNLOAD_AUDIENCE: &str = "chat-export";
+pub const DOWNLOAD_LINK_TTL_SECS: i64 = 60 * 60;
+
+/// The conversation being exported, from the exporter's point of view.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportChat {
+    pub id: Uuid,
+    pub name: String,
+    pub is_group: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportMessage {
+    pub id: Uuid,
+    pub sender_id: Uuid,
+    pub sender_name: String,
+    pub created_at: DateTime<Utc>,
+    /// Empty for deleted messages
+    pub content: String,
+    #[serde(default)]
+    pub is_edited: bool,
+    #[serde(default)]
+    pub is_deleted: bool,
+    #[serde(default)]
+    pub is_system: bool,
+    pub reply_to_id: Option<Uuid>,
+    pub media: Option<ExportMedia>,
+    #[serde(default)]
+    pub reactions: Vec<ExportReaction>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportMedia {
+    pub url: String,
+    /// Path of the copy inside the zip, when media was included
+    pub file: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportReaction {
+    pub emoji: String,
+    pub user_id: Uuid,
+    pub user_name: String,
+}
+
+/// Timestamps as WhatsApp writes them in its day-first locales.
+pub fn format_txt_timestamp(at: DateTime<Utc>) -> String {
+    at.format("%d/%m/%Y, %H:%M").to_string()
+}
+
+/// Everything before the first message.
+pub fn render_header(format: ExportFormat, chat: &ExportChat, exported_at: DateTime<Utc>) -> String {
+    match format {
+        ExportFormat::Json => format!(
+            "{{\"chat\":{},\"exported_at\":{},\"messages\":[\n",
+            serde_json::to_string(chat).unwrap_or_default(),
+            serde_json::to_string(&exported_at).unwrap_or_default()
+        ),
+        ExportFormat::Txt => String::new(),
+        ExportFormat::Html => format!(
+            concat!(
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{name}</title>\n",
+                "<style>\n",
+                "body {{ font-family: sans-serif; background: #ece5dd; margin: 0; padding: 1em; }}\n",
+                "h1 {{ font-size: 1.2em; }}\n",
+                ".message {{ background: #fff; border-radius: 6px; margin: 0.5em 0; padding: 0.5em 0.75em; max-width: 40em; }}\n",
+                ".system {{ background: #fdf4c5; text-align: center; margin: 0.5em auto; }}\n",
+                ".meta {{ color: #667781; font-size: 0.8em; }}\n",
+                ".sender {{ font-weight: bold; }}\n",
+                ".reply {{ border-left: 3px solid #25d366; padding-left: 0.5em; font-size: 0.85em; }}\n",
+                ".deleted {{ font-style: italic; color: #667781; }}\n",
+                ".reactions {{ font-size: 0.85em; }}\n",
+                "img {{ max-width: 100%; }}\n",
+                "</style>\n</head>\n<body>\n<h1>{name}</h1>\n<p class=\"meta\">Exported {exported_at}</p>\n"
+            ),
+            name = escape_html(&chat.name),
+            exported_at = exported_at.to_rfc3339()
+        ),
+    }
+}
+
+/// One message; `first` is set for the first message of the export.
+pub fn render_message(format: ExportFormat, message: &ExportMessage, first: bool) -> String {
+    match format {
+        ExportFormat::Json => {
+            let json = serde_json::to_string(message).unwrap_or_default();
+            if first { json } else { format!(",\n{}", json) }
+        }
+        ExportFormat::Txt => render_txt_message(message),
+        ExportFormat::Html => render_html_message(message),
+    }
+}
+
+/// Everything after the last message.
+pub fn render_footer(format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Json => "\n]}\n".to_string(),
+        ExportFormat::Txt => String::new(),
+        ExportFormat::Html => "</body>\n</html>\n".to_string(),
+    }
+}
+
+fn render_txt_message(message: &ExportMessage) -> String {
+    let timestamp = format_txt_timestamp(message.created_at);
+    if message.is_system {
+        return format!("{} - {}\n", timestamp, message.content);
+    }
+
+    let body = if message.is_deleted {
+        "This message was deleted".to_string()
+    } else {
+        let media = message.media.as_ref().map(|media| match &media.file {
+            Some(file) => format!("{} (file attached)", file.trim_start_matches("media/")),
+            None => "<Media omitted>".to_string(),
+        });
+        match (media, message.content.is_empty()) {
+            (Some(media), true) => media,
+            (Some(media), false) => format!("{}\n{}", media, message.content),
+            (None, _) => message.content.clone(),
+        }
+    };
+    let edited = if message.is_edited && !message.is_deleted { " <This message was edited>" } else { "" };
+
+    format!("{} - {}: {}{}\n", timestamp, message.sender_name, body, edited)
+}
+
+fn render_html_message(message: &ExportMessage) -> String {
+    let time = message.created_at.format("%Y-%m-%d %H:%M UTC");
+    if message.is_system {
+        return format!(
+            "<div class=\"message system\" id=\"m-{}\"><span class=\"meta\">{}</span> {}</div>\n",
+            message.id,
+            time,
+            escape_html(&message.content)
+        );
+    }
+
+    let mut html = format!(
+        "<div class=\"message\" id=\"m-{}\">\n<div class=\"meta\"><span class=\"sender\">{}</span> {}{}</div>\n",
+        message.id,
+        escape_html(&message.sender_name),
+        time,
+        if message.is_edited && !message.is_deleted { " (edited)" } else { "" }
+    );
+
+    if let Some(reply_to_id) = message.reply_to_id {
+        html.push_str(&format!(
+            "<div class=\"reply\"><a href=\"#m-{}\">In reply to an earlier message</a></div>\n",
+            reply_to_id
+        ));
+    }
+
+    if message.is_deleted {
+        html.push_str("<div class=\"deleted\">This message was deleted</div>\n");
+    } else {
+        // Links out of the file must be web URLs, whatever the sender stored
+        let target = message.media.as_ref().and_then(|media| match &media.file {
+            Some(file) => Some(file.as_str()),
+            None => (media.url.starts_with("https://") || media.url.starts_with("http://"))
+                .then_some(media.url.as_str()),
+        });
+        if let Some(target) = target {
+            let src = escape_html(target);
+            if is_image(target) {
+                html.push_str(&format!("<div><a href=\"{0}\"><img src=\"{0}\" alt=\"\"></a></div>\n", src));
+            } else {
+                html.push_str(&format!("<div><a href=\"{}\">Attachment</a></div>\n", src));
+            }
+        }
+        if !message.content.is_empty() {
+            html.push_str(&format!("<div>{}</div>\n", escape_html(&message.content).replace('\n', "<br>")));
+        }
+    }
+
+    if !message.reactions.is_empty() {
+        let reactions: Vec<String> = message
+            .reactions
+            .iter()
+            .map(|reaction| format!("{} {}", escape_html(&reaction.emoji), escape_html(&reaction.user_name)))
+            .collect();
+        html.push_str(&format!("<div class=\"reactions\">{}</div>\n", reactions.join(", ")));
+    }
+
+    html.push_str("</div>\n");
+    html
+}
+
+pub fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn is_image(path: &str) -> bool {
+    let path = path.split(['?', '#']).next().unwrap_or_default().to_ascii_lowercase();
+    [".jpg", ".jpeg", ".png", ".gif", ".webp"].iter().any(|ext| path.ends_with(ext))
+}
+
+/// File extension of a media URL, if it has a short alphanumeric one.
+fn media_extension(url: &str) -> Option<String> {
+    let path = url.split(['?', '#']).next()?;
+    let (_, ext) = path.rsplit_once('/')?.1.rsplit_once('.')?;
+    (!ext.is_empty() && ext.len() <= 5 && ext.chars().all(|c| c.is_ascii_alphanumeric()))
+        .then(|| ext.to_ascii_lowercase())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DownloadClaims {
+    sub: String,
+    aud: String,
+    exp: usize,
+}
+
+/// A token for downloading a finished export without logging in. It carries
+/// its own audience, so it can't be used as a session token.
+pub fn download_token(secret: &str, export_id: Uuid, expires_at: DateTime<Utc>) -> Result<String, AppError> {
+    let claims = DownloadClaims {
+        sub: export_id.to_string(),
+        aud: DOWNLOAD_AUDIENCE.to_string(),
+        exp: expires_at.timestamp() as usize,
+    };
+
+    Ok(encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(secret.as_bytes()),
+    )?)
+}
+
+/// The export a download token is for, if it is genuine and unexpired.
+pub fn verify_download_token(secret: &str, token: &str) -> Result<Uuid, AppError> {
+    let mut validation = Validation::default();
+    validation.set_audience(&[DOWNLOAD_AUDIENCE]);
+
+    let claims = decode::<DownloadClaims>(
+        token,
+        &DecodingKey::from_secret(secret.as_bytes()),
+        &validation,
+    )
+    .map_err(|_| AppError::Unauthorized("Invalid or expired download link".into()))?
+    .claims;
+
+    Ok(Uuid::parse_str(&claims.sub)?)
+}
+
+/// Path of a finished export's file.
+pub fn export_path(state: &AppState, file_name: &str) -> PathBuf {
+    state.messaging.export_dir.join(file_name)
+}
+
+/// Runs queued exports in the background, and removes expired ones every
+/// hour, for the lifetime of the process.
+pub fn spawn_chat_export_worker(state: Arc<AppState>) {
+    let worker = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            match run_pending_exports(&worker).await {
+                Ok(finished) if finished > 0 => info!("Finished {} chat exports", finished),
+                Ok(_) => {}
+                Err(e) => error!("Chat export run failed: {}", e),
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match prune_expired_exports(&state).await {
+                Ok(pruned) if pruned > 0 => info!("Pruned {} expired chat exports", pruned),
+                Ok(_) => {}
+                Err(e) => error!("Chat export pruning failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Works through queued exports one at a time. Each is claimed before it
+/// runs, so replicas running at the same time pick up different exports, and
+/// only the latest claim on an export may finish it.
+pub async fn run_pending_exports(state: &AppState) -> Result<u64, AppError> {
+    let mut finished = 0;
+
+    loop {
+        let claim_id = Uuid::new_v4();
+        let Some(export) = claim_next(state, claim_id).await? else { break };
+
+        let heartbeat = spawn_heartbeat(state.pool.clone(), export.id, claim_id);
+        let result = write_export(state, &export, claim_id).await;
+        heartbeat.abort();
+
+        match result {
+            Ok((file_name, file_size, message_count)) => {
+                let completed = sqlx::query!(
+                    r#"
+                    UPDATE chat_exports
+                    SET status = 'completed', file_name = $2, file_size = $3, message_count = $4,
+                        completed_at = NOW(), expires_at = NOW() + make_interval(secs => $5)
+                    WHERE id = $1 AND claim_id = $6 AND status = 'running'
+                    "#,
+                    export.id,
+                    file_name,
+                    file_size,
+                    message_count,
+                    state.messaging.export_retention.num_seconds() as f64,
+                    claim_id
+                )
+                .execute(&state.pool)
+                .await?
+                .rows_affected();
+
+                if completed == 0 {
+                    warn!("Chat export {} was taken over by another worker; dropping this run", export.id);
+                    let _ = tokio::fs::remove_file(export_path(state, &file_name)).await;
+                    continue;
+                }
+                finished += 1;
+            }
+            Err(e) => {
+                warn!("Chat export {} failed: {}", export.id, e);
+                let _ = tokio::fs::remove_dir_all(staging_dir(state, claim_id)).await;
+                let _ = tokio::fs::remove_file(export_path(state, &format!("{}.zip", claim_id))).await;
+                sqlx::query!(
+                    r#"
+                    UPDATE chat_exports
+                    SET status = 'failed', last_error = $2, completed_at = NOW()
+                    WHERE id = $1 AND claim_id = $3 AND status = 'running'
+                    "#,
+                    export.id,
+                    e.to_string(),
+                    claim_id
+                )
+                .execute(&state.pool)
+                .await?;
+            }
+        }
+    }
+
+    Ok(finished)
+}
+
+async fn claim_next(state: &AppState, claim_id: Uuid) -> Result<Option<ChatExport>, AppError> {
+    let export = sqlx::query_as!(
+        ChatExport,
+        r#"
+        UPDATE chat_exports
+        SET status = 'running', started_at = NOW(), heartbeat_at = NOW(), claim_id = $2
+        WHERE id = (
+            SELECT id FROM chat_exports
+            WHERE status = 'pending'
+                OR (status = 'running' AND heartbeat_at < NOW() - make_interval(secs => $1))
+            ORDER BY created_at
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING id, user_id, chat_id, format as "format: _", include_media,
+            status as "status: _", file_name, file_size, message_count, last_error,
+            created_at, started_at, completed_at, expires_at
+        "#,
+        STALE_AFTER_SECS,
+        claim_id
+    )
+    .fetch_optional(&state.pool)
+    .await?;
+
+    Ok(export)
+}
+
+/// Keeps a claim's heartbeat fresh while its export runs, so a long export
+/// isn't mistaken for an abandoned one.
+fn spawn_heartbeat(pool: PgPool, export_id: Uuid, claim_id: Uuid) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let beat = sqlx::query!(
+                r#"
+                UPDATE chat_exports
+                SET heartbeat_at = NOW()
+                WHERE id = $1 AND claim_id = $2 AND status = 'running'
+                "#,
+                export_id,
+                claim_id
+            )
+            .execute(&pool)
+            .await;
+            if let Err(e) = beat {
+                warn!("Chat export {} heartbeat failed: {}", export_id, e);
+            }
+        }
+    })
+}
+
+/// Files are named after the claim rather than the export, so a worker that
+/// lost its claim can't overwrite the files of the one that took over.
+fn staging_dir(state: &AppState, claim_id: Uuid) -> PathBuf {
+    state.messaging.export_dir.join(claim_id.to_string())
+}
+
+/// Streams the conversation into the export file page by page, then zips it
+/// with its media if asked to. Returns the file's name, size and how many
+/// messages went into it.
+async fn write_export(state: &AppState, export: &ChatExport, claim_id: Uuid) -> Result<(String, i64, i32), AppError> {
+    let (conversation_id, chat) = load_chat(state, export.user_id, export.chat_id).await?;
+    let staging = staging_dir(state, claim_id);
+    if export.include_media {
+        tokio::fs::create_dir_all(staging.join("media")).await?;
+    } else {
+        tokio::fs::create_dir_all(&staging).await?;
+    }
+
+    let main_name = format!("chat.{}", export.format.extension());
+    let mut file = BufWriter::new(tokio::fs::File::create(staging.join(&main_name)).await?);
+    file.write_all(render_header(export.format, &chat, Utc::now()).as_bytes()).await?;
+
+    let client = Client::builder().timeout(MEDIA_TIMEOUT).build().map_err(|e| {
+        AppError::InternalServerError(format!("Failed to create HTTP client: {}", e))
+    })?;
+    let mut media_bytes = 0;
+    let mut count: i32 = 0;
+    let mut after = None;
+    loop {
+        let page = load_page(state, conversation_id, export.user_id, after).await?;
+        let Some(last) = page.last() else { break };
+        after = Some(Cursor::new(last.message.created_at, last.message.id));
+
+        for row in page {
+            let mut message = row.message;
+            if export.include_media && row.media_is_ours {
+                if let Some(media) = message.media.as_mut() {
+                    media.file = copy_media(&client, &staging, message.id, &media.url, &mut media_bytes).await;
+                }
+            }
+            file.write_all(render_message(export.format, &message, count == 0).as_bytes()).await?;
+            count += 1;
+        }
+    }
+
+    file.write_all(render_footer(export.format).as_bytes()).await?;
+    file.flush().await?;
+    drop(file);
+
+    let file_name = if export.include_media {
+        let file_name = format!("{}.zip", claim_id);
+        let archive = export_path(state, &file_name);
+        let source = staging.clone();
+        tokio::task::spawn_blocking(move || zip_dir(&source, &archive))
+            .await
+            .map_err(|e| AppError::InternalServerError(format!("Zip task failed: {}", e)))??;
+        file_name
+    } else {
+        let file_name = format!("{}.{}", claim_id, export.format.extension());
+        tokio::fs::rename(staging.join(&main_name), export_path(state, &file_name)).await?;
+        file_name
+    };
+    tokio::fs::remove_dir_all(&staging).await?;
+
+    let file_size = tokio::fs::metadata(export_path(state, &file_name)).await?.len() as i64;
+    Ok((file_name, file_size, count))
+}
+
+/// The exporter's view of the conversation, with the conversation's id.
+/// Fails once they have left it.
+async fn load_chat(state: &AppState, user_id: Uuid, chat_id: Uuid) -> Result<(Uuid, ExportChat), AppError> {
+    let conversation = conversations::ensure_conversation(state, user_id, chat_id).await?;
+
+    let name = sqlx::query_scalar!(
+        r#"
+        SELECT COALESCE(g.name, u.display_name, u.username) as "name!"
+        FROM conversations c
+        LEFT JOIN groups g ON g.id = c.id
+        LEFT JOIN users u ON c.kind = 'direct' AND u.id = $2
+        WHERE c.id = $1
+        "#,
+        conversation.id,
+        chat_id
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    Ok((conversation.id, ExportChat { id: chat_id, name, is_group: conversation.is_group() }))
+}
+
+struct ExportRow {
+    message: ExportMessage,
+    /// Uploaded through our media storage, so safe to fetch
+    media_is_ours: bool,
+}
+
+/// The next page of messages the exporter can see, oldest first.
+async fn load_page(
+    state: &AppState,
+    conversation_id: Uuid,
+    user_id: Uuid,
+    after: Option<Cursor>,
+) -> Result<Vec<ExportRow>, AppError> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT m.id, m.sender_id, COALESCE(u.display_name, u.username) as "sender_name!",
+            m.created_at, m.content, m.media_url, m.is_edited, m.is_deleted, m.is_system, m.reply_to_id,
+            EXISTS(SELECT 1 FROM media md WHERE md.url = m.media_url) as "media_is_ours!",
+            (SELECT COALESCE(json_agg(json_build_object(
+                'emoji', r.emoji, 'user_id', r.user_id, 'user_name', COALESCE(ru.display_name, ru.username)
+            ) ORDER BY r.created_at), '[]')
+                FROM message_reactions r JOIN users ru ON ru.id = r.user_id
+                WHERE r.message_id = m.id) as "reactions!: SqlJson<Vec<ExportReaction>>"
+        FROM messages m
+        JOIN users u ON u.id = m.sender_id
+        WHERE m.conversation_id = $2
+            AND NOT EXISTS (SELECT 1 FROM hidden_messages h WHERE h.message_id = m.id AND h.user_id = $1)
+            AND (m.expires_at IS NULL OR m.expires_at > NOW())
+            AND ($3::timestamptz IS NULL OR (m.created_at, m.id) > ($3, $4::uuid))
+        ORDER BY m.created_at, m.id
+        LIMIT $5
+        "#,
+        user_id,
+        conversation_id,
+        after.map(|cursor| cursor.created_at),
+        after.map(|cursor| cursor.id),
+        PAGE_SIZE
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| ExportRow {
+            media_is_ours: row.media_is_ours,
+            message: ExportMessage {
+                id: row.id,
+                sender_id: row.sender_id,
+                sender_name: row.sender_name,
+                created_at: row.created_at,
+                content: if row.is_deleted { String::new() } else { row.content },
+                is_edited: row.is_edited,
+                is_deleted: row.is_deleted,
+                is_system: row.is_system,
+                reply_to_id: row.reply_to_id,
+                media: row
+                    .media_url
+                    .filter(|_| !row.is_deleted)
+                    .map(|url| ExportMedia { url, file: None }),
+                reactions: if row.is_deleted { Vec::new() } else { row.reactions.0 },
+            },
+        })
+        .collect())
+}
+
+/// Downloads a message's media into the staging directory, returning its
+/// path inside the zip. Media that can't be fetched, or doesn't fit within
+/// the size limits, stays a link.
+async fn copy_media(
+    client: &Client,
+    staging: &Path,
+    message_id: Uuid,
+    url: &str,
+    total_bytes: &mut usize,
+) -> Option<String> {
+    let mut response = match client.get(url).send().await.and_then(|r| r.error_for_status()) {
+        Ok(response) => response,
+        Err(e) => {
+            warn!("Failed to fetch media {} for export: {}", url, e);
+            return None;
+        }
+    };
+
+    let mut body = Vec::new();
+    loop {
+        match response.chunk().await {
+            Ok(Some(chunk)) => {
+                body.extend_from_slice(&chunk);
+                if body.len() > MAX_MEDIA_FILE_BYTES || *total_bytes + body.len() > MAX_MEDIA_TOTAL_BYTES {
+                    return None;
+                }
+            }
+            Ok(None) => break,
+            Err(e) => {
+                warn!("Failed to fetch media {} for export: {}", url, e);
+                return None;
+            }
+        }
+    }
+
+    let file = match media_extension(url) {
+        Some(ext) => format!("media/{}.{}", message_id, ext),
+        None => format!("media/{}", message_id),
+    };
+    if let Err(e) = tokio::fs::write(staging.join(&file), &body).await {
+        warn!("Failed to store media {} for export: {}", url, e);
+        return None;
+    }
+    *total_bytes += body.len();
+    Some(file)
+}
+
+/// Zips every file under `source`, keeping paths relative to it.
+fn zip_dir(source: &Path, archive: &Path) -> Result<(), AppError> {
+    let zip_error = |e: zip::result::ZipError| AppError::InternalServerError(format!("Failed to write zip: {}", e));
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(archive)?);
+    let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    let mut pending = vec![source.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            let name = path
+                .strip_prefix(source)
+                .map_err(|e| AppError::InternalServerError(e.to_string()))?
+                .to_string_lossy()
+                .replace('\\', "/");
+            zip.start_file(name, options).map_err(zip_error)?;
+
+            let mut file = std::fs::File::open(&path)?;
+            let mut buffer = [0u8; 64 * 1024];
+            loop {
+                let read = file.read(&mut buffer)?;
+                if read == 0 {
+                    break;
+                }
+                zip.write_all(&buffer[..read])?;
+            }
+        }
+    }
+
+    zip.finish().map_err(zip_error)?;
+    Ok(())
+}
+
+/// Deletes finished exports past their retention, files first.
+pub async fn prune_expired_exports(state: &AppState) -> Result<u64, AppError> {
+    let expired = sqlx::query!(
+        r#"
+        SELECT id, file_name FROM chat_exports
+        WHERE expires_at <= NOW()
+        "#
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    let mut pruned = 0;
+    for export in expired {
+        if let Some(file_name) = &export.file_name {
+            match tokio::fs::remove_file(export_path(state, file_name)).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    warn!("Failed to delete chat export {}: {}", export.id, e);
+                    continue;
+                }
+            }
+        }
+        sqlx::query!("DELETE FROM chat_exports WHERE id = $1", export.id)
+            .execute(&state.pool)
+            .await?;
+        pruned += 1;
+    }
+
+    Ok(pruned)
+}
@@ -0,0 +1,92 @@
+use url::Url;
+
+use crate::{
+    error::AppError,
+    models::message::{FormattingEntity, FormattingKind},
+};
+
+const MAX_FORMATTING_ENTITIES: usize = 100;
+const MAX_LINK_LENGTH: usize = 2048;
+
+/// Checks formatting entities against the content they describe.
+///
+/// Spans must lie within the content without splitting a character. Styles
+/// may nest inside one another but not partially overlap, and `code` and
+/// `pre` spans can't share any text with another entity.
+pub fn validate_formatting(content: &str, entities: &[FormattingEntity]) -> Result<(), AppError> {
+    if entities.len() > MAX_FORMATTING_ENTITIES {
+        return Err(AppError::BadRequest(format!(
+            "A message cannot have more than {} formatting entities",
+            MAX_FORMATTING_ENTITIES
+        )));
+    }
+
+    let units: Vec<u16> = content.encode_utf16().collect();
+    let splits_character = |index: usize| {
+        units.get(index).is_some_and(|unit| (0xDC00..=0xDFFF).contains(unit))
+    };
+
+    let mut spans = Vec::with_capacity(entities.len());
+    for entity in entities {
+        if entity.offset < 0 || entity.length <= 0 {
+            return Err(AppError::BadRequest("Formatting spans must have a positive length".into()));
+        }
+        let start = entity.offset as usize;
+        let end = start + entity.length as usize;
+        if end > units.len() {
+            return Err(AppError::BadRequest("Formatting span is outside the message content".into()));
+        }
+        if splits_character(start) || splits_character(end) {
+            return Err(AppError::BadRequest("Formatting span splits a character".into()));
+        }
+
+        match (entity.kind, entity.url.as_deref()) {
+            (FormattingKind::Link, Some(url)) => validate_link(url)?,
+            (FormattingKind::Link, None) => {
+                return Err(AppError::BadRequest("Link formatting requires a url".into()));
+            }
+            (_, Some(_)) => {
+                return Err(AppError::BadRequest("Only link formatting can have a url".into()));
+            }
+            (_, None) => {}
+        }
+
+        spans.push((start, end, entity.kind));
+    }
+
+    let is_code = |kind: FormattingKind| matches!(kind, FormattingKind::Code | FormattingKind::Pre);
+
+    // Outer spans first, so each span can only nest inside an earlier one
+    spans.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+    for (i, &(_, end, kind)) in spans.iter().enumerate() {
+        for &(other_start, other_end, other_kind) in &spans[i + 1..] {
+            if other_start >= end {
+                break;
+            }
+            if is_code(kind) || is_code(other_kind) {
+                return Err(AppError::BadRequest("Code formatting cannot overlap other formatting".into()));
+            }
+            if kind == other_kind {
+                return Err(AppError::BadRequest("Formatting of the same type cannot overlap".into()));
+            }
+            if other_end > end {
+                return Err(AppError::BadRequest("Formatting spans must nest, not partially overlap".into()));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_link(url: &str) -> Result<(), AppError> {
+    if url.len() > MAX_LINK_LENGTH {
+        return Err(AppError::BadRequest(format!(
+            "Link cannot exceed {} characters",
+            MAX_LINK_LENGTH
+        )));
+    }
+    match Url::parse(url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(()),
+        _ => Err(AppError::BadRequest("Links must be http or https URLs".into())),
+    }
+}
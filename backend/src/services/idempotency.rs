@@ -0,0 +1,154 @@
+use axum::http::HeaderMap;
+use sqlx::{Postgres, Transaction};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    error::AppError,
+    models::message::Message,
+};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+const MAX_KEY_LENGTH: usize = 255;
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The client message id of a REST send, from the `Idempotency-Key` header or
+/// the request body. Both may be given as long as they agree.
+pub fn client_message_id(
+    headers: &HeaderMap,
+    from_body: Option<String>,
+) -> Result<Option<String>, AppError> {
+    let from_header = headers
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .map(|value| {
+            value
+                .to_str()
+                .map(str::to_owned)
+                .map_err(|_| AppError::BadRequest("Invalid Idempotency-Key header".into()))
+        })
+        .transpose()?;
+
+    let key = match (from_header, from_body) {
+        (Some(header), Some(body)) if header != body => {
+            return Err(AppError::BadRequest(
+                "Idempotency-Key header and client_message_id don't match".into(),
+            ));
+        }
+        (header, body) => header.or(body),
+    };
+
+    if let Some(key) = &key {
+        validate_key(key)?;
+    }
+    Ok(key)
+}
+
+fn validate_key(key: &str) -> Result<(), AppError> {
+    if key.is_empty() || key.len() > MAX_KEY_LENGTH {
+        return Err(AppError::BadRequest(format!(
+            "Client message id must be between 1 and {} characters",
+            MAX_KEY_LENGTH
+        )));
+    }
+    if !key.chars().all(|c| c.is_ascii_graphic()) {
+        return Err(AppError::BadRequest("Client message id must be printable ASCII".into()));
+    }
+    Ok(())
+}
+
+/// The message a sender already sent with this client message id, if it is
+/// still within the retention window. Reusing an id in another conversation
+/// is an error rather than a retry.
+pub async fn find_sent_message(
+    state: &AppState,
+    sender_id: Uuid,
+    conversation_id: Uuid,
+    key: &str,
+) -> Result<Option<Message>, AppError> {
+    let message = sqlx::query_as!(
+        Message,
+        r#"
+        SELECT m.* FROM message_idempotency_keys k
+        JOIN messages m ON m.id = k.message_id
+        WHERE k.sender_id = $1 AND k.idempotency_key = $2
+            AND k.created_at > NOW() - make_interval(secs => $3)
+        "#,
+        sender_id,
+        key,
+        state.messaging.idempotency_window.num_seconds() as f64
+    )
+    .fetch_optional(&state.pool)
+    .await?;
+
+    match message {
+        Some(message) if message.conversation_id != conversation_id => Err(AppError::BadRequest(
+            "Client message id was already used in another chat".into(),
+        )),
+        message => Ok(message),
+    }
+}
+
+/// Records the client message id of a message being sent. Returns false when
+/// a concurrent send with the same id got there first, in which case the
+/// transaction should be rolled back and the other message returned instead.
+pub async fn claim(
+    tx: &mut Transaction<'_, Postgres>,
+    state: &AppState,
+    sender_id: Uuid,
+    key: &str,
+    message_id: Uuid,
+) -> Result<bool, AppError> {
+    // A key past the window is free to use again
+    let claimed = sqlx::query!(
+        r#"
+        INSERT INTO message_idempotency_keys (sender_id, idempotency_key, message_id, created_at)
+        VALUES ($1, $2, $3, NOW())
+        ON CONFLICT (sender_id, idempotency_key) DO UPDATE
+        SET message_id = EXCLUDED.message_id, created_at = EXCLUDED.created_at
+        WHERE message_idempotency_keys.created_at <= NOW() - make_interval(secs => $4)
+        RETURNING message_id
+        "#,
+        sender_id,
+        key,
+        message_id,
+        state.messaging.idempotency_window.num_seconds() as f64
+    )
+    .fetch_optional(&mut **tx)
+    .await?
+    .is_some();
+
+    Ok(claimed)
+}
+
+/// Drops client message ids past the retention window every hour for the
+/// lifetime of the process.
+pub fn spawn_idempotency_key_pruner(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match prune_expired_keys(&state).await {
+                Ok(pruned) if pruned > 0 => info!("Pruned {} expired idempotency keys", pruned),
+                Ok(_) => {}
+                Err(e) => error!("Idempotency key pruning failed: {}", e),
+            }
+        }
+    });
+}
+
+pub async fn prune_expired_keys(state: &AppState) -> Result<u64, AppError> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM message_idempotency_keys
+        WHERE created_at <= NOW() - make_interval(secs => $1)
+        "#,
+        state.messaging.idempotency_window.num_seconds() as f64
+    )
+    .execute(&state.pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
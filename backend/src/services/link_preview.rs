@@ -0,0 +1,479 @@
+use reqwest::{header, redirect, Client, StatusCode};
+use scraper::{Html, Selector};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::{mpsc, Semaphore};
+use tracing::{debug, error, warn};
+use url::Url;
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    error::AppError,
+    handlers::conversations::conversation_members,
+    metrics::{LINK_PREVIEWS_FETCHED, LINK_PREVIEW_FAILURES},
+    models::message::{LinkPreview, Message},
+    websocket::{fanout, validation::WebSocketMessage},
+};
+
+/// Jobs waiting for the worker; more than this and new links go without a preview
+pub const QUEUE_SIZE: usize = 1000;
+const MAX_CONCURRENT_FETCHES: usize = 8;
+/// How long a fetched preview is reused before the page is fetched again
+const PREVIEW_TTL_HOURS: i32 = 24;
+/// How long to wait before retrying a page that had no preview
+const FAILED_PREVIEW_TTL_HOURS: i32 = 1;
+const MAX_URL_LENGTH: usize = 2048;
+const MAX_TITLE_LENGTH: usize = 300;
+const MAX_DESCRIPTION_LENGTH: usize = 1000;
+const USER_AGENT: &str = "messaging-app-link-preview/1.0";
+
+#[derive(Debug, Error)]
+pub enum PreviewError {
+    #[error("Invalid URL: {0}")]
+    InvalidUrl(String),
+    #[error("Blocked address: {0}")]
+    Blocked(String),
+    #[error("Request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Unexpected status: {0}")]
+    Status(StatusCode),
+    #[error("Not an HTML page")]
+    NotHtml,
+    #[error("Too many redirects")]
+    TooManyRedirects,
+    #[error("Timed out")]
+    Timeout,
+    #[error("Page has no preview")]
+    NoPreview,
+}
+
+/// Limits on what a preview fetch may do.
+#[derive(Debug, Clone)]
+pub struct FetchPolicy {
+    /// Covers the whole fetch, redirects included
+    pub timeout: Duration,
+    /// Bytes of the page read; anything after is ignored
+    pub max_body_bytes: usize,
+    pub max_redirects: usize,
+    /// Only for tests against a local server
+    pub allow_private_networks: bool,
+}
+
+impl Default for FetchPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(5),
+            max_body_bytes: 512 * 1024,
+            max_redirects: 3,
+            allow_private_networks: false,
+        }
+    }
+}
+
+/// Fetches pages and extracts previews from them without letting a message
+/// reach internal services.
+pub struct LinkPreviewFetcher {
+    policy: FetchPolicy,
+}
+
+impl LinkPreviewFetcher {
+    pub fn new(policy: FetchPolicy) -> Self {
+        Self { policy }
+    }
+
+    pub async fn fetch(&self, url: &str) -> Result<LinkPreview, PreviewError> {
+        tokio::time::timeout(self.policy.timeout, self.fetch_page(url))
+            .await
+            .map_err(|_| PreviewError::Timeout)?
+    }
+
+    async fn fetch_page(&self, url: &str) -> Result<LinkPreview, PreviewError> {
+        let mut current = Url::parse(url).map_err(|e| PreviewError::InvalidUrl(e.to_string()))?;
+
+        for _ in 0..=self.policy.max_redirects {
+            // Redirects are followed by hand so every hop is checked, and the
+            // connection is pinned to the checked address so DNS can't change
+            // underneath it
+            let mut client = Client::builder()
+                .redirect(redirect::Policy::none())
+                .no_proxy()
+                .user_agent(USER_AGENT);
+            if let Some((host, addr)) = self.resolve(&current).await? {
+                client = client.resolve(&host, addr);
+            }
+
+            let mut response = client
+                .build()?
+                .get(current.clone())
+                .header(header::ACCEPT, "text/html,application/xhtml+xml")
+                .send()
+                .await?;
+
+            if response.status().is_redirection() {
+                let location = response
+                    .headers()
+                    .get(header::LOCATION)
+                    .and_then(|location| location.to_str().ok())
+                    .ok_or(PreviewError::Status(response.status()))?;
+                current = current
+                    .join(location)
+                    .map_err(|e| PreviewError::InvalidUrl(e.to_string()))?;
+                continue;
+            }
+            if !response.status().is_success() {
+                return Err(PreviewError::Status(response.status()));
+            }
+
+            let is_html = response
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|content_type| content_type.to_str().ok())
+                .map(|content_type| {
+                    let content_type = content_type.to_ascii_lowercase();
+                    content_type.starts_with("text/html") || content_type.starts_with("application/xhtml+xml")
+                })
+                .unwrap_or(false);
+            if !is_html {
+                return Err(PreviewError::NotHtml);
+            }
+
+            let mut body = Vec::new();
+            while let Some(chunk) = response.chunk().await? {
+                let remaining = self.policy.max_body_bytes - body.len();
+                body.extend_from_slice(&chunk[..chunk.len().min(remaining)]);
+                if body.len() >= self.policy.max_body_bytes {
+                    break;
+                }
+            }
+
+            let html = String::from_utf8_lossy(&body);
+            return parse_preview(url, &current, &html).ok_or(PreviewError::NoPreview);
+        }
+
+        Err(PreviewError::TooManyRedirects)
+    }
+
+    /// Checks that the URL points at a public address, and returns the address
+    /// to connect to when the host is a domain name.
+    async fn resolve(&self, url: &Url) -> Result<Option<(String, SocketAddr)>, PreviewError> {
+        if url.scheme() != "http" && url.scheme() != "https" {
+            return Err(PreviewError::InvalidUrl(format!("Unsupported scheme {}", url.scheme())));
+        }
+        let port = url
+            .port_or_known_default()
+            .ok_or_else(|| PreviewError::InvalidUrl("Missing port".into()))?;
+
+        match url.host() {
+            Some(url::Host::Ipv4(ip)) => self.check_ip(IpAddr::V4(ip)).map(|_| None),
+            Some(url::Host::Ipv6(ip)) => self.check_ip(IpAddr::V6(ip)).map(|_| None),
+            Some(url::Host::Domain(domain)) => {
+                let addrs: Vec<SocketAddr> = tokio::net::lookup_host((domain, port))
+                    .await
+                    .map_err(|e| PreviewError::InvalidUrl(e.to_string()))?
+                    .collect();
+                // Every address must be public, or a rebinding record could slip through
+                for addr in &addrs {
+                    self.check_ip(addr.ip())?;
+                }
+                let addr = addrs
+                    .first()
+                    .copied()
+                    .ok_or_else(|| PreviewError::InvalidUrl(format!("{} does not resolve", domain)))?;
+                Ok(Some((domain.to_string(), addr)))
+            }
+            None => Err(PreviewError::InvalidUrl("Missing host".into())),
+        }
+    }
+
+    fn check_ip(&self, ip: IpAddr) -> Result<(), PreviewError> {
+        if self.policy.allow_private_networks || is_public(ip) {
+            Ok(())
+        } else {
+            Err(PreviewError::Blocked(ip.to_string()))
+        }
+    }
+}
+
+/// Whether an address is on the public internet, as opposed to loopback,
+/// private, link-local, shared or reserved ranges.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match embedded_v4(ip) {
+            Some(ip) => is_public_v4(ip),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+/// The IPv4 address an IPv6 address routes to, for the forms that carry one:
+/// IPv4-mapped, IPv4-compatible, NAT64 (64:ff9b::/96) and 6to4 (2002::/16).
+fn embedded_v4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let segments = ip.segments();
+    let v4 = |hi: u16, lo: u16| Ipv4Addr::from(((hi as u32) << 16) | lo as u32);
+    match segments {
+        [0, 0, 0, 0, 0, 0xffff, hi, lo] | [0, 0, 0, 0, 0, 0, hi, lo] => Some(v4(hi, lo)),
+        [0x64, 0xff9b, 0, 0, 0, 0, hi, lo] => Some(v4(hi, lo)),
+        [0x2002, hi, lo, ..] => Some(v4(hi, lo)),
+        _ => None,
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || a == 0
+        || (a == 100 && (64..128).contains(&b)) // shared address space
+        || (a == 192 && b == 0 && c == 0) // IETF protocol assignments
+        || (a == 198 && (18..20).contains(&b)) // benchmarking
+        || a >= 240) // reserved
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || (first & 0xfe00) == 0xfc00 // unique local
+        || (first & 0xffc0) == 0xfe80 // link-local
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8)) // documentation
+}
+
+/// Extracts a preview from a page's OpenGraph and Twitter card tags, falling
+/// back to its title and description. `page_url` is where the page ended up
+/// after redirects, for resolving relative image URLs.
+pub fn parse_preview(url: &str, page_url: &Url, html: &str) -> Option<LinkPreview> {
+    let document = Html::parse_document(html);
+    let meta_selector = Selector::parse("meta").unwrap();
+    let title_selector = Selector::parse("title").unwrap();
+
+    let mut tags: HashMap<String, String> = HashMap::new();
+    for meta in document.select(&meta_selector) {
+        let element = meta.value();
+        let key = element.attr("property").or_else(|| element.attr("name"));
+        if let (Some(key), Some(content)) = (key, element.attr("content")) {
+            let content = content.trim();
+            if !content.is_empty() {
+                tags.entry(key.to_ascii_lowercase()).or_insert_with(|| content.to_string());
+            }
+        }
+    }
+    let tag = |keys: &[&str]| keys.iter().find_map(|key| tags.get(*key).cloned());
+
+    let title = tag(&["og:title", "twitter:title"]).or_else(|| {
+        document
+            .select(&title_selector)
+            .next()
+            .map(|title| title.text().collect::<String>().trim().to_string())
+            .filter(|title| !title.is_empty())
+    });
+    let description = tag(&["og:description", "twitter:description", "description"]);
+    let image_url = tag(&["og:image", "og:image:url", "og:image:secure_url", "twitter:image", "twitter:image:src"])
+        .and_then(|image| page_url.join(&image).ok())
+        .filter(|image| image.scheme() == "http" || image.scheme() == "https")
+        .map(String::from);
+    let site_name = tag(&["og:site_name"]);
+
+    if title.is_none() && description.is_none() && image_url.is_none() {
+        return None;
+    }
+
+    Some(LinkPreview {
+        url: url.to_string(),
+        title: title.map(|title| truncate(&title, MAX_TITLE_LENGTH)),
+        description: description.map(|description| truncate(&description, MAX_DESCRIPTION_LENGTH)),
+        image_url,
+        site_name: site_name.map(|site_name| truncate(&site_name, MAX_TITLE_LENGTH)),
+    })
+}
+
+fn truncate(value: &str, max_chars: usize) -> String {
+    value.chars().take(max_chars).collect()
+}
+
+/// The first http(s) link in a message, if any.
+pub fn first_url(content: &str) -> Option<Url> {
+    content.split_whitespace().find_map(|word| {
+        let word = word
+            .trim_start_matches(&['(', '<', '[', '"', '\''][..])
+            .trim_end_matches(&['.', ',', ';', ':', '!', '?', ')', '>', ']', '"', '\''][..]);
+        let lowered = word.get(..8)?.to_ascii_lowercase();
+        if word.len() > MAX_URL_LENGTH || !(lowered.starts_with("http://") || lowered == "https://") {
+            return None;
+        }
+        Url::parse(word).ok().filter(|url| url.host().is_some())
+    })
+}
+
+/// A message waiting for its link preview.
+#[derive(Debug, Clone)]
+pub struct PreviewJob {
+    pub message_id: Uuid,
+    pub conversation_id: Uuid,
+    /// User or group the message was sent to
+    pub receiver_id: Uuid,
+    pub url: String,
+}
+
+/// Queues a preview for the first link in a message. Sending never waits on
+/// it; if the queue is full the message simply goes without a preview.
+pub fn enqueue(state: &AppState, message: &Message) {
+    let Some(url) = first_url(&message.content) else {
+        return;
+    };
+
+    let job = PreviewJob {
+        message_id: message.id,
+        conversation_id: message.conversation_id,
+        receiver_id: message.receiver_id,
+        url: url.to_string(),
+    };
+    if let Err(e) = state.link_previews.try_send(job) {
+        warn!("Dropped link preview for message {}: {}", message.id, e);
+    }
+}
+
+/// Runs the link preview worker in the background for the lifetime of the process.
+pub fn spawn_link_preview_worker(state: Arc<AppState>, mut jobs: mpsc::Receiver<PreviewJob>) {
+    let fetcher = Arc::new(LinkPreviewFetcher::new(FetchPolicy::default()));
+    let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_FETCHES));
+
+    tokio::spawn(async move {
+        while let Some(job) = jobs.recv().await {
+            let Ok(permit) = permits.clone().acquire_owned().await else {
+                break;
+            };
+            let state = state.clone();
+            let fetcher = fetcher.clone();
+            tokio::spawn(async move {
+                if let Err(e) = attach_preview(&state, &fetcher, &job).await {
+                    error!("Link preview for message {} failed: {}", job.message_id, e);
+                }
+                drop(permit);
+            });
+        }
+    });
+}
+
+/// Attaches a preview to a message, from the cache or a fresh fetch, and
+/// tells the conversation about it.
+async fn attach_preview(
+    state: &AppState,
+    fetcher: &LinkPreviewFetcher,
+    job: &PreviewJob,
+) -> Result<(), AppError> {
+    let Some(preview) = cached_or_fetch(state, fetcher, &job.url).await? else {
+        return Ok(());
+    };
+
+    // The message may have been deleted, or edited to another link, meanwhile
+    let message = sqlx::query!(
+        "SELECT content, is_deleted FROM messages WHERE id = $1",
+        job.message_id
+    )
+    .fetch_optional(&state.pool)
+    .await?;
+    let still_linked = message.is_some_and(|message| {
+        !message.is_deleted && first_url(&message.content).is_some_and(|url| url.as_str() == job.url)
+    });
+    if !still_linked {
+        return Ok(());
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO message_link_previews (message_id, url)
+        VALUES ($1, $2)
+        ON CONFLICT (message_id) DO UPDATE SET url = EXCLUDED.url
+        "#,
+        job.message_id,
+        job.url
+    )
+    .execute(&state.pool)
+    .await?;
+
+    let members = conversation_members(state, job.conversation_id).await?;
+    fanout::send_to_users(state, &members, &WebSocketMessage::LinkPreviewReady {
+        message_id: job.message_id,
+        receiver_id: job.receiver_id,
+        preview,
+    })
+    .await
+}
+
+async fn cached_or_fetch(
+    state: &AppState,
+    fetcher: &LinkPreviewFetcher,
+    url: &str,
+) -> Result<Option<LinkPreview>, AppError> {
+    let cached = sqlx::query!(
+        r#"
+        SELECT url, title, description, image_url, site_name, is_available
+        FROM link_previews
+        WHERE url = $1
+            AND fetched_at > NOW() - make_interval(hours => CASE WHEN is_available THEN $2::int ELSE $3::int END)
+        "#,
+        url,
+        PREVIEW_TTL_HOURS,
+        FAILED_PREVIEW_TTL_HOURS
+    )
+    .fetch_optional(&state.pool)
+    .await?;
+
+    if let Some(cached) = cached {
+        return Ok(cached.is_available.then_some(LinkPreview {
+            url: cached.url,
+            title: cached.title,
+            description: cached.description,
+            image_url: cached.image_url,
+            site_name: cached.site_name,
+        }));
+    }
+
+    let preview = match fetcher.fetch(url).await {
+        Ok(preview) => {
+            LINK_PREVIEWS_FETCHED.inc();
+            Some(preview)
+        }
+        Err(e) => {
+            debug!("No link preview for {}: {}", url, e);
+            LINK_PREVIEW_FAILURES.inc();
+            None
+        }
+    };
+
+    // Failures are cached too, so a popular broken link isn't fetched for every message
+    sqlx::query!(
+        r#"
+        INSERT INTO link_previews (url, title, description, image_url, site_name, is_available, fetched_at)
+        VALUES ($1, $2, $3, $4, $5, $6, NOW())
+        ON CONFLICT (url) DO UPDATE SET
+            title = EXCLUDED.title,
+            description = EXCLUDED.description,
+            image_url = EXCLUDED.image_url,
+            site_name = EXCLUDED.site_name,
+            is_available = EXCLUDED.is_available,
+            fetched_at = EXCLUDED.fetched_at
+        "#,
+        url,
+        preview.as_ref().and_then(|preview| preview.title.clone()),
+        preview.as_ref().and_then(|preview| preview.description.clone()),
+        preview.as_ref().and_then(|preview| preview.image_url.clone()),
+        preview.as_ref().and_then(|preview| preview.site_name.clone()),
+        preview.is_some()
+    )
+    .execute(&state.pool)
+    .await?;
+
+    Ok(preview)
+}
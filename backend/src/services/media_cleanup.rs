@@ -0,0 +1,122 @@
+use sqlx::{PgPool, Postgres, Transaction};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    error::AppError,
+    metrics::{MEDIA_DELETED, MEDIA_DELETE_FAILURES},
+    services::media_storage::MediaStorage,
+};
+
+const DELETION_BATCH_SIZE: i64 = 100;
+
+#[derive(Debug, Default)]
+pub struct DeletionReport {
+    pub deleted: u64,
+    pub failed: u64,
+}
+
+/// Drops the media rows behind `media` (each a url and the user who uploaded
+/// it) and queues their storage objects for deletion, as part of the caller's
+/// transaction. Call it once whatever stopped using the media is gone: media
+/// that anything else still uses, such as a forwarded copy, a status or the
+/// preview of a reply to one, is kept. Storage itself is only touched by
+/// `delete_pending_media`, after the caller has committed. Returns how many
+/// objects were queued.
+pub async fn release_media(
+    tx: &mut Transaction<'_, Postgres>,
+    media: &[(String, Uuid)],
+) -> Result<u64, AppError> {
+    if media.is_empty() {
+        return Ok(0);
+    }
+    let (urls, owners): (Vec<String>, Vec<Uuid>) = media.iter().cloned().unzip();
+
+    let queued = sqlx::query!(
+        r#"
+        WITH released AS (
+            DELETE FROM media md
+            USING UNNEST($1::text[], $2::uuid[]) AS item(url, owner)
+            WHERE md.url = item.url AND md.user_id = item.owner
+                AND NOT EXISTS(SELECT 1 FROM messages m WHERE m.media_url = md.url)
+                AND NOT EXISTS(SELECT 1 FROM status_updates s WHERE s.media_id = md.id)
+                AND NOT EXISTS(SELECT 1 FROM status_replies r WHERE r.media_url = md.url)
+            RETURNING md.public_id
+        )
+        INSERT INTO media_deletions (public_id)
+        SELECT public_id FROM released
+        ON CONFLICT (public_id) DO NOTHING
+        "#,
+        &urls,
+        &owners
+    )
+    .execute(&mut **tx)
+    .await?
+    .rows_affected();
+
+    Ok(queued)
+}
+
+/// Removes a batch of queued media from storage. Each object is leased while
+/// it is being deleted, and one that fails waits longer before every retry.
+pub async fn delete_pending_media(
+    pool: &PgPool,
+    storage: &dyn MediaStorage,
+) -> Result<DeletionReport, AppError> {
+    let mut report = DeletionReport::default();
+
+    let due = sqlx::query!(
+        r#"
+        UPDATE media_deletions
+        SET next_attempt_at = NOW() + INTERVAL '5 minutes'
+        WHERE public_id IN (
+            SELECT public_id FROM media_deletions
+            WHERE next_attempt_at <= NOW()
+            ORDER BY next_attempt_at
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING public_id
+        "#,
+        DELETION_BATCH_SIZE
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for item in due {
+        match storage.delete(&item.public_id).await {
+            Ok(()) => {
+                sqlx::query!("DELETE FROM media_deletions WHERE public_id = $1", item.public_id)
+                    .execute(pool)
+                    .await?;
+                report.deleted += 1;
+                MEDIA_DELETED.inc();
+            }
+            Err(e) => {
+                warn!("Failed to delete media {}: {}", item.public_id, e);
+                report.failed += 1;
+                MEDIA_DELETE_FAILURES.inc();
+
+                // A minute, then doubling up to a day
+                sqlx::query!(
+                    r#"
+                    UPDATE media_deletions
+                    SET attempts = attempts + 1,
+                        next_attempt_at = NOW() + LEAST(
+                            make_interval(mins => (2 ^ LEAST(attempts, 11))::int),
+                            INTERVAL '1 day'
+                        ),
+                        last_error = $2
+                    WHERE public_id = $1
+                    "#,
+                    item.public_id,
+                    e.to_string()
+                )
+                .execute(pool)
+                .await?;
+            }
+        }
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,69 @@
+use async_trait::async_trait;
+use cloudinary::Cloudinary;
+use tracing::error;
+
+use crate::error::AppError;
+
+#[derive(Debug, Clone)]
+pub struct StoredMedia {
+    pub url: String,
+    pub public_id: String,
+}
+
+/// Where uploaded media objects live. Handlers and background workers go
+/// through this instead of talking to the storage provider directly.
+#[async_trait]
+pub trait MediaStorage: Send + Sync {
+    async fn upload(&self, data: &[u8], resource_type: &str) -> Result<StoredMedia, AppError>;
+
+    async fn delete(&self, public_id: &str) -> Result<(), AppError>;
+}
+
+pub struct CloudinaryStorage {
+    cloudinary: Cloudinary,
+}
+
+impl CloudinaryStorage {
+    pub fn from_env() -> Self {
+        Self {
+            cloudinary: Cloudinary::new(
+                std::env::var("CLOUDINARY_CLOUD_NAME").unwrap_or_default(),
+                std::env::var("CLOUDINARY_API_KEY").unwrap_or_default(),
+                std::env::var("CLOUDINARY_API_SECRET").unwrap_or_default(),
+            ),
+        }
+    }
+}
+
+#[async_trait]
+impl MediaStorage for CloudinaryStorage {
+    async fn upload(&self, data: &[u8], resource_type: &str) -> Result<StoredMedia, AppError> {
+        let result = self.cloudinary.upload()
+            .resource_type(resource_type)
+            .file(data)
+            .execute()
+            .await
+            .map_err(|e| {
+                error!("Failed to upload to Cloudinary: {}", e);
+                AppError::InternalServerError(format!("Failed to upload to Cloudinary: {}", e))
+            })?;
+
+        Ok(StoredMedia {
+            url: result.secure_url,
+            public_id: result.public_id,
+        })
+    }
+
+    async fn delete(&self, public_id: &str) -> Result<(), AppError> {
+        self.cloudinary.delete()
+            .public_id(public_id)
+            .execute()
+            .await
+            .map_err(|e| {
+                error!("Failed to delete from Cloudinary: {}", e);
+                AppError::InternalServerError(format!("Failed to delete from Cloudinary: {}", e))
+            })?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    error::AppError,
+    handlers::conversations::conversation_members,
+    metrics::MESSAGES_EXPIRED,
+    services::media_cleanup,
+    websocket::{fanout, validation::WebSocketMessage},
+};
+
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(60);
+const EXPIRY_BATCH_SIZE: i64 = 100;
+
+/// Runs the disappearing messages purge in the background for the lifetime of the process.
+pub fn spawn_message_expiry_worker(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(EXPIRY_INTERVAL);
+        loop {
+            interval.tick().await;
+            match purge_expired_messages(&state).await {
+                Ok(purged) if purged > 0 => info!("Purged {} expired messages", purged),
+                Ok(_) => {}
+                Err(e) => error!("Message expiry run failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Hard deletes expired messages in batches, along with everything hanging off
+/// them and media nothing else uses, and tells each conversation which
+/// messages are gone. Media is removed from storage once the batches commit.
+pub async fn purge_expired_messages(state: &AppState) -> Result<u64, AppError> {
+    let mut purged = 0;
+
+    loop {
+        let mut tx = state.pool.begin().await?;
+
+        let expired = sqlx::query!(
+            r#"
+            SELECT id, sender_id, receiver_id, conversation_id, media_url
+            FROM messages
+            WHERE expires_at <= NOW()
+            ORDER BY expires_at
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+            "#,
+            EXPIRY_BATCH_SIZE
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        if expired.is_empty() {
+            tx.commit().await?;
+            break;
+        }
+
+        let expired_ids: Vec<Uuid> = expired.iter().map(|message| message.id).collect();
+        let media: Vec<(String, Uuid)> = expired
+            .iter()
+            .filter_map(|message| Some((message.media_url.clone()?, message.sender_id)))
+            .collect();
+
+        // Reactions, edits and hidden markers go with the message
+        let deleted = sqlx::query!("DELETE FROM messages WHERE id = ANY($1)", &expired_ids)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        media_cleanup::release_media(&mut tx, &media).await?;
+
+        tx.commit().await?;
+
+        purged += deleted;
+        MESSAGES_EXPIRED.inc_by(deleted);
+
+        // One event per conversation, however many senders it had
+        let mut conversations: HashMap<Uuid, (Uuid, Vec<Uuid>)> = HashMap::new();
+        for message in &expired {
+            conversations
+                .entry(message.conversation_id)
+                .or_insert_with(|| (message.receiver_id, Vec::new()))
+                .1
+                .push(message.id);
+        }
+        for (conversation_id, (receiver_id, message_ids)) in conversations {
+            let members = conversation_members(state, conversation_id).await?;
+            fanout::send_to_users(state, &members, &WebSocketMessage::MessagesExpired {
+                receiver_id,
+                message_ids,
+            })
+            .await?;
+        }
+
+        // A short batch means there is nothing more to do right now
+        if (expired.len() as i64) < EXPIRY_BATCH_SIZE {
+            break;
+        }
+    }
+
+    media_cleanup::delete_pending_media(&state.pool, state.media_storage.as_ref()).await?;
+    Ok(purged)
+}
@@ -1,3 +1,17 @@
+pub mod cache;
+pub mod chat_export;
+pub mod formatting;
+pub mod idempotency;
 pub mod jwt;
+pub mod link_preview;
+pub mod media_cleanup;
+pub mod media_storage;
+pub mod message_expiry;
+pub mod presence;
+pub mod privacy;
+pub mod read_marks;
 pub mod redis;
+pub mod scheduled_messages;
+pub mod status_expiry;
+pub mod whatsapp_import;
 pub mod ws;
\ No newline at end of file
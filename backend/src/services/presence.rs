@@ -0,0 +1,105 @@
+use chrono::Utc;
+use redis::{AsyncCommands, Client, RedisError};
+use std::collections::HashSet;
+use std::time::Duration;
+use uuid::Uuid;
+
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+pub const PRESENCE_TTL: Duration = Duration::from_secs(90);
+
+/// Tracks live connections per user in Redis so every node agrees on who is
+/// online. Each user has a sorted set of connection ids scored by the time the
+/// connection expires unless it heartbeats again.
+pub struct PresenceService {
+    client: Client,
+}
+
+impl PresenceService {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    fn key(user_id: Uuid) -> String {
+        format!("presence:{}", user_id)
+    }
+
+    /// Records a new connection. Returns true when it is the user's first live
+    /// connection on any node.
+    pub async fn connect(&self, user_id: Uuid, connection_id: Uuid) -> Result<bool, RedisError> {
+        let mut conn = self.client.get_multiplexed_tokio_connection().await?;
+        let key = Self::key(user_id);
+        let now = Utc::now().timestamp();
+
+        let (live_before,): (usize,) = redis::pipe()
+            .atomic()
+            .zrembyscore(&key, "-inf", now).ignore()
+            .zcard(&key)
+            .zadd(&key, connection_id.to_string(), now + PRESENCE_TTL.as_secs() as i64).ignore()
+            .expire(&key, PRESENCE_TTL.as_secs() as usize).ignore()
+            .query_async(&mut conn)
+            .await?;
+
+        Ok(live_before == 0)
+    }
+
+    pub async fn heartbeat(&self, user_id: Uuid, connection_id: Uuid) -> Result<(), RedisError> {
+        let mut conn = self.client.get_multiplexed_tokio_connection().await?;
+        let key = Self::key(user_id);
+        let expires_at = Utc::now().timestamp() + PRESENCE_TTL.as_secs() as i64;
+
+        redis::pipe()
+            .atomic()
+            .zadd(&key, connection_id.to_string(), expires_at).ignore()
+            .expire(&key, PRESENCE_TTL.as_secs() as usize).ignore()
+            .query_async(&mut conn)
+            .await
+    }
+
+    /// Drops a connection. Returns true when the user has no live connections
+    /// left anywhere.
+    pub async fn disconnect(&self, user_id: Uuid, connection_id: Uuid) -> Result<bool, RedisError> {
+        let mut conn = self.client.get_multiplexed_tokio_connection().await?;
+        let key = Self::key(user_id);
+        let now = Utc::now().timestamp();
+
+        let (live,): (usize,) = redis::pipe()
+            .atomic()
+            .zrem(&key, connection_id.to_string()).ignore()
+            .zrembyscore(&key, "-inf", now).ignore()
+            .zcard(&key)
+            .query_async(&mut conn)
+            .await?;
+
+        Ok(live == 0)
+    }
+
+    pub async fn is_online(&self, user_id: Uuid) -> Result<bool, RedisError> {
+        let mut conn = self.client.get_multiplexed_tokio_connection().await?;
+        let live: usize = conn
+            .zcount(Self::key(user_id), Utc::now().timestamp(), "+inf")
+            .await?;
+        Ok(live > 0)
+    }
+
+    /// Returns the subset of `user_ids` with at least one live connection.
+    pub async fn online_users(&self, user_ids: &[Uuid]) -> Result<HashSet<Uuid>, RedisError> {
+        if user_ids.is_empty() {
+            return Ok(HashSet::new());
+        }
+
+        let mut conn = self.client.get_multiplexed_tokio_connection().await?;
+        let now = Utc::now().timestamp();
+        let mut pipe = redis::pipe();
+        for user_id in user_ids {
+            pipe.zcount(Self::key(*user_id), now, "+inf");
+        }
+        let counts: Vec<usize> = pipe.query_async(&mut conn).await?;
+
+        Ok(user_ids
+            .iter()
+            .zip(counts)
+            .filter(|(_, live)| *live > 0)
+            .map(|(user_id, _)| *user_id)
+            .collect())
+    }
+}
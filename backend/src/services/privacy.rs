@@ -0,0 +1,121 @@
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+use crate::models::{
+    privacy::{PrivacySetting, PrivacySettings, PrivacyVisibility},
+    user::UserResponse,
+};
+
+/// Privacy decisions between a set of profile owners and a set of viewers,
+/// loaded in a fixed number of queries regardless of how many users are involved.
+#[derive(Debug, Default)]
+pub struct PrivacyRules {
+    settings: HashMap<Uuid, PrivacySettings>,
+    /// (owner, viewer) pairs where the owner has the viewer as a contact
+    contacts: HashSet<(Uuid, Uuid)>,
+    /// (owner, setting, viewer) triples excluded by the owner
+    exceptions: HashSet<(Uuid, PrivacySetting, Uuid)>,
+}
+
+impl PrivacyRules {
+    pub async fn load(pool: &PgPool, owners: &[Uuid], viewers: &[Uuid]) -> Result<Self, sqlx::Error> {
+        let everyone: Vec<Uuid> = owners.iter().chain(viewers).copied().collect();
+
+        let settings = sqlx::query_as!(
+            PrivacySettings,
+            r#"
+            SELECT
+                user_id,
+                last_seen as "last_seen: _",
+                online as "online: _",
+                avatar as "avatar: _",
+                status as "status: _",
+                read_receipts as "read_receipts: _",
+                updated_at
+            FROM privacy_settings
+            WHERE user_id = ANY($1)
+            "#,
+            &everyone
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let contacts = sqlx::query!(
+            r#"
+            SELECT user_id, contact_id FROM contacts
+            WHERE user_id = ANY($1) AND contact_id = ANY($2)
+            "#,
+            owners,
+            viewers
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let exceptions = sqlx::query!(
+            r#"
+            SELECT user_id, setting, excluded_user_id FROM privacy_exceptions
+            WHERE user_id = ANY($1) AND excluded_user_id = ANY($2)
+            "#,
+            owners,
+            viewers
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(Self {
+            settings: settings.into_iter().map(|s| (s.user_id, s)).collect(),
+            contacts: contacts.into_iter().map(|c| (c.user_id, c.contact_id)).collect(),
+            exceptions: exceptions
+                .into_iter()
+                .filter_map(|e| {
+                    PrivacySetting::parse(&e.setting).map(|setting| (e.user_id, setting, e.excluded_user_id))
+                })
+                .collect(),
+        })
+    }
+
+    fn visibility(&self, user_id: Uuid, setting: PrivacySetting) -> PrivacyVisibility {
+        self.settings
+            .get(&user_id)
+            .map(|s| s.get(setting))
+            .unwrap_or(PrivacyVisibility::Everyone)
+    }
+
+    pub fn allows(&self, owner: Uuid, viewer: Uuid, setting: PrivacySetting) -> bool {
+        if owner == viewer {
+            return true;
+        }
+        if self.exceptions.contains(&(owner, setting, viewer)) {
+            return false;
+        }
+
+        let permitted = match self.visibility(owner, setting) {
+            PrivacyVisibility::Everyone => true,
+            PrivacyVisibility::Contacts => self.contacts.contains(&(owner, viewer)),
+            PrivacyVisibility::Nobody => false,
+        };
+
+        // Like WhatsApp, not sharing your own last seen or read receipts means
+        // you don't get to see anyone else's either
+        permitted
+            && !(setting.is_reciprocal()
+                && self.visibility(viewer, setting) == PrivacyVisibility::Nobody)
+    }
+
+    /// Blanks out every profile field the viewer is not allowed to see.
+    pub fn apply(&self, viewer: Uuid, user: &mut UserResponse) {
+        if !self.allows(user.id, viewer, PrivacySetting::LastSeen) {
+            user.last_seen = None;
+        }
+        if !self.allows(user.id, viewer, PrivacySetting::Online) {
+            user.is_online = false;
+        }
+        if !self.allows(user.id, viewer, PrivacySetting::Avatar) {
+            user.avatar_url = None;
+        }
+        if !self.allows(user.id, viewer, PrivacySetting::Status) {
+            user.status = None;
+        }
+    }
+}
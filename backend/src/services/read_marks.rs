@@ -0,0 +1,100 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Postgres, Transaction};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{AppState, error::AppError};
+
+/// Reads this close after a member's last mark extend it instead of adding
+/// another, so read times are only kept to within this window.
+const MERGE_WINDOW_SECS: f64 = 5.0 * 60.0;
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Records that a member's watermark moved from `previous` to `read_up_to`.
+/// Call it in the transaction that moves `last_read_at`, with the member's
+/// row locked.
+pub async fn record(
+    tx: &mut Transaction<'_, Postgres>,
+    conversation_id: Uuid,
+    user_id: Uuid,
+    previous: Option<DateTime<Utc>>,
+    read_up_to: DateTime<Utc>,
+) -> Result<(), AppError> {
+    let merged = sqlx::query!(
+        r#"
+        UPDATE read_marks
+        SET read_up_to = $4
+        WHERE conversation_id = $1 AND user_id = $2 AND read_up_to = $3
+            AND marked_at > NOW() - make_interval(secs => $5)
+        "#,
+        conversation_id,
+        user_id,
+        previous,
+        read_up_to,
+        MERGE_WINDOW_SECS
+    )
+    .execute(&mut **tx)
+    .await?
+    .rows_affected();
+
+    if merged == 0 {
+        sqlx::query!(
+            r#"
+            INSERT INTO read_marks (conversation_id, user_id, read_up_to)
+            VALUES ($1, $2, $3)
+            "#,
+            conversation_id,
+            user_id,
+            read_up_to
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Prunes read marks every hour for the lifetime of the process.
+pub fn spawn_read_mark_pruner(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match prune_read_marks(&state).await {
+                Ok(pruned) if pruned > 0 => info!("Pruned {} read marks", pruned),
+                Ok(_) => {}
+                Err(e) => error!("Read mark pruning failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Deletes marks that no longer give any message its read time: everything
+/// they covered from other people has expired, or there never was any.
+pub async fn prune_read_marks(state: &AppState) -> Result<u64, AppError> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM read_marks rm
+        WHERE rm.marked_at <= NOW() - make_interval(secs => $1)
+            AND NOT EXISTS(
+                SELECT 1 FROM messages m
+                WHERE m.conversation_id = rm.conversation_id
+                    AND m.sender_id <> rm.user_id
+                    AND m.created_at <= rm.read_up_to
+                    AND m.created_at > COALESCE((
+                        SELECT MAX(p.read_up_to) FROM read_marks p
+                        WHERE p.conversation_id = rm.conversation_id
+                            AND p.user_id = rm.user_id
+                            AND p.read_up_to < rm.read_up_to
+                    ), '-infinity')
+            )
+        "#,
+        MERGE_WINDOW_SECS
+    )
+    .execute(&state.pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
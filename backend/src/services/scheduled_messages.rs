@@ -0,0 +1,141 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::{
+    AppState,
+    error::AppError,
+    handlers::messages::{deliver_direct_message, deliver_group_message},
+    metrics::{SCHEDULED_MESSAGES_FAILED, SCHEDULED_MESSAGES_SENT},
+    models::message::{CreateMessageRequest, ScheduledMessage},
+    websocket::{fanout, validation::WebSocketMessage},
+};
+
+const DISPATCH_INTERVAL: Duration = Duration::from_secs(5);
+const DISPATCH_BATCH_SIZE: i64 = 50;
+
+/// Runs the scheduled message dispatcher in the background for the lifetime of the process.
+pub fn spawn_scheduled_message_dispatcher(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(DISPATCH_INTERVAL);
+        loop {
+            interval.tick().await;
+            match dispatch_due_messages(&state).await {
+                Ok(sent) if sent > 0 => info!("Sent {} scheduled messages", sent),
+                Ok(_) => {}
+                Err(e) => error!("Scheduled message dispatch failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Sends every scheduled message that is due. Rows stay locked while their
+/// batch is being sent, so replicas running at the same time pick up
+/// different messages.
+pub async fn dispatch_due_messages(state: &AppState) -> Result<u64, AppError> {
+    let mut sent = 0;
+
+    loop {
+        let mut tx = state.pool.begin().await?;
+
+        let due = sqlx::query_as!(
+            ScheduledMessage,
+            r#"
+            SELECT id, sender_id, receiver_id, is_group, content, media_url, reply_to_id, send_at,
+                   status as "status: _", last_error, created_at, updated_at,
+                   formatting as "formatting: _"
+            FROM scheduled_messages
+            WHERE status = 'pending' AND send_at <= NOW()
+            ORDER BY send_at
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+            "#,
+            DISPATCH_BATCH_SIZE
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let batch_size = due.len() as i64;
+        let mut settled = 0;
+        for scheduled in due {
+            match dispatch(state, &scheduled).await {
+                Ok(()) => {
+                    sqlx::query!("DELETE FROM scheduled_messages WHERE id = $1", scheduled.id)
+                        .execute(&mut *tx)
+                        .await?;
+                    sent += 1;
+                    settled += 1;
+                    SCHEDULED_MESSAGES_SENT.inc();
+                }
+                // The message can never be sent as it is, so the sender has to step in
+                Err(e @ (AppError::BadRequest(_) | AppError::Forbidden(_) | AppError::NotFound(_))) => {
+                    sqlx::query!(
+                        r#"
+                        UPDATE scheduled_messages
+                        SET status = 'failed', last_error = $2
+                        WHERE id = $1
+                        "#,
+                        scheduled.id,
+                        e.to_string()
+                    )
+                    .execute(&mut *tx)
+                    .await?;
+                    settled += 1;
+                    SCHEDULED_MESSAGES_FAILED.inc();
+
+                    let event = WebSocketMessage::ScheduledMessageFailed {
+                        scheduled_id: scheduled.id,
+                        error: e.to_string(),
+                    };
+                    if let Err(e) = fanout::send_to_users(state, &[scheduled.sender_id], &event).await {
+                        warn!("Failed to notify {} about scheduled message {}: {}", scheduled.sender_id, scheduled.id, e);
+                    }
+                }
+                // Anything else is retried on the next run
+                Err(e) => warn!("Failed to send scheduled message {}: {}", scheduled.id, e),
+            }
+        }
+
+        tx.commit().await?;
+
+        if batch_size < DISPATCH_BATCH_SIZE || settled == 0 {
+            return Ok(sent);
+        }
+    }
+}
+
+async fn dispatch(state: &AppState, scheduled: &ScheduledMessage) -> Result<(), AppError> {
+    // A previous attempt may have sent it and stopped before removing the row
+    let already_sent = sqlx::query!(
+        r#"SELECT 1 as "exists!" FROM messages WHERE id = $1"#,
+        scheduled.id
+    )
+    .fetch_optional(&state.pool)
+    .await?
+    .is_some();
+
+    if already_sent {
+        return Ok(());
+    }
+
+    let req = CreateMessageRequest {
+        content: scheduled.content.clone(),
+        media_url: scheduled.media_url.clone(),
+        reply_to_id: scheduled.reply_to_id,
+        status_id: None,
+        send_at: None,
+        // Mentions are parsed from the content as it stands at send time
+        mentions: None,
+        formatting: scheduled.formatting.0.clone(),
+        // Scheduled messages are sent under their own id, so retries can't duplicate them
+        client_message_id: None,
+    };
+
+    if scheduled.is_group {
+        deliver_group_message(state, scheduled.sender_id, scheduled.receiver_id, scheduled.id, req).await?;
+    } else {
+        deliver_direct_message(state, scheduled.sender_id, scheduled.receiver_id, scheduled.id, req).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    error::AppError,
+    metrics::{STATUS_EXPIRY_RUN_DURATION, STATUS_VIEWS_PURGED, STATUSES_PURGED},
+    services::{media_cleanup, media_storage::MediaStorage},
+};
+
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(60);
+const EXPIRY_BATCH_SIZE: i64 = 100;
+/// Advisory lock shared by every replica so only one purges at a time
+const STATUS_EXPIRY_LOCK_ID: i64 = 0x5354_4154_5553; // "STATUS"
+
+#[derive(Debug, Default)]
+pub struct PurgeReport {
+    pub statuses: u64,
+    pub views: u64,
+    pub media: u64,
+    pub media_failures: u64,
+}
+
+/// Runs the expiry worker in the background for the lifetime of the process.
+pub fn spawn_status_expiry_worker(pool: PgPool, storage: Arc<dyn MediaStorage>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(EXPIRY_INTERVAL);
+        loop {
+            interval.tick().await;
+            match purge_expired_statuses(&pool, storage.as_ref()).await {
+                Ok(report) if report.statuses > 0 || report.media > 0 => info!(
+                    "Purged {} expired statuses, {} views and {} media objects",
+                    report.statuses, report.views, report.media
+                ),
+                Ok(_) => {}
+                Err(e) => error!("Status expiry run failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Deletes expired statuses and their views in batches, along with media
+/// nothing else uses, then removes that media from storage.
+pub async fn purge_expired_statuses(
+    pool: &PgPool,
+    storage: &dyn MediaStorage,
+) -> Result<PurgeReport, AppError> {
+    let _timer = STATUS_EXPIRY_RUN_DURATION.start_timer();
+    let mut report = PurgeReport::default();
+
+    loop {
+        let mut tx = pool.begin().await?;
+
+        // Another replica holding the lock is already doing this run
+        let locked = sqlx::query_scalar!(
+            "SELECT pg_try_advisory_xact_lock($1) as \"locked!\"",
+            STATUS_EXPIRY_LOCK_ID
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        if !locked {
+            return Ok(report);
+        }
+
+        let expired = sqlx::query!(
+            r#"
+            SELECT s.id, s.user_id, md.url as "media_url?"
+            FROM status_updates s
+            LEFT JOIN media md ON md.id = s.media_id
+            WHERE s.expires_at <= NOW()
+            ORDER BY s.expires_at
+            LIMIT $1
+            FOR UPDATE OF s SKIP LOCKED
+            "#,
+            EXPIRY_BATCH_SIZE
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        if expired.is_empty() {
+            tx.commit().await?;
+            break;
+        }
+
+        let status_ids: Vec<Uuid> = expired.iter().map(|status| status.id).collect();
+        let media: Vec<(String, Uuid)> = expired
+            .iter()
+            .filter_map(|status| Some((status.media_url.clone()?, status.user_id)))
+            .collect();
+
+        let views = sqlx::query!(
+            "DELETE FROM status_views WHERE status_id = ANY($1)",
+            &status_ids
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        let statuses = sqlx::query!(
+            "DELETE FROM status_updates WHERE id = ANY($1)",
+            &status_ids
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        media_cleanup::release_media(&mut tx, &media).await?;
+
+        tx.commit().await?;
+
+        report.statuses += statuses;
+        report.views += views;
+        STATUSES_PURGED.inc_by(statuses);
+        STATUS_VIEWS_PURGED.inc_by(views);
+
+        if (expired.len() as i64) < EXPIRY_BATCH_SIZE {
+            break;
+        }
+    }
+
+    let deletions = media_cleanup::delete_pending_media(pool, storage).await?;
+    report.media = deletions.deleted;
+    report.media_failures = deletions.failed;
+    Ok(report)
+}
@@ -0,0 +1,321 @@
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::error::AppError;
+
+pub use crate::models::import::DateOrder;
+
+pub const MAX_IMPORTED_MESSAGES: usize = 50_000;
+/// What an imported message says in place of media that wasn't exported
+pub const MEDIA_OMITTED: &str = "<Media omitted>";
+const EDITED_MARKER: &str = "<This message was edited>";
+const MAX_UTC_OFFSET_MINUTES: i32 = 14 * 60;
+
+/// A message read from a WhatsApp `.txt` export.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedMessage {
+    /// As shown on the exporting phone, in its local time
+    pub sent_at: NaiveDateTime,
+    /// Absent for system lines such as "Alice added Bob"
+    pub sender: Option<String>,
+    pub content: String,
+    pub has_media: bool,
+    pub is_edited: bool,
+    pub is_deleted: bool,
+}
+
+/// The numbers of a date before it is known which is the day and which the month.
+#[derive(Debug, Clone, Copy)]
+struct RawDate {
+    parts: [u32; 3],
+    year_first: bool,
+}
+
+struct Entry {
+    date: RawDate,
+    time: NaiveTime,
+    twelve_hour: bool,
+    text: String,
+}
+
+/// Parses a WhatsApp chat export, as written by Android (`31/12/20, 21:41 -
+/// Alice: hi`) or iOS (`[31/12/20, 21:41:05] Alice: hi`) in any of the common
+/// date layouts. Lines that don't start with a timestamp continue the message
+/// before them.
+pub fn parse_chat(text: &str, order: Option<DateOrder>) -> Result<Vec<ParsedMessage>, AppError> {
+    let mut entries: Vec<Entry> = Vec::new();
+
+    for raw_line in text.trim_start_matches('\u{feff}').lines() {
+        let line = clean_line(raw_line);
+        let header = split_header(&line).and_then(|(stamp, rest)| Some((parse_stamp(stamp)?, rest)));
+
+        match header {
+            Some(((date, time, twelve_hour), rest)) => {
+                if entries.len() == MAX_IMPORTED_MESSAGES {
+                    return Err(AppError::BadRequest(format!(
+                        "An import cannot have more than {} messages",
+                        MAX_IMPORTED_MESSAGES
+                    )));
+                }
+                entries.push(Entry { date, time, twelve_hour, text: rest.to_string() });
+            }
+            // Anything before the first message, such as a blank line, is skipped
+            None => {
+                if let Some(last) = entries.last_mut() {
+                    last.text.push('\n');
+                    last.text.push_str(&line);
+                }
+            }
+        }
+    }
+
+    if entries.is_empty() {
+        return Err(AppError::BadRequest("No messages found; is this a WhatsApp chat export?".into()));
+    }
+
+    let order = order.unwrap_or_else(|| detect_order(&entries));
+    entries
+        .into_iter()
+        .map(|entry| {
+            let date = resolve_date(entry.date, order).ok_or_else(|| {
+                AppError::BadRequest(format!(
+                    "Invalid date {}/{}/{} in chat export",
+                    entry.date.parts[0], entry.date.parts[1], entry.date.parts[2]
+                ))
+            })?;
+            Ok(classify(date.and_time(entry.time), entry.text.trim_end()))
+        })
+        .collect()
+}
+
+/// Drops the direction marks WhatsApp scatters through exports and
+/// normalises the no-break spaces some locales put before AM/PM.
+fn clean_line(line: &str) -> String {
+    line.chars()
+        .filter(|c| !matches!(c, '\u{200e}' | '\u{200f}'))
+        .map(|c| if matches!(c, '\u{202f}' | '\u{a0}') { ' ' } else { c })
+        .collect()
+}
+
+/// Splits `[stamp] rest` (iOS) or `stamp - rest` (Android).
+fn split_header(line: &str) -> Option<(&str, &str)> {
+    if let Some(inner) = line.strip_prefix('[') {
+        let end = inner.find(']')?;
+        Some((&inner[..end], inner[end + 1..].trim_start()))
+    } else {
+        let end = line.find(" - ")?;
+        Some((&line[..end], &line[end + 3..]))
+    }
+}
+
+fn parse_stamp(stamp: &str) -> Option<(RawDate, NaiveTime, bool)> {
+    let (date, time) = stamp.split_once(", ").or_else(|| stamp.split_once(' '))?;
+    let date = parse_date(date.trim())?;
+    let (time, twelve_hour) = parse_time(time.trim())?;
+    Some((date, time, twelve_hour))
+}
+
+fn parse_date(value: &str) -> Option<RawDate> {
+    let parts: Vec<&str> = value.trim_end_matches('.').split(['/', '.', '-']).collect();
+    if parts.len() != 3 || parts.iter().any(|part| part.is_empty() || part.len() > 4 || !part.bytes().all(|b| b.is_ascii_digit())) {
+        return None;
+    }
+
+    let year_first = parts[0].len() == 4;
+    let (short, year) = if year_first { (&parts[1..], parts[0]) } else { (&parts[..2], parts[2]) };
+    if short.iter().any(|part| part.len() > 2) || !matches!(year.len(), 2 | 4) {
+        return None;
+    }
+
+    let mut numbers = [0; 3];
+    for (number, part) in numbers.iter_mut().zip(&parts) {
+        *number = part.parse().ok()?;
+    }
+    Some(RawDate { parts: numbers, year_first })
+}
+
+/// `21:41`, `21:41:05`, `9:41 PM` or `9:41 p.m.`; also says whether the clock is 12-hour.
+fn parse_time(value: &str) -> Option<(NaiveTime, bool)> {
+    let lower = value.to_ascii_lowercase();
+    let meridiem = [("am", false), ("a.m.", false), ("pm", true), ("p.m.", true)]
+        .into_iter()
+        .find_map(|(suffix, pm)| lower.strip_suffix(suffix).map(|clock| (clock.trim_end().to_string(), pm)));
+    let clock = meridiem.as_ref().map_or(lower.as_str(), |(clock, _)| clock.as_str());
+
+    let parts: Vec<&str> = clock.split(':').collect();
+    if !matches!(parts.len(), 2 | 3) || parts.iter().any(|part| part.is_empty() || part.len() > 2 || !part.bytes().all(|b| b.is_ascii_digit())) {
+        return None;
+    }
+    let mut hour: u32 = parts[0].parse().ok()?;
+    let minute: u32 = parts[1].parse().ok()?;
+    let second: u32 = parts.get(2).map_or(Some(0), |part| part.parse().ok())?;
+
+    if let Some((_, pm)) = meridiem {
+        if hour == 0 || hour > 12 {
+            return None;
+        }
+        hour = hour % 12 + if pm { 12 } else { 0 };
+    }
+    NaiveTime::from_hms_opt(hour, minute, second).map(|time| (time, meridiem.is_some()))
+}
+
+/// A first number over 12 can only be a day and a second one only a day in
+/// month-first order; otherwise 12-hour clocks suggest a US-style export.
+fn detect_order(entries: &[Entry]) -> DateOrder {
+    let dates = entries.iter().filter(|entry| !entry.date.year_first);
+    if dates.clone().any(|entry| entry.date.parts[0] > 12) {
+        DateOrder::Dmy
+    } else if dates.clone().any(|entry| entry.date.parts[1] > 12) || entries.iter().any(|entry| entry.twelve_hour) {
+        DateOrder::Mdy
+    } else {
+        DateOrder::Dmy
+    }
+}
+
+fn resolve_date(date: RawDate, order: DateOrder) -> Option<NaiveDate> {
+    let [a, b, c] = date.parts;
+    let (year, month, day) = match (date.year_first, order) {
+        (true, _) => (a, b, c),
+        (false, DateOrder::Dmy) => (c, b, a),
+        (false, DateOrder::Mdy) => (c, a, b),
+    };
+    let year = if year < 100 { 2000 + year } else { year };
+    NaiveDate::from_ymd_opt(year as i32, month, day)
+}
+
+fn classify(sent_at: NaiveDateTime, text: &str) -> ParsedMessage {
+    let Some((sender, body)) = text
+        .split_once(": ")
+        .filter(|(sender, _)| !sender.is_empty() && !sender.contains('\n'))
+    else {
+        return ParsedMessage {
+            sent_at,
+            sender: None,
+            content: text.to_string(),
+            has_media: false,
+            is_edited: false,
+            is_deleted: false,
+        };
+    };
+
+    let (body, is_edited) = match body.strip_suffix(EDITED_MARKER) {
+        Some(body) => (body.trim_end(), true),
+        None => (body, false),
+    };
+    let is_deleted = matches!(
+        body.trim(),
+        "This message was deleted" | "You deleted this message" | "This message was deleted."
+    );
+
+    let (first, caption) = body.split_once('\n').unwrap_or((body, ""));
+    let has_media = !is_deleted && is_media_placeholder(first.trim());
+    let content = if is_deleted {
+        String::new()
+    } else if has_media && caption.is_empty() {
+        MEDIA_OMITTED.to_string()
+    } else if has_media {
+        format!("{}\n{}", MEDIA_OMITTED, caption)
+    } else {
+        body.to_string()
+    };
+
+    ParsedMessage {
+        sent_at,
+        sender: Some(sender.trim().to_string()),
+        content,
+        has_media,
+        is_edited: is_edited && !is_deleted,
+        is_deleted,
+    }
+}
+
+/// `<Media omitted>` (Android), `image omitted` (iOS), `<attached: 00000012-PHOTO.jpg>`
+/// (iOS with media) or `IMG-20200101-WA0001.jpg (file attached)` (Android with media).
+fn is_media_placeholder(line: &str) -> bool {
+    line == MEDIA_OMITTED
+        || (line.starts_with("<attached: ") && line.ends_with('>'))
+        || line.ends_with(" (file attached)")
+        || matches!(
+            line,
+            "image omitted" | "video omitted" | "audio omitted" | "sticker omitted"
+                | "GIF omitted" | "document omitted" | "Contact card omitted"
+        )
+}
+
+/// Converts the exporting phone's local times to UTC, nudging messages that
+/// share a minute a microsecond apart so they keep the export's order.
+pub fn sequence_timestamps(
+    messages: &[ParsedMessage],
+    utc_offset_minutes: i32,
+) -> Result<Vec<DateTime<Utc>>, AppError> {
+    if utc_offset_minutes.abs() > MAX_UTC_OFFSET_MINUTES {
+        return Err(AppError::BadRequest("UTC offset must be within 14 hours".into()));
+    }
+    let offset = Duration::minutes(utc_offset_minutes as i64);
+
+    let mut previous: Option<DateTime<Utc>> = None;
+    Ok(messages
+        .iter()
+        .map(|message| {
+            let at = Utc.from_utc_datetime(&(message.sent_at - offset));
+            let at = match previous {
+                Some(previous) if at <= previous => previous + Duration::microseconds(1),
+                _ => at,
+            };
+            previous = Some(at);
+            at
+        })
+        .collect())
+}
+
+/// Matches sender names to chat members, first through the explicit mapping
+/// and then by display name or username, ignoring case. Names that match
+/// nobody, or more than one member, are left out.
+pub fn match_participants(
+    names: &[String],
+    explicit: &HashMap<String, Uuid>,
+    members: &[(Uuid, Vec<String>)],
+) -> HashMap<String, Uuid> {
+    names
+        .iter()
+        .filter_map(|name| {
+            if let Some(user_id) = explicit.get(name) {
+                return Some((name.clone(), *user_id));
+            }
+            let wanted = name.trim().to_lowercase();
+            let mut matches = members
+                .iter()
+                .filter(|(_, member_names)| member_names.iter().any(|n| n.trim().to_lowercase() == wanted));
+            match (matches.next(), matches.next()) {
+                (Some((user_id, _)), None) => Some((name.clone(), *user_id)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Picks out which senders of a direct chat are the importer. Everyone else
+/// is imported under a placeholder rather than the other person's account,
+/// since they never agreed to what the export says they wrote. When the
+/// other person is recognised, a single name left over must be the importer.
+pub fn importer_names(
+    names: &[String],
+    matched: &HashMap<String, Uuid>,
+    importer_id: Uuid,
+) -> Result<Vec<String>, AppError> {
+    let mine: Vec<String> = names.iter().filter(|name| matched.get(*name) == Some(&importer_id)).cloned().collect();
+    if !mine.is_empty() {
+        return Ok(mine);
+    }
+
+    let unmatched: Vec<&String> = names.iter().filter(|name| !matched.contains_key(*name)).collect();
+    match unmatched.as_slice() {
+        [] => Ok(Vec::new()),
+        [name] if names.len() > 1 => Ok(vec![(*name).clone()]),
+        _ => Err(AppError::BadRequest(format!(
+            "Map your own name to your user id in `participants`; senders: {}",
+            names.join(", ")
+        ))),
+    }
+}
@@ -0,0 +1,85 @@
+use futures::StreamExt;
+use redis::Client as RedisClient;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    error::AppError,
+    websocket::{validation::WebSocketMessage, WebSocketManager},
+};
+
+pub const FANOUT_CHANNEL: &str = "ws:fanout";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Envelope {
+    user_ids: Vec<Uuid>,
+    payload: String,
+}
+
+/// Sends an event to specific users on whichever node holds their sockets.
+pub async fn send_to_users(
+    state: &AppState,
+    user_ids: &[Uuid],
+    event: &WebSocketMessage,
+) -> Result<(), AppError> {
+    if user_ids.is_empty() {
+        return Ok(());
+    }
+
+    let payload = serde_json::to_string(event)?;
+    let envelope = serde_json::to_string(&Envelope {
+        user_ids: user_ids.to_vec(),
+        payload: payload.clone(),
+    })?;
+
+    let published = async {
+        let mut conn = state.redis.get_multiplexed_tokio_connection().await?;
+        redis::cmd("PUBLISH")
+            .arg(FANOUT_CHANNEL)
+            .arg(envelope)
+            .query_async::<_, ()>(&mut conn)
+            .await
+    }
+    .await;
+
+    if let Err(e) = published {
+        // Without Redis the event can still reach sockets on this node
+        warn!("Failed to publish fanout event, delivering locally: {}", e);
+        state.ws_manager.send_to_users(user_ids, &payload).await;
+    }
+
+    Ok(())
+}
+
+/// Relays fanout events published by any node to the sockets connected here.
+pub fn spawn_relay(redis: RedisClient, manager: Arc<WebSocketManager>) {
+    tokio::spawn(async move {
+        loop {
+            match relay(&redis, &manager).await {
+                Ok(()) => warn!("Fanout subscription closed, reconnecting"),
+                Err(e) => error!("Fanout subscription failed: {}", e),
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    });
+}
+
+async fn relay(redis: &RedisClient, manager: &WebSocketManager) -> Result<(), redis::RedisError> {
+    let mut pubsub = redis.get_async_connection().await?.into_pubsub();
+    pubsub.subscribe(FANOUT_CHANNEL).await?;
+
+    let mut messages = pubsub.on_message();
+    while let Some(msg) = messages.next().await {
+        let payload: String = msg.get_payload()?;
+        match serde_json::from_str::<Envelope>(&payload) {
+            Ok(envelope) => manager.send_to_users(&envelope.user_ids, &envelope.payload).await,
+            Err(e) => warn!("Dropping malformed fanout envelope: {}", e),
+        }
+    }
+
+    Ok(())
+}
@@ -3,42 +3,82 @@ use axum::{
         ws::{Message, WebSocket, WebSocketUpgrade},
         State,
     },
-    response::IntoResponse,
+    response::{IntoResponse, Response},
 };
 use futures::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, mpsc};
+use tracing::{error, warn};
 use uuid::Uuid;
 use crate::{
     AppState,
+    auth::AuthUser,
     error::AppError,
+    handlers::{conversations, messages::{self, Stored}, presence, receipts},
     models::{
-        message::Message as ChatMessage,
+        conversation::Conversation,
+        message::{CreateMessageRequest, Message as ChatMessage},
     },
+    services::presence::HEARTBEAT_INTERVAL,
     websocket::validation::{WebSocketMessage, WebSocketResponse},
 };
 
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
     State(state): State<Arc<AppState>>,
-) -> impl IntoResponse {
-    ws.on_upgrade(|socket| handle_socket(socket, state))
+    auth_user: AuthUser,
+) -> Response {
+    if !state.ws_manager.can_connect(auth_user.id).await {
+        return AppError::TooManyRequests("Too many open connections".into()).into_response();
+    }
+
+    ws.on_upgrade(move |socket| handle_socket(socket, state, auth_user.id))
+        .into_response()
 }
 
-async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
+async fn handle_socket(socket: WebSocket, state: Arc<AppState>, user_id: Uuid) {
     let (mut sender, mut receiver) = socket.split();
     let mut rx = state.ws_tx.subscribe();
 
-    // Spawn a task to forward messages from the broadcast channel to the WebSocket
+    // Events addressed to this user (and replies to this socket) arrive here
+    let connection_id = Uuid::new_v4();
+    let (direct_tx, mut direct_rx) = mpsc::unbounded_channel::<String>();
+    let reply_tx = direct_tx.clone();
+
+    state.ws_manager.add_connection(user_id).await;
+    state.ws_manager.register_session(user_id, connection_id, direct_tx).await;
+    if let Err(e) = presence::connection_opened(&state, user_id, connection_id).await {
+        error!("Failed to record presence for {}: {}", user_id, e);
+    }
+
+    // Spawn a task to forward broadcast and direct messages to the WebSocket
     let mut send_task = tokio::spawn(async move {
-        while let Ok(msg) = rx.recv().await {
+        loop {
+            let msg = tokio::select! {
+                Ok(msg) = rx.recv() => msg,
+                Some(msg) = direct_rx.recv() => msg,
+                else => break,
+            };
             if let Err(_) = sender.send(Message::Text(msg)).await {
                 break;
             }
         }
     });
 
+    // Keep this connection alive in the shared presence set
+    let heartbeat_state = state.clone();
+    let heartbeat_task = tokio::spawn(async move {
+        let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            if let Err(e) = heartbeat_state.presence.heartbeat(user_id, connection_id).await {
+                warn!("Presence heartbeat failed for {}: {}", user_id, e);
+            }
+        }
+    });
+
     // Spawn a task to handle incoming messages
     let state_clone = state.clone();
     let mut recv_task = tokio::spawn(async move {
@@ -48,52 +88,64 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                     if let Ok(event) = serde_json::from_str::<WebSocketMessage>(&text) {
                         if let Err(e) = event.validate() {
                             let response = WebSocketResponse::<()>::error("VALIDATION_ERROR", &e.to_string());
-                            if let Err(_) = sender.send(Message::Text(serde_json::to_string(&response).unwrap())).await {
+                            if let Err(_) = reply_tx.send(serde_json::to_string(&response).unwrap()) {
                                 break;
                             }
                             continue;
                         }
 
                         let response = match event {
+                            // The sender is always this connection's user, as for reads below
                             WebSocketMessage::DirectMessage(message) => {
-                                match handle_direct_message(&state_clone, message).await {
-                                    Ok(msg) => WebSocketResponse::success(msg),
-                                    Err(e) => WebSocketResponse::<ChatMessage>::error("MESSAGE_ERROR", &e.to_string()),
+                                match handle_direct_message(&state_clone, user_id, message).await {
+                                    Ok(msg) => serde_json::to_string(&WebSocketResponse::success(msg)),
+                                    Err(e) => serde_json::to_string(&WebSocketResponse::<ChatMessage>::error("MESSAGE_ERROR", &e.to_string())),
                                 }
                             }
                             WebSocketMessage::GroupMessage { group_id, message } => {
-                                match handle_group_message(&state_clone, group_id, message).await {
-                                    Ok(msg) => WebSocketResponse::success(msg),
-                                    Err(e) => WebSocketResponse::<ChatMessage>::error("GROUP_MESSAGE_ERROR", &e.to_string()),
+                                match handle_group_message(&state_clone, user_id, group_id, message).await {
+                                    Ok(msg) => serde_json::to_string(&WebSocketResponse::success(msg)),
+                                    Err(e) => serde_json::to_string(&WebSocketResponse::<ChatMessage>::error("GROUP_MESSAGE_ERROR", &e.to_string())),
                                 }
                             }
                             WebSocketMessage::Typing { user_id, chat_id } => {
                                 match handle_typing(&state_clone, user_id, chat_id).await {
-                                    Ok(_) => WebSocketResponse::<()>::success(()),
-                                    Err(e) => WebSocketResponse::<()>::error("TYPING_ERROR", &e.to_string()),
+                                    Ok(_) => serde_json::to_string(&WebSocketResponse::<()>::success(())),
+                                    Err(e) => serde_json::to_string(&WebSocketResponse::<()>::error("TYPING_ERROR", &e.to_string())),
                                 }
                             }
                             WebSocketMessage::GroupTyping { group_id, user_id } => {
                                 match handle_group_typing(&state_clone, group_id, user_id).await {
-                                    Ok(_) => WebSocketResponse::<()>::success(()),
-                                    Err(e) => WebSocketResponse::<()>::error("GROUP_TYPING_ERROR", &e.to_string()),
+                                    Ok(_) => serde_json::to_string(&WebSocketResponse::<()>::success(())),
+                                    Err(e) => serde_json::to_string(&WebSocketResponse::<()>::error("GROUP_TYPING_ERROR", &e.to_string())),
                                 }
                             }
-                            WebSocketMessage::Read { user_id, chat_id, message_id } => {
-                                match handle_read_receipt(&state_clone, user_id, chat_id, message_id).await {
-                                    Ok(_) => WebSocketResponse::<()>::success(()),
-                                    Err(e) => WebSocketResponse::<()>::error("READ_ERROR", &e.to_string()),
+                            // The message decides the chat, and the reader is always this connection's user
+                            WebSocketMessage::Read { message_id, .. } => {
+                                match receipts::mark_read_up_to(&state_clone, user_id, message_id).await {
+                                    Ok(_) => serde_json::to_string(&WebSocketResponse::<()>::success(())),
+                                    Err(e) => serde_json::to_string(&WebSocketResponse::<()>::error("READ_ERROR", &e.to_string())),
                                 }
                             }
-                            WebSocketMessage::GroupRead { group_id, user_id, message_id } => {
-                                match handle_group_read_receipt(&state_clone, group_id, user_id, message_id).await {
-                                    Ok(_) => WebSocketResponse::<()>::success(()),
-                                    Err(e) => WebSocketResponse::<()>::error("GROUP_READ_ERROR", &e.to_string()),
+                            WebSocketMessage::GroupRead { message_id, .. } => {
+                                match receipts::mark_read_up_to(&state_clone, user_id, message_id).await {
+                                    Ok(_) => serde_json::to_string(&WebSocketResponse::<()>::success(())),
+                                    Err(e) => serde_json::to_string(&WebSocketResponse::<()>::error("GROUP_READ_ERROR", &e.to_string())),
                                 }
                             }
+                            WebSocketMessage::Delivered { message_ids } => {
+                                match receipts::record_deliveries(&state_clone, user_id, &message_ids).await {
+                                    Ok(_) => serde_json::to_string(&WebSocketResponse::<()>::success(())),
+                                    Err(e) => serde_json::to_string(&WebSocketResponse::<()>::error("DELIVERED_ERROR", &e.to_string())),
+                                }
+                            }
+                            _ => serde_json::to_string(&WebSocketResponse::<()>::error(
+                                "UNSUPPORTED_EVENT",
+                                "This event can only be sent by the server",
+                            )),
                         };
 
-                        if let Err(_) = sender.send(Message::Text(serde_json::to_string(&response).unwrap())).await {
+                        if let Err(_) = reply_tx.send(response.unwrap()) {
                             break;
                         }
                     }
@@ -109,78 +161,74 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
         _ = (&mut send_task) => recv_task.abort(),
         _ = (&mut recv_task) => send_task.abort(),
     }
-}
+    heartbeat_task.abort();
 
-async fn handle_direct_message(state: &Arc<AppState>, message: ChatMessage) -> Result<ChatMessage, AppError> {
-    // Save message to database
-    let saved_message = sqlx::query_as!(
-        ChatMessage,
-        r#"
-        INSERT INTO messages (id, sender_id, receiver_id, content, media_url, created_at, updated_at, is_edited, is_deleted)
-        VALUES ($1, $2, $3, $4, $5, NOW(), NULL, false, false)
-        RETURNING *
-        "#,
-        message.id,
-        message.sender_id,
-        message.receiver_id,
-        message.content,
-        message.media_url
-    )
-    .fetch_one(&state.pool)
-    .await?;
-
-    // Broadcast message to all connected clients
-    state.ws_tx.send(serde_json::to_string(&WebSocketMessage::DirectMessage(saved_message.clone()))?)?;
+    state.ws_manager.unregister_session(user_id, connection_id).await;
+    state.ws_manager.remove_connection(user_id).await;
+    if let Err(e) = presence::connection_closed(&state, user_id, connection_id).await {
+        error!("Failed to record disconnect for {}: {}", user_id, e);
+    }
+}
 
-    Ok(saved_message)
+async fn handle_direct_message(
+    state: &Arc<AppState>,
+    user_id: Uuid,
+    message: ChatMessage,
+) -> Result<ChatMessage, AppError> {
+    ensure_sender(user_id, &message)?;
+    let conversation = conversations::direct_conversation(state, user_id, message.receiver_id).await?;
+    let receiver_id = message.receiver_id;
+    send_message(state, user_id, &conversation, receiver_id, message).await
 }
 
 async fn handle_group_message(
     state: &Arc<AppState>,
+    user_id: Uuid,
     group_id: Uuid,
     message: ChatMessage,
 ) -> Result<ChatMessage, AppError> {
-    // Verify user is a member of the group
-    let is_member = sqlx::query!(
-        r#"
-        SELECT 1 FROM group_members
-        WHERE group_id = $1 AND user_id = $2
-        "#,
-        group_id,
-        message.sender_id
-    )
-    .fetch_optional(&state.pool)
-    .await?
-    .is_some();
+    ensure_sender(user_id, &message)?;
+    let conversation = conversations::ensure_group_member(state, user_id, group_id).await?;
+    send_message(state, user_id, &conversation, group_id, message).await
+}
 
-    if !is_member {
-        return Err(AppError::Forbidden("Not a member of this group".into()));
+/// Messages are always sent as the connection's user; a client claiming to be
+/// someone else is turned away.
+fn ensure_sender(user_id: Uuid, message: &ChatMessage) -> Result<(), AppError> {
+    if message.sender_id != user_id {
+        return Err(AppError::Forbidden("Cannot send messages as another user".into()));
     }
+    Ok(())
+}
 
-    // Save message to database
-    let saved_message = sqlx::query_as!(
-        ChatMessage,
-        r#"
-        INSERT INTO messages (id, sender_id, receiver_id, content, media_url, created_at, updated_at, is_edited, is_deleted)
-        VALUES ($1, $2, $3, $4, $5, NOW(), NULL, false, false)
-        RETURNING *
-        "#,
-        message.id,
-        message.sender_id,
-        group_id,
-        message.content,
-        message.media_url
-    )
-    .fetch_one(&state.pool)
-    .await?;
-
-    // Broadcast message to all group members
-    state.ws_tx.send(serde_json::to_string(&WebSocketMessage::GroupMessage {
-        group_id,
-        message: saved_message.clone(),
-    })?)?;
-
-    Ok(saved_message)
+/// Sends a message the same way as the REST API. The `id` the client sends is
+/// its client message id: a retry with the same id returns the message
+/// already stored, which gets an id of its own.
+async fn send_message(
+    state: &AppState,
+    sender_id: Uuid,
+    conversation: &Conversation,
+    receiver_id: Uuid,
+    message: ChatMessage,
+) -> Result<ChatMessage, AppError> {
+    let req = CreateMessageRequest {
+        content: message.content,
+        media_url: message.media_url,
+        reply_to_id: message.reply_to_id,
+        status_id: None,
+        send_at: None,
+        mentions: None,
+        formatting: message.formatting.0,
+        client_message_id: Some(message.id.to_string()),
+    };
+
+    match messages::store_message(state, sender_id, receiver_id, conversation, Uuid::new_v4(), req).await? {
+        Stored::New(sent) => {
+            messages::announce_message(state, conversation, &sent).await?;
+            Ok(sent.message)
+        }
+        Stored::Replayed(original) => Ok(original),
+    }
 }
 
 async fn handle_typing(
@@ -197,68 +245,8 @@ async fn handle_group_typing(
     group_id: Uuid,
     user_id: Uuid,
 ) -> Result<(), AppError> {
-    // Verify user is a member of the group
-    let is_member = sqlx::query!(
-        r#"
-        SELECT 1 FROM group_members
-        WHERE group_id = $1 AND user_id = $2
-        "#,
-        group_id,
-        user_id
-    )
-    .fetch_optional(&state.pool)
-    .await?
-    .is_some();
-
-    if !is_member {
-        return Err(AppError::Forbidden("Not a member of this group".into()));
-    }
+    conversations::ensure_group_member(state, user_id, group_id).await?;
 
     state.ws_tx.send(serde_json::to_string(&WebSocketMessage::GroupTyping { group_id, user_id })?)?;
     Ok(())
 }
-
-async fn handle_read_receipt(
-    state: &Arc<AppState>,
-    user_id: Uuid,
-    chat_id: Uuid,
-    message_id: Uuid,
-) -> Result<(), AppError> {
-    state.ws_tx.send(serde_json::to_string(&WebSocketMessage::Read {
-        user_id,
-        chat_id,
-        message_id,
-    })?)?;
-    Ok(())
-}
-
-async fn handle_group_read_receipt(
-    state: &Arc<AppState>,
-    group_id: Uuid,
-    user_id: Uuid,
-    message_id: Uuid,
-) -> Result<(), AppError> {
-    // Verify user is a member of the group
-    let is_member = sqlx::query!(
-        r#"
-        SELECT 1 FROM group_members
-        WHERE group_id = $1 AND user_id = $2
-        "#,
-        group_id,
-        user_id
-    )
-    .fetch_optional(&state.pool)
-    .await?
-    .is_some();
-
-    if !is_member {
-        return Err(AppError::Forbidden("Not a member of this group".into()));
-    }
-
-    state.ws_tx.send(serde_json::to_string(&WebSocketMessage::GroupRead {
-        group_id,
-        user_id,
-        message_id,
-    })?)?;
-    Ok(())
-} 
\ No newline at end of file
@@ -1,6 +1,7 @@
 pub mod rate_limit;
 pub mod validation;
 pub mod handler;
+pub mod fanout;
 
 pub use rate_limit::WebSocketManager;
 pub use handler::handle_websocket; 
\ No newline at end of file
@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::sync::RwLock;
+use tokio::sync::{mpsc, RwLock};
 use uuid::Uuid;
 
 #[derive(Debug)]
@@ -85,10 +85,47 @@ impl ConnectionPool {
     }
 }
 
+/// Outbound channels for every socket connected to this node, keyed by user
+/// and then by connection so a user can be online from several devices.
+#[derive(Debug, Default)]
+pub struct SessionRegistry {
+    sessions: HashMap<Uuid, HashMap<Uuid, mpsc::UnboundedSender<String>>>,
+}
+
+impl SessionRegistry {
+    pub fn register(&mut self, user_id: Uuid, connection_id: Uuid, tx: mpsc::UnboundedSender<String>) {
+        self.sessions.entry(user_id).or_default().insert(connection_id, tx);
+    }
+
+    /// Removes a connection and returns how many are left for the user.
+    pub fn unregister(&mut self, user_id: Uuid, connection_id: Uuid) -> usize {
+        let remaining = match self.sessions.get_mut(&user_id) {
+            Some(connections) => {
+                connections.remove(&connection_id);
+                connections.len()
+            }
+            None => 0,
+        };
+        if remaining == 0 {
+            self.sessions.remove(&user_id);
+        }
+        remaining
+    }
+
+    pub fn send_to_user(&self, user_id: Uuid, payload: &str) {
+        if let Some(connections) = self.sessions.get(&user_id) {
+            for tx in connections.values() {
+                let _ = tx.send(payload.to_string());
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct WebSocketManager {
     rate_limiter: Arc<RwLock<RateLimiter>>,
     connection_pool: Arc<RwLock<ConnectionPool>>,
+    sessions: Arc<RwLock<SessionRegistry>>,
 }
 
 impl WebSocketManager {
@@ -99,6 +136,7 @@ impl WebSocketManager {
                 100, // 100 requests per minute
             ))),
             connection_pool: Arc::new(RwLock::new(ConnectionPool::new(3))), // 3 connections per user
+            sessions: Arc::new(RwLock::new(SessionRegistry::default())),
         }
     }
 
@@ -118,6 +156,22 @@ impl WebSocketManager {
         self.connection_pool.write().await.remove_connection(user_id);
     }
 
+    pub async fn register_session(&self, user_id: Uuid, connection_id: Uuid, tx: mpsc::UnboundedSender<String>) {
+        self.sessions.write().await.register(user_id, connection_id, tx);
+    }
+
+    pub async fn unregister_session(&self, user_id: Uuid, connection_id: Uuid) -> usize {
+        self.sessions.write().await.unregister(user_id, connection_id)
+    }
+
+    /// Delivers a payload to every local connection of the given users.
+    pub async fn send_to_users(&self, user_ids: &[Uuid], payload: &str) {
+        let sessions = self.sessions.read().await;
+        for user_id in user_ids {
+            sessions.send_to_user(*user_id, payload);
+        }
+    }
+
     pub async fn cleanup(&self) {
         self.rate_limiter.write().await.cleanup();
     }
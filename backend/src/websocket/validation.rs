@@ -1,7 +1,11 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use crate::error::AppError;
-use crate::models::message::Message;
+use crate::models::conversation::ConversationState;
+use crate::models::message::{LinkPreview, Message, MessageStatus};
+use crate::models::status::StatusResponse;
+use crate::services::formatting::validate_formatting;
 
 pub const MAX_MESSAGE_LENGTH: usize = 4096; // 4KB
 pub const MAX_EMOJI_LENGTH: usize = 8; // Maximum length for emoji reactions
@@ -22,16 +26,111 @@ pub enum WebSocketMessage {
         group_id: Uuid,
         user_id: Uuid,
     },
+    /// Marks a direct chat read up to `message_id`; relayed to the other side
+    /// when the reader shares read receipts with them
     Read {
         user_id: Uuid,
+        /// The other side of the chat, from the reader's point of view
         chat_id: Uuid,
         message_id: Uuid,
     },
+    /// Marks a group read up to `message_id`, see `Read`
     GroupRead {
         group_id: Uuid,
         user_id: Uuid,
         message_id: Uuid,
     },
+    /// Sent by clients as messages reach the device
+    Delivered {
+        message_ids: Vec<Uuid>,
+    },
+    /// Sent to the sender when a message is delivered or read
+    MessageStatusChanged {
+        /// User or group the message was sent to
+        receiver_id: Uuid,
+        status: MessageStatus,
+    },
+    MessageEdited {
+        message_id: Uuid,
+        /// User or group the message was sent to
+        receiver_id: Uuid,
+        content: String,
+        edited_at: DateTime<Utc>,
+    },
+    MessageDeleted {
+        message_id: Uuid,
+        /// User or group the message was sent to
+        receiver_id: Uuid,
+        /// False when the message was only hidden for the receiving user
+        for_everyone: bool,
+    },
+    MessagesExpired {
+        /// User or group the messages were sent to
+        receiver_id: Uuid,
+        message_ids: Vec<Uuid>,
+    },
+    DisappearingTimerChanged {
+        /// User or group whose timer changed
+        receiver_id: Uuid,
+        seconds: Option<i32>,
+        changed_by: Uuid,
+        /// System message announcing the change in the chat
+        message: Message,
+    },
+    MessagePinned {
+        message_id: Uuid,
+        /// User or group the message was sent to
+        receiver_id: Uuid,
+        pinned_by: Uuid,
+        pinned_at: DateTime<Utc>,
+    },
+    MessageUnpinned {
+        message_id: Uuid,
+        /// User or group the message was sent to
+        receiver_id: Uuid,
+        unpinned_by: Uuid,
+    },
+    /// History from another app was added to a conversation; reload it
+    HistoryImported {
+        /// User or group the messages were sent to
+        receiver_id: Uuid,
+        imported_by: Uuid,
+        message_count: usize,
+    },
+    /// Sent to my other devices when I archive, mute or pin a conversation
+    ConversationStateChanged(ConversationState),
+    LinkPreviewReady {
+        message_id: Uuid,
+        /// User or group the message was sent to
+        receiver_id: Uuid,
+        preview: LinkPreview,
+    },
+    Mentioned {
+        message_id: Uuid,
+        /// User or group the message was sent to
+        receiver_id: Uuid,
+        sender_id: Uuid,
+    },
+    MentionsRead {
+        /// Group, or the other side of a direct chat
+        chat_id: Uuid,
+    },
+    ScheduledMessageFailed {
+        scheduled_id: Uuid,
+        error: String,
+    },
+    Presence {
+        user_id: Uuid,
+        is_online: bool,
+        last_seen: Option<DateTime<Utc>>,
+    },
+    NewStatus {
+        status: StatusResponse,
+    },
+    StatusDeleted {
+        user_id: Uuid,
+        status_id: Uuid,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -95,6 +194,7 @@ impl WebSocketMessage {
                         )));
                     }
                 }
+                validate_formatting(&message.content, &message.formatting)?;
             }
             WebSocketMessage::GroupMessage { message, .. } => {
                 if message.content.is_empty() {
@@ -114,6 +214,7 @@ impl WebSocketMessage {
                         )));
                     }
                 }
+                validate_formatting(&message.content, &message.formatting)?;
             }
             WebSocketMessage::MessageReaction { emoji, .. } => {
                 if emoji.is_empty() {
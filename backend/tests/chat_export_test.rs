@@ -0,0 +1,112 @@
+use chrono::{TimeZone, Utc};
+use messaging_app::services::chat_export::{
+    download_token, render_footer, render_header, render_message, verify_download_token,
+    ExportChat, ExportFormat, ExportMedia, ExportMessage, ExportReaction,
+};
+use uuid::Uuid;
+
+fn message(content: &str) -> ExportMessage {
+    ExportMessage {
+        id: Uuid::new_v4(),
+        sender_id: Uuid::new_v4(),
+        sender_name: "Alice".into(),
+        created_at: Utc.with_ymd_and_hms(2024, 3, 5, 14, 7, 0).unwrap(),
+        content: content.into(),
+        is_edited: false,
+        is_deleted: false,
+        is_system: false,
+        reply_to_id: None,
+        media: None,
+        reactions: Vec::new(),
+    }
+}
+
+fn chat() -> ExportChat {
+    ExportChat { id: Uuid::new_v4(), name: "Team <3".into(), is_group: true }
+}
+
+#[test]
+fn test_txt_matches_whatsapp_layout() {
+    let mut photo = message("");
+    photo.media = Some(ExportMedia { url: "https://cdn.example.com/a.jpg".into(), file: None });
+    let mut deleted = message("");
+    deleted.is_deleted = true;
+    let mut system = message("Alice created group \"Team\"");
+    system.is_system = true;
+
+    assert_eq!(
+        render_message(ExportFormat::Txt, &message("hello\nsecond line"), true),
+        "05/03/2024, 14:07 - Alice: hello\nsecond line\n"
+    );
+    assert_eq!(
+        render_message(ExportFormat::Txt, &photo, false),
+        "05/03/2024, 14:07 - Alice: <Media omitted>\n"
+    );
+    assert_eq!(
+        render_message(ExportFormat::Txt, &deleted, false),
+        "05/03/2024, 14:07 - Alice: This message was deleted\n"
+    );
+    assert_eq!(
+        render_message(ExportFormat::Txt, &system, false),
+        "05/03/2024, 14:07 - Alice created group \"Team\"\n"
+    );
+}
+
+#[test]
+fn test_json_export_is_one_document() {
+    let chat = chat();
+    let mut reply = message("agreed");
+    reply.reply_to_id = Some(Uuid::new_v4());
+    reply.reactions.push(ExportReaction {
+        emoji: "👍".into(),
+        user_id: Uuid::new_v4(),
+        user_name: "Bob".into(),
+    });
+
+    let document = [
+        render_header(ExportFormat::Json, &chat, Utc::now()),
+        render_message(ExportFormat::Json, &message("first"), true),
+        render_message(ExportFormat::Json, &reply, false),
+        render_footer(ExportFormat::Json),
+    ]
+    .concat();
+
+    let parsed: serde_json::Value = serde_json::from_str(&document).unwrap();
+    assert_eq!(parsed["chat"]["name"], "Team <3");
+    assert_eq!(parsed["messages"].as_array().unwrap().len(), 2);
+    assert_eq!(parsed["messages"][1]["reply_to_id"], reply.reply_to_id.unwrap().to_string());
+    assert_eq!(parsed["messages"][1]["reactions"][0]["emoji"], "👍");
+}
+
+#[test]
+fn test_html_escapes_content_and_unsafe_links() {
+    let mut unsafe_media = message("<script>alert(1)</script>");
+    unsafe_media.media = Some(ExportMedia { url: "javascript:alert(1)".into(), file: None });
+
+    let header = render_header(ExportFormat::Html, &chat(), Utc::now());
+    let html = render_message(ExportFormat::Html, &unsafe_media, true);
+    assert!(header.contains("<title>Team &lt;3</title>"));
+    assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+    assert!(!html.contains("javascript:"));
+
+    let mut bundled = message("");
+    bundled.media = Some(ExportMedia {
+        url: "https://cdn.example.com/a.png".into(),
+        file: Some("media/a.png".into()),
+    });
+    assert!(render_message(ExportFormat::Html, &bundled, true).contains("<img src=\"media/a.png\""));
+}
+
+#[test]
+fn test_download_tokens_are_bound_to_their_export() {
+    let secret = "test-secret";
+    let export_id = Uuid::new_v4();
+
+    let token = download_token(secret, export_id, Utc::now() + chrono::Duration::minutes(5)).unwrap();
+    assert_eq!(verify_download_token(secret, &token).unwrap(), export_id);
+    assert!(verify_download_token("another-secret", &token).is_err());
+
+    let expired = download_token(secret, export_id, Utc::now() - chrono::Duration::minutes(5)).unwrap();
+    assert!(verify_download_token(secret, &expired).is_err());
+    assert!(verify_download_token(secret, "not-a-token").is_err());
+}
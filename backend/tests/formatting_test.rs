@@ -0,0 +1,82 @@
+use messaging_app::services::formatting::validate_formatting;
+use serde_json::json;
+
+fn check(content: &str, entities: serde_json::Value) -> bool {
+    // Unknown types are rejected when the request is parsed
+    let Ok(entities) = serde_json::from_value::<Vec<_>>(entities) else {
+        return false;
+    };
+    validate_formatting(content, &entities).is_ok()
+}
+
+#[test]
+fn test_accepts_nested_styles_and_links() {
+    assert!(check(
+        "bold and italic, see docs",
+        json!([
+            {"type": "bold", "offset": 0, "length": 15},
+            {"type": "italic", "offset": 9, "length": 6},
+            {"type": "link", "offset": 21, "length": 4, "url": "https://example.com/docs"},
+        ])
+    ));
+    assert!(check("no formatting", json!([])));
+}
+
+#[test]
+fn test_counts_utf16_code_units() {
+    // The emoji takes two code units, so "hi" starts at 3
+    assert!(check("😀 hi", json!([{"type": "bold", "offset": 3, "length": 2}])));
+    assert!(!check("😀 hi", json!([{"type": "bold", "offset": 3, "length": 3}])));
+    assert!(!check("😀 hi", json!([{"type": "bold", "offset": 1, "length": 2}])));
+}
+
+#[test]
+fn test_rejects_out_of_bounds_spans() {
+    assert!(!check("hello", json!([{"type": "bold", "offset": 2, "length": 4}])));
+    assert!(!check("hello", json!([{"type": "bold", "offset": -1, "length": 2}])));
+    assert!(!check("hello", json!([{"type": "bold", "offset": 1, "length": 0}])));
+}
+
+#[test]
+fn test_rejects_invalid_overlaps() {
+    // Partial overlap
+    assert!(!check(
+        "hello world",
+        json!([
+            {"type": "bold", "offset": 0, "length": 7},
+            {"type": "italic", "offset": 4, "length": 7},
+        ])
+    ));
+    // Same type
+    assert!(!check(
+        "hello world",
+        json!([
+            {"type": "bold", "offset": 0, "length": 11},
+            {"type": "bold", "offset": 0, "length": 5},
+        ])
+    ));
+    // Anything inside code
+    assert!(!check(
+        "let x = 1;",
+        json!([
+            {"type": "code", "offset": 0, "length": 10},
+            {"type": "bold", "offset": 4, "length": 1},
+        ])
+    ));
+    // Adjacent spans don't overlap
+    assert!(check(
+        "let x = 1;",
+        json!([
+            {"type": "code", "offset": 0, "length": 5},
+            {"type": "pre", "offset": 5, "length": 5},
+        ])
+    ));
+}
+
+#[test]
+fn test_links_need_a_web_url() {
+    assert!(!check("docs", json!([{"type": "link", "offset": 0, "length": 4}])));
+    assert!(!check("docs", json!([{"type": "link", "offset": 0, "length": 4, "url": "javascript:alert(1)"}])));
+    assert!(!check("docs", json!([{"type": "bold", "offset": 0, "length": 4, "url": "https://example.com"}])));
+    assert!(!check("docs", json!([{"type": "underline", "offset": 0, "length": 4}])));
+}
@@ -0,0 +1,33 @@
+use axum::http::{HeaderMap, HeaderValue};
+use messaging_app::services::idempotency::{client_message_id, IDEMPOTENCY_KEY_HEADER};
+
+fn headers(key: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(IDEMPOTENCY_KEY_HEADER, HeaderValue::from_str(key).unwrap());
+    headers
+}
+
+#[test]
+fn test_takes_key_from_header_or_body() {
+    assert_eq!(client_message_id(&HeaderMap::new(), None).unwrap(), None);
+    assert_eq!(
+        client_message_id(&headers("send-1"), None).unwrap().as_deref(),
+        Some("send-1")
+    );
+    assert_eq!(
+        client_message_id(&HeaderMap::new(), Some("send-1".into())).unwrap().as_deref(),
+        Some("send-1")
+    );
+    assert_eq!(
+        client_message_id(&headers("send-1"), Some("send-1".into())).unwrap().as_deref(),
+        Some("send-1")
+    );
+}
+
+#[test]
+fn test_rejects_conflicting_or_malformed_keys() {
+    assert!(client_message_id(&headers("send-1"), Some("send-2".into())).is_err());
+    assert!(client_message_id(&HeaderMap::new(), Some(String::new())).is_err());
+    assert!(client_message_id(&HeaderMap::new(), Some("has space".into())).is_err());
+    assert!(client_message_id(&HeaderMap::new(), Some("k".repeat(256))).is_err());
+}
@@ -0,0 +1,188 @@
+use messaging_app::services::link_preview::{
+    first_url, is_public, FetchPolicy, LinkPreviewFetcher, PreviewError,
+};
+use std::time::Duration;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const ARTICLE: &str = r#"
+<html>
+  <head>
+    <title>Fallback title</title>
+    <meta property="og:title" content="Release notes" />
+    <meta property="og:description" content="Everything new in this release." />
+    <meta property="og:image" content="/images/cover.png" />
+    <meta property="og:site_name" content="Example Blog" />
+  </head>
+  <body><p>Hello</p></body>
+</html>
+"#;
+
+/// The fixture server listens on localhost, which the default policy blocks.
+fn local_fetcher() -> LinkPreviewFetcher {
+    LinkPreviewFetcher::new(FetchPolicy {
+        allow_private_networks: true,
+        ..FetchPolicy::default()
+    })
+}
+
+fn html(body: &str) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_raw(body, "text/html; charset=utf-8")
+}
+
+#[tokio::test]
+async fn test_extracts_opengraph_tags() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/article"))
+        .respond_with(html(ARTICLE))
+        .mount(&server)
+        .await;
+
+    let url = format!("{}/article", server.uri());
+    let preview = local_fetcher().fetch(&url).await.unwrap();
+
+    assert_eq!(preview.url, url);
+    assert_eq!(preview.title.as_deref(), Some("Release notes"));
+    assert_eq!(preview.description.as_deref(), Some("Everything new in this release."));
+    assert_eq!(preview.image_url, Some(format!("{}/images/cover.png", server.uri())));
+    assert_eq!(preview.site_name.as_deref(), Some("Example Blog"));
+}
+
+#[tokio::test]
+async fn test_falls_back_to_twitter_card_and_title() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/card"))
+        .respond_with(html(
+            r#"<html><head>
+                <title> Page title </title>
+                <meta name="twitter:description" content="From the card" />
+                <meta name="twitter:image" content="https://cdn.example.com/card.jpg" />
+            </head></html>"#,
+        ))
+        .mount(&server)
+        .await;
+
+    let preview = local_fetcher().fetch(&format!("{}/card", server.uri())).await.unwrap();
+
+    assert_eq!(preview.title.as_deref(), Some("Page title"));
+    assert_eq!(preview.description.as_deref(), Some("From the card"));
+    assert_eq!(preview.image_url.as_deref(), Some("https://cdn.example.com/card.jpg"));
+    assert_eq!(preview.site_name, None);
+}
+
+#[tokio::test]
+async fn test_follows_redirects_up_to_the_limit() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/short"))
+        .respond_with(ResponseTemplate::new(301).insert_header("location", "/article"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/article"))
+        .respond_with(html(ARTICLE))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/loop"))
+        .respond_with(ResponseTemplate::new(302).insert_header("location", "/loop"))
+        .mount(&server)
+        .await;
+
+    let fetcher = local_fetcher();
+    let preview = fetcher.fetch(&format!("{}/short", server.uri())).await.unwrap();
+    assert_eq!(preview.title.as_deref(), Some("Release notes"));
+
+    let result = fetcher.fetch(&format!("{}/loop", server.uri())).await;
+    assert!(matches!(result, Err(PreviewError::TooManyRedirects)));
+}
+
+#[tokio::test]
+async fn test_blocks_private_addresses() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(html(ARTICLE))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    let fetcher = LinkPreviewFetcher::new(FetchPolicy::default());
+    let result = fetcher.fetch(&format!("{}/article", server.uri())).await;
+    assert!(matches!(result, Err(PreviewError::Blocked(_))));
+
+    let result = fetcher.fetch("http://localhost:1/").await;
+    assert!(matches!(result, Err(PreviewError::Blocked(_))));
+
+    for ip in ["10.0.0.1", "172.16.0.1", "192.168.1.1", "169.254.169.254", "100.64.0.1", "::1", "fd00::1", "::ffff:127.0.0.1", "::10.0.0.1", "64:ff9b::7f00:1", "2002:c0a8:101::1"] {
+        assert!(!is_public(ip.parse().unwrap()), "{} should be blocked", ip);
+    }
+    assert!(is_public("93.184.216.34".parse().unwrap()));
+    assert!(is_public("64:ff9b::5db8:d822".parse().unwrap()));
+}
+
+#[tokio::test]
+async fn test_rejects_non_html_and_slow_pages() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/file.zip"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "application/zip")
+                .set_body_bytes(vec![0u8; 16]),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/slow"))
+        .respond_with(html(ARTICLE).set_delay(Duration::from_secs(2)))
+        .mount(&server)
+        .await;
+
+    let result = local_fetcher().fetch(&format!("{}/file.zip", server.uri())).await;
+    assert!(matches!(result, Err(PreviewError::NotHtml)));
+
+    let fetcher = LinkPreviewFetcher::new(FetchPolicy {
+        allow_private_networks: true,
+        timeout: Duration::from_millis(200),
+        ..FetchPolicy::default()
+    });
+    let result = fetcher.fetch(&format!("{}/slow", server.uri())).await;
+    assert!(matches!(result, Err(PreviewError::Timeout)));
+}
+
+#[tokio::test]
+async fn test_stops_reading_at_the_size_limit() {
+    let server = MockServer::start().await;
+    let page = format!("{}<!-- {} -->", ARTICLE, "x".repeat(64 * 1024));
+    Mock::given(method("GET"))
+        .and(path("/big"))
+        .respond_with(html(&page))
+        .mount(&server)
+        .await;
+
+    // The head is within the limit, so the preview still comes through
+    let fetcher = LinkPreviewFetcher::new(FetchPolicy {
+        allow_private_networks: true,
+        max_body_bytes: 4 * 1024,
+        ..FetchPolicy::default()
+    });
+    let preview = fetcher.fetch(&format!("{}/big", server.uri())).await.unwrap();
+    assert_eq!(preview.title.as_deref(), Some("Release notes"));
+}
+
+#[test]
+fn test_finds_first_url_in_content() {
+    assert_eq!(
+        first_url("see (https://example.com/a?b=1), thanks").map(String::from),
+        Some("https://example.com/a?b=1".to_string())
+    );
+    assert_eq!(
+        first_url("HTTP://Example.com and https://other.com").map(String::from),
+        Some("http://example.com/".to_string())
+    );
+    assert_eq!(first_url("ftp://example.com or example.com"), None);
+}
@@ -0,0 +1,135 @@
+use chrono::{NaiveDate, TimeZone, Utc};
+use messaging_app::services::chat_export::{render_message, ExportFormat, ExportMessage};
+use messaging_app::services::whatsapp_import::{
+    importer_names, match_participants, parse_chat, sequence_timestamps, DateOrder, MEDIA_OMITTED,
+};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32, second: u32) -> chrono::NaiveDateTime {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap().and_hms_opt(hour, minute, second).unwrap()
+}
+
+#[test]
+fn test_parses_android_and_ios_layouts() {
+    let android = parse_chat("31/12/2020, 21:41 - Alice: hi", None).unwrap();
+    assert_eq!(android[0].sent_at, at(2020, 12, 31, 21, 41, 0));
+    assert_eq!(android[0].sender.as_deref(), Some("Alice"));
+    assert_eq!(android[0].content, "hi");
+
+    let ios = parse_chat("\u{feff}[12/31/20, 9:41:05\u{202f}PM] Bob: hey", None).unwrap();
+    assert_eq!(ios[0].sent_at, at(2020, 12, 31, 21, 41, 5));
+    assert_eq!(ios[0].sender.as_deref(), Some("Bob"));
+
+    let german = parse_chat("31.12.20, 21:41 - Carla: hallo", None).unwrap();
+    assert_eq!(german[0].sent_at, at(2020, 12, 31, 21, 41, 0));
+
+    let iso = parse_chat("2020-12-31 21:41 - Dan: hej", None).unwrap();
+    assert_eq!(iso[0].sent_at, at(2020, 12, 31, 21, 41, 0));
+}
+
+#[test]
+fn test_date_order_is_detected_or_taken_from_the_hint() {
+    let text = "03/04/2021, 10:00 - Alice: one\n05/04/2021, 10:00 - Alice: two";
+    assert_eq!(parse_chat(text, None).unwrap()[0].sent_at, at(2021, 4, 3, 10, 0, 0));
+    assert_eq!(parse_chat(text, Some(DateOrder::Mdy)).unwrap()[0].sent_at, at(2021, 3, 4, 10, 0, 0));
+
+    let us = "03/04/2021, 10:00 - Alice: one\n03/25/2021, 10:00 - Alice: two";
+    assert_eq!(parse_chat(us, None).unwrap()[0].sent_at, at(2021, 3, 4, 10, 0, 0));
+    assert!(parse_chat(us, Some(DateOrder::Dmy)).is_err());
+}
+
+#[test]
+fn test_multiline_system_media_and_deleted_messages() {
+    let text = "\
+01/02/2022, 08:00 - Messages and calls are end-to-end encrypted.
+01/02/2022, 08:01 - Alice: first line
+second line: still Alice
+
+01/02/2022, 08:02 - Bob: <Media omitted>
+01/02/2022, 08:03 - Bob: \u{200e}image omitted
+01/02/2022, 08:04 - Alice: This message was deleted
+01/02/2022, 08:05 - Alice: fixed it <This message was edited>";
+
+    let messages = parse_chat(text, None).unwrap();
+    assert_eq!(messages.len(), 6);
+    assert_eq!(messages[0].sender, None);
+    assert_eq!(messages[1].content, "first line\nsecond line: still Alice");
+    assert!(messages[2].has_media && messages[2].content == MEDIA_OMITTED);
+    assert!(messages[3].has_media);
+    assert!(messages[4].is_deleted && messages[4].content.is_empty());
+    assert!(messages[5].is_edited);
+    assert_eq!(messages[5].content, "fixed it");
+
+    assert!(parse_chat("just some text\nwith no timestamps", None).is_err());
+}
+
+#[test]
+fn test_reads_back_our_own_txt_export() {
+    let message = ExportMessage {
+        id: Uuid::new_v4(),
+        sender_id: Uuid::new_v4(),
+        sender_name: "Alice".into(),
+        created_at: Utc.with_ymd_and_hms(2024, 3, 5, 14, 7, 0).unwrap(),
+        content: "hello\nsecond line".into(),
+        is_edited: true,
+        is_deleted: false,
+        is_system: false,
+        reply_to_id: None,
+        media: None,
+        reactions: Vec::new(),
+    };
+
+    let parsed = parse_chat(&render_message(ExportFormat::Txt, &message, true), None).unwrap();
+    assert_eq!(parsed[0].sent_at, message.created_at.naive_utc());
+    assert_eq!(parsed[0].sender.as_deref(), Some("Alice"));
+    assert_eq!(parsed[0].content, "hello\nsecond line");
+    assert!(parsed[0].is_edited);
+}
+
+#[test]
+fn test_timestamps_keep_export_order() {
+    let text = "01/02/2022, 08:00 - Alice: a\n01/02/2022, 08:00 - Bob: b\n01/02/2022, 08:00 - Alice: c";
+    let messages = parse_chat(text, None).unwrap();
+
+    let times = sequence_timestamps(&messages, 60).unwrap();
+    assert_eq!(times[0], Utc.with_ymd_and_hms(2022, 2, 1, 7, 0, 0).unwrap());
+    assert!(times[0] < times[1] && times[1] < times[2]);
+    assert!(sequence_timestamps(&messages, 15 * 60).is_err());
+}
+
+#[test]
+fn test_participants_match_by_name_or_mapping() {
+    let (alice, bob, other_bob) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    let members = vec![
+        (alice, vec!["Alice Smith".to_string(), "alice".to_string()]),
+        (bob, vec!["Bob".to_string(), "bob1".to_string()]),
+        (other_bob, vec!["bob".to_string(), "bob2".to_string()]),
+    ];
+    let names = vec!["ALICE".to_string(), "Bob".to_string(), "Mum".to_string()];
+
+    let matched = match_participants(&names, &HashMap::new(), &members);
+    assert_eq!(matched.get("ALICE"), Some(&alice));
+    assert_eq!(matched.get("Bob"), None);
+    assert_eq!(matched.get("Mum"), None);
+
+    let explicit = HashMap::from([("Bob".to_string(), bob)]);
+    assert_eq!(match_participants(&names, &explicit, &members).get("Bob"), Some(&bob));
+}
+
+#[test]
+fn test_direct_imports_keep_only_the_importer_on_a_real_account() {
+    let (me, peer) = (Uuid::new_v4(), Uuid::new_v4());
+    let names = vec!["Me".to_string(), "Peer".to_string()];
+
+    // Lines matched to the other person still don't get their account
+    let matched = HashMap::from([("Me".to_string(), me), ("Peer".to_string(), peer)]);
+    assert_eq!(importer_names(&names, &matched, me).unwrap(), vec!["Me".to_string()]);
+
+    let matched = HashMap::from([("Peer".to_string(), peer)]);
+    assert_eq!(importer_names(&names, &matched, me).unwrap(), vec!["Me".to_string()]);
+
+    // Nothing says which of two unknown names is mine
+    assert!(importer_names(&names, &HashMap::new(), me).is_err());
+    assert!(importer_names(&names[..1], &HashMap::new(), me).is_err());
+}